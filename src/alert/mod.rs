@@ -0,0 +1,245 @@
+mod notify;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time;
+
+use crate::config::{AlertMetric, AlertRule, AlertsConfig};
+use crate::model::PacketState;
+use crate::source::{Source, Sources};
+use notify::{Event, Notifier};
+
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(10);
+
+fn now_micros() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
+}
+
+/// Per-rule state machine. Timestamps are microseconds since epoch.
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Inactive,
+    /// Condition holding since, waiting out `for`.
+    Pending(u128),
+    Firing(u128),
+    /// Clear since `since`, waiting out `for` before resolving.
+    Resolving { fired: u128, since: u128 },
+}
+
+impl State {
+    fn firing_since(self) -> Option<u128> {
+        match self {
+            State::Firing(fired) | State::Resolving { fired, .. } => Some(fired),
+            _ => None,
+        }
+    }
+}
+
+/// Advance `state` with a new value. Firing starts once the condition held
+/// for `for`; resolving needs the value back past `clear` for as long.
+fn step(rule: &AlertRule, state: State, value: f64, now: u128) -> State {
+    let bad = rule.comparison.holds(value, rule.threshold);
+    let still_bad = rule.comparison.holds(value, rule.clear);
+    let waited = |since: u128| now.saturating_sub(since) >= rule.for_micros;
+    match state {
+        State::Inactive | State::Pending(_) if !bad => State::Inactive,
+        State::Inactive if waited(now) => State::Firing(now),
+        State::Inactive => State::Pending(now),
+        State::Pending(since) if waited(since) => State::Firing(since),
+        State::Pending(_) => state,
+        State::Firing(fired) | State::Resolving { fired, .. } if still_bad => State::Firing(fired),
+        State::Firing(_) if waited(now) => State::Inactive,
+        State::Firing(fired) => State::Resolving { fired, since: now },
+        State::Resolving { since, .. } if waited(since) => State::Inactive,
+        State::Resolving { .. } => state,
+    }
+}
+
+/// Current value of `rule`'s metric, or None without data to judge by.
+/// `started` is when this process started monitoring.
+async fn measure(rule: &AlertRule, source: &Source, started: u128, now: u128) -> Option<f64> {
+    if rule.metric == AlertMetric::Mtu {
+        return source.mtu_history.lock().await.back().map(|&(_, mtu)| mtu as f64);
+    }
+    let mut history = source.history.lock().await;
+    history.refresh();
+
+    // Only settled packets within the window: pending ones would read as lost.
+    let settled_before = now.saturating_sub(history.loss_timeout() as u128);
+
+    if rule.metric == AlertMetric::Silence {
+        // Replies from before a restart say nothing about the time the
+        // process was down: silence counts at the earliest from the first
+        // packet of this run, once it has had its chance to be answered.
+        let first = history.range(started, u128::MAX).next()?.timestamp;
+        if first >= settled_before {
+            return None;
+        }
+        let last_reply = history
+            .iter()
+            .rev()
+            .find(|p| !p.duplicate && (p.is_received() || p.state == PacketState::Late))
+            .map(|p| p.timestamp + p.latency as u128);
+        let since = last_reply.map_or(first, |reply| reply.max(first));
+        return Some(now.saturating_sub(since) as f64 / 1e6);
+    }
+
+    let window_start = settled_before.saturating_sub(rule.window_micros);
+    let mut sent = 0u64;
+    let mut lost = 0u64;
+    let mut rtts = Vec::new();
+    for p in history.iter().rev() {
+        if p.timestamp >= settled_before || p.duplicate || p.is_pending() || p.is_failed() {
+            continue;
+        }
+        if p.timestamp < window_start {
+            break;
+        }
+        sent += 1;
+        if p.is_lost() {
+            lost += 1;
+        } else {
+            rtts.push(p.latency);
+        }
+    }
+    match rule.metric {
+        AlertMetric::Loss => (sent > 0).then(|| 100.0 * lost as f64 / sent as f64),
+        AlertMetric::Jitter => (rtts.len() >= 2).then(|| {
+            let diffs: u64 = rtts.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+            diffs as f64 / (rtts.len() - 1) as f64 / 1000.0
+        }),
+        _ => {
+            rtts.sort_unstable();
+            rtts.get(rtts.len() / 2).map(|&rtt| rtt as f64 / 1000.0)
+        }
+    }
+}
+
+/// Evaluate every rule periodically and notify when alerts fire or resolve.
+///
+/// Sources are looked up on every evaluation, so rules on ping targets added
+/// at runtime start evaluating once the target exists.
+pub async fn start_alerting(config: AlertsConfig, sources: Sources) {
+    let rules = &config.rules;
+    for rule in rules {
+        if sources.get(&rule.source).is_none() {
+            eprintln!("Alert '{}': unknown source '{}', skipped until it is added", rule.name, rule.source);
+        }
+    }
+    let notifier = Notifier::new(&config);
+    if notifier.is_none() {
+        println!("No alert notification channel configured; alerts are only logged");
+    }
+    println!("Evaluating {} alert rule(s)", rules.len());
+
+    let mut states = vec![State::Inactive; rules.len()];
+    let started = now_micros();
+    let mut interval = time::interval(EVALUATION_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = now_micros();
+        for (rule, state) in rules.iter().zip(states.iter_mut()) {
+            let Some(source) = sources.get(&rule.source) else { continue };
+            let Some(value) = measure(rule, &source, started, now).await else { continue };
+            let old = *state;
+            *state = step(rule, old, value, now);
+            let event = match (old.firing_since(), state.firing_since()) {
+                (None, Some(started)) => Event { rule, firing: true, value, started, now },
+                (Some(started), None) => Event { rule, firing: false, value, started, now },
+                _ => continue,
+            };
+            println!("{}: {}", event.title(), event.message());
+            if let Some(notifier) = &notifier {
+                notifier.send(&event).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Comparison;
+    use crate::test_http;
+
+    const SECOND: u128 = 1_000_000;
+
+    /// `loopback loss > 5% for 30s clear 2`.
+    fn loss_rule() -> AlertRule {
+        AlertRule {
+            name: "loopback loss".to_string(),
+            expr: "loopback loss > 5 for 30s clear 2".to_string(),
+            source: "loopback".to_string(),
+            metric: AlertMetric::Loss,
+            comparison: Comparison::Above,
+            threshold: 5.0,
+            clear: 2.0,
+            window_micros: 300 * SECOND,
+            for_micros: 30 * SECOND,
+        }
+    }
+
+    #[test]
+    fn fires_holds_and_resolves_with_hysteresis() {
+        let rule = loss_rule();
+        let t0 = 1_000 * SECOND;
+        let mut state = State::Inactive;
+        let mut at = |value: f64, now: u128| {
+            state = step(&rule, state, value, now);
+            state
+        };
+
+        // Over the threshold: pending until it has held for 30 s.
+        assert!(matches!(at(7.0, t0), State::Pending(since) if since == t0));
+        assert!(matches!(at(7.0, t0 + 20 * SECOND), State::Pending(_)));
+        // A dip below the threshold before then starts over.
+        assert!(matches!(at(4.0, t0 + 25 * SECOND), State::Inactive));
+        assert!(matches!(at(7.0, t0 + 30 * SECOND), State::Pending(_)));
+        assert!(matches!(at(7.0, t0 + 60 * SECOND), State::Firing(fired) if fired == t0 + 30 * SECOND));
+
+        // Between clear and threshold: still firing.
+        assert!(matches!(at(3.0, t0 + 70 * SECOND), State::Firing(_)));
+        // Past clear: resolving, and back to firing if it comes back.
+        assert!(matches!(at(1.0, t0 + 80 * SECOND), State::Resolving { since, .. } if since == t0 + 80 * SECOND));
+        assert!(matches!(at(3.0, t0 + 90 * SECOND), State::Firing(fired) if fired == t0 + 30 * SECOND));
+        // Clear for 30 s: resolved.
+        assert!(matches!(at(1.0, t0 + 100 * SECOND), State::Resolving { .. }));
+        assert!(matches!(at(1.0, t0 + 120 * SECOND), State::Resolving { .. }));
+        assert!(matches!(at(1.0, t0 + 130 * SECOND), State::Inactive));
+    }
+
+    #[tokio::test]
+    async fn posts_events_to_the_webhook() {
+        let (listener, url) = test_http::bind().await;
+        let receiver = tokio::spawn(test_http::receive_one(listener));
+
+        let rule = loss_rule();
+        let notifier = Notifier::new(&AlertsConfig {
+            webhook_url: Some(format!("{}/hook", url)),
+            ..AlertsConfig::default()
+        })
+        .unwrap();
+        let event = Event { rule: &rule, firing: true, value: 7.5, started: 1_000 * SECOND, now: 1_030 * SECOND };
+        notifier.send(&event).await;
+
+        let posted = receiver.await.unwrap();
+        assert!(posted.head.starts_with("post /hook "));
+        assert!(posted.head.contains("content-type: application/json"));
+        let body = String::from_utf8(posted.body).unwrap();
+        assert!(body.starts_with('{') && body.ends_with('}'));
+        for field in [
+            r#""status":"firing""#,
+            r#""alert":"loopback loss""#,
+            r#""source":"loopback""#,
+            r#""value":7.5"#,
+            r#""threshold":5"#,
+            r#""comparison":">""#,
+            r#""started_ms":1000000"#,
+            r#""timestamp_ms":1030000"#,
+        ] {
+            assert!(body.contains(field), "{} missing from {}", field, body);
+        }
+    }
+}
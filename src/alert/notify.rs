@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use crate::config::{AlertRule, AlertsConfig, GotifyConfig, NtfyConfig};
+use crate::json;
+
+/// An alert changing state.
+pub struct Event<'a> {
+    pub rule: &'a AlertRule,
+    pub firing: bool,
+    pub value: f64,
+    /// When the condition started holding, microseconds since epoch.
+    pub started: u128,
+    pub now: u128,
+}
+
+impl Event<'_> {
+    fn status(&self) -> &'static str {
+        if self.firing {
+            "firing"
+        } else {
+            "resolved"
+        }
+    }
+
+    pub fn title(&self) -> String {
+        format!("[{}] {}", self.status().to_uppercase(), self.rule.name)
+    }
+
+    /// e.g. `loopback loss 7.31% (rule: loopback loss > 5% over 5m)`.
+    pub fn message(&self) -> String {
+        format!(
+            "{} {} {:.2}{} (rule: {})",
+            self.rule.source,
+            self.rule.metric.name(),
+            self.value,
+            self.rule.metric.unit(),
+            self.rule.expr
+        )
+    }
+
+    fn to_json(&self) -> String {
+        json::object(&[
+            ("status", json::string(self.status())),
+            ("alert", json::string(&self.rule.name)),
+            ("rule", json::string(&self.rule.expr)),
+            ("source", json::string(&self.rule.source)),
+            ("value", json::number(self.value)),
+            ("threshold", json::number(self.rule.threshold)),
+            ("comparison", json::string(self.rule.comparison.symbol())),
+            ("started_ms", (self.started / 1000).to_string()),
+            ("timestamp_ms", (self.now / 1000).to_string()),
+            ("message", json::string(&self.message())),
+        ])
+    }
+}
+
+/// Delivers events to every configured channel.
+pub struct Notifier {
+    client: reqwest::Client,
+    webhook_url: Option<String>,
+    ntfy: Option<NtfyConfig>,
+    gotify: Option<GotifyConfig>,
+}
+
+impl Notifier {
+    pub fn new(config: &AlertsConfig) -> Option<Self> {
+        if config.webhook_url.is_none() && config.ntfy.is_none() && config.gotify.is_none() {
+            return None;
+        }
+        match reqwest::Client::builder().timeout(Duration::from_secs(10)).build() {
+            Ok(client) => Some(Notifier {
+                client,
+                webhook_url: config.webhook_url.clone(),
+                ntfy: config.ntfy.clone(),
+                gotify: config.gotify.clone(),
+            }),
+            Err(e) => {
+                eprintln!("Alerts: cannot build HTTP client: {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn send(&self, event: &Event<'_>) {
+        if let Some(url) = &self.webhook_url {
+            let request = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(event.to_json());
+            deliver("webhook", request).await;
+        }
+        if let Some(ntfy) = &self.ntfy {
+            self.ntfy(ntfy, event).await;
+        }
+        if let Some(gotify) = &self.gotify {
+            self.gotify(gotify, event).await;
+        }
+    }
+
+    async fn ntfy(&self, ntfy: &NtfyConfig, event: &Event<'_>) {
+        let priority = match (&ntfy.priority, event.firing) {
+            (Some(p), true) => p.as_str(),
+            (None, true) => "high",
+            (_, false) => "default",
+        };
+        let tags = if event.firing { "warning" } else { "white_check_mark" };
+        let mut request = self
+            .client
+            .post(&ntfy.url)
+            .header("Title", event.title())
+            .header("Priority", priority)
+            .header("Tags", tags)
+            .body(event.message());
+        if let Some(token) = &ntfy.token {
+            let Some(token) = token.resolve() else { return };
+            request = request.bearer_auth(token);
+        }
+        deliver("ntfy", request).await;
+    }
+
+    async fn gotify(&self, gotify: &GotifyConfig, event: &Event<'_>) {
+        let Some(token) = gotify.token.resolve() else { return };
+        let body = json::object(&[
+            ("title", json::string(&event.title())),
+            ("message", json::string(&event.message())),
+            ("priority", if event.firing { "8" } else { "4" }.to_string()),
+        ]);
+        let request = self
+            .client
+            .post(format!("{}/message", gotify.url.trim_end_matches('/')))
+            .header("X-Gotify-Key", token)
+            .header("Content-Type", "application/json")
+            .body(body);
+        deliver("Gotify", request).await;
+    }
+}
+
+async fn deliver(channel: &str, request: reqwest::RequestBuilder) {
+    match request.send().await {
+        Ok(r) if r.status().is_success() => {}
+        Ok(r) => eprintln!("Alert {} notification failed: HTTP {}", channel, r.status()),
+        Err(e) => eprintln!("Alert {} notification error: {}", channel, e),
+    }
+}
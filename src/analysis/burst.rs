@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+
+/// Upper bounds of the loss-run-length histogram buckets; a final overflow
+/// bucket catches longer runs.
+pub const BURST_BUCKETS: [u64; 9] = [1, 2, 3, 4, 8, 16, 32, 64, 128];
+
+fn bucket(len: u64) -> usize {
+    BURST_BUCKETS.partition_point(|&bound| bound < len)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Run {
+    lost: bool,
+    len: u64,
+}
+
+/// Run-length encoding of a loss sequence, with aggregates kept up to date as
+/// packets are appended at the back and expire from the front.
+#[derive(Debug, Default)]
+pub struct BurstTracker {
+    runs: VecDeque<Run>,
+    lost: u64,
+    received: u64,
+    loss_runs: u64,
+    gap_runs: u64,
+    /// Number of loss runs per length bucket.
+    loss_run_buckets: [u64; BURST_BUCKETS.len() + 1],
+}
+
+/// Loss runs counted as they end, so that the counts never go down; the run
+/// still open is left out until a received packet closes it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LossRunCounter {
+    open: u64,
+    pub runs: u64,
+    /// Packets lost in the counted runs.
+    pub lost: u64,
+    pub buckets: [u64; BURST_BUCKETS.len() + 1],
+}
+
+impl LossRunCounter {
+    pub fn push(&mut self, lost: bool) {
+        if lost {
+            self.open += 1;
+        } else if self.open > 0 {
+            self.runs += 1;
+            self.lost += self.open;
+            self.buckets[bucket(self.open)] += 1;
+            self.open = 0;
+        }
+    }
+}
+
+/// Loss-burst summary over a tracked sequence.
+///
+/// `p` and `r` are the transition probabilities of a two-state Gilbert model
+/// (good→bad and bad→good), fitted from run lengths with every packet lost in
+/// the bad state and none in the good state.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BurstStats {
+    pub packets: u64,
+    pub lost: u64,
+    pub loss_runs: u64,
+    pub loss_run_buckets: [u64; BURST_BUCKETS.len() + 1],
+    pub mean_burst_length: Option<f64>,
+    pub mean_gap_length: Option<f64>,
+    pub gilbert_p: Option<f64>,
+    pub gilbert_r: Option<f64>,
+}
+
+impl BurstStats {
+    /// Stationary probability of the bad state, p / (p + r).
+    pub fn pi_bad(&self) -> Option<f64> {
+        let (p, r) = (self.gilbert_p?, self.gilbert_r?);
+        (p + r > 0.0).then(|| p / (p + r))
+    }
+}
+
+impl BurstTracker {
+    fn open(&mut self, lost: bool) {
+        self.runs.push_back(Run { lost, len: 1 });
+        if lost {
+            self.loss_runs += 1;
+            self.loss_run_buckets[bucket(1)] += 1;
+        } else {
+            self.gap_runs += 1;
+        }
+    }
+
+    fn resize(&mut self, run: Run, new_len: u64) {
+        if run.lost {
+            self.loss_run_buckets[bucket(run.len)] -= 1;
+            if new_len > 0 {
+                self.loss_run_buckets[bucket(new_len)] += 1;
+            } else {
+                self.loss_runs -= 1;
+            }
+        } else if new_len == 0 {
+            self.gap_runs -= 1;
+        }
+    }
+
+    /// Append the outcome of the next packet.
+    pub fn push(&mut self, lost: bool) {
+        if lost {
+            self.lost += 1;
+        } else {
+            self.received += 1;
+        }
+        match self.runs.back().copied() {
+            Some(run) if run.lost == lost => {
+                self.resize(run, run.len + 1);
+                self.runs.back_mut().unwrap().len += 1;
+            }
+            _ => self.open(lost),
+        }
+    }
+
+    /// Forget the oldest packet.
+    pub fn pop_front(&mut self) {
+        let Some(run) = self.runs.front().copied() else { return };
+        if run.lost {
+            self.lost -= 1;
+        } else {
+            self.received -= 1;
+        }
+        self.resize(run, run.len - 1);
+        if run.len == 1 {
+            self.runs.pop_front();
+        } else {
+            self.runs.front_mut().unwrap().len -= 1;
+        }
+    }
+
+    pub fn stats(&self) -> BurstStats {
+        let ratio = |num: u64, den: u64| (den > 0).then(|| num as f64 / den as f64);
+        BurstStats {
+            packets: self.lost + self.received,
+            lost: self.lost,
+            loss_runs: self.loss_runs,
+            loss_run_buckets: self.loss_run_buckets,
+            mean_burst_length: ratio(self.lost, self.loss_runs),
+            mean_gap_length: ratio(self.received, self.gap_runs),
+            // Each loss run is entered from the good state and each gap run from
+            // the bad state, except possibly the very first run.
+            gilbert_p: ratio(self.loss_runs, self.received).map(|p| p.min(1.0)),
+            gilbert_r: ratio(self.gap_runs, self.lost).map(|r| r.min(1.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(sequence: &[bool]) -> BurstTracker {
+        let mut tracker = BurstTracker::default();
+        for &lost in sequence {
+            tracker.push(lost);
+        }
+        tracker
+    }
+
+    const G: bool = false;
+    const L: bool = true;
+
+    #[test]
+    fn fits_the_gilbert_model_of_a_short_sequence() {
+        // G G L L G G G L, read as a cycle: from the good state 3 of 5 steps
+        // stay and 2 go bad; from the bad state 1 of 3 stays and 2 (one of
+        // them wrapping around) go good. So p = 2/5 and r = 2/3.
+        let stats = tracker(&[G, G, L, L, G, G, G, L]).stats();
+        assert_eq!((stats.packets, stats.lost, stats.loss_runs), (8, 3, 2));
+        assert_eq!(stats.gilbert_p, Some(2.0 / 5.0));
+        assert_eq!(stats.gilbert_r, Some(2.0 / 3.0));
+        // The stationary bad-state probability is the loss rate.
+        assert!((stats.pi_bad().unwrap() - 3.0 / 8.0).abs() < 1e-12);
+        assert_eq!((stats.mean_burst_length, stats.mean_gap_length), (Some(1.5), Some(2.5)));
+        assert_eq!((stats.loss_run_buckets[bucket(1)], stats.loss_run_buckets[bucket(2)]), (1, 1));
+    }
+
+    #[test]
+    fn forgetting_the_front_matches_a_fresh_tracker() {
+        let sequence = [L, L, G, L, L, L, G, G, L, G];
+        let mut popped = tracker(&sequence);
+        for start in 1..sequence.len() {
+            popped.pop_front();
+            let (got, want) = (popped.stats(), tracker(&sequence[start..]).stats());
+            assert_eq!((got.packets, got.lost, got.loss_runs), (want.packets, want.lost, want.loss_runs));
+            assert_eq!(got.loss_run_buckets, want.loss_run_buckets);
+            assert_eq!((got.gilbert_p, got.gilbert_r), (want.gilbert_p, want.gilbert_r));
+        }
+    }
+
+    #[test]
+    fn a_loss_free_sequence_has_no_bad_state() {
+        let stats = tracker(&[G; 5]).stats();
+        assert_eq!((stats.gilbert_p, stats.gilbert_r, stats.pi_bad()), (Some(0.0), None, None));
+        assert_eq!(stats.mean_burst_length, None);
+    }
+
+    #[test]
+    fn loss_runs_count_once_closed() {
+        let mut counter = LossRunCounter::default();
+        for lost in [true, false, true, true, true, true, true, false, true] {
+            counter.push(lost);
+        }
+        assert_eq!((counter.runs, counter.lost), (2, 6));
+        assert_eq!(counter.buckets[bucket(1)], 1);
+        assert_eq!(counter.buckets[bucket(5)], 1);
+        assert_eq!(bucket(5), 4);
+    }
+}
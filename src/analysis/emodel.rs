@@ -0,0 +1,113 @@
+//! ITU-T G.107 E-model, reduced to the terms a network measurement can drive:
+//!
+//!   R = R0 − Is − Id − Ie,eff   (A = 0)
+//!
+//! with the G.107 defaults for every terminal and room parameter, so that
+//! R0 − Is = 93.2, and talker echo assumed fully cancelled (Id = Idd). The
+//! codec is G.711 with packet loss concealment (Ie = 0, Bpl = 25.1, G.113).
+
+/// R0 − Is with G.107 default parameters.
+const R_DEFAULT: f64 = 93.2;
+/// Equipment impairment and packet-loss robustness of G.711 with PLC.
+const CODEC_IE: f64 = 0.0;
+const CODEC_BPL: f64 = 25.1;
+/// 20 ms frames: packetisation adds one frame of mouth-to-ear delay.
+const CODEC_DELAY_MS: f64 = 20.0;
+
+pub struct Inputs {
+    pub rtt_ms: f64,
+    pub jitter_ms: f64,
+    /// Packet loss probability, 0..1.
+    pub loss: f64,
+    /// Gilbert model transition probabilities, when loss was observed.
+    pub gilbert: Option<(f64, f64)>,
+}
+
+/// Delay impairment Idd for mouth-to-ear delay `ta_ms` (G.107 §7.3).
+fn delay_impairment(ta_ms: f64) -> f64 {
+    if ta_ms <= 100.0 {
+        return 0.0;
+    }
+    let x = (ta_ms / 100.0).ln() / 2f64.ln();
+    25.0 * ((1.0 + x.powi(6)).powf(1.0 / 6.0) - 3.0 * (1.0 + (x / 3.0).powi(6)).powf(1.0 / 6.0) + 2.0)
+}
+
+/// Effective equipment impairment under (possibly bursty) loss (G.107 §7.4).
+/// BurstR = 1 / (p + r) for a two-state Markov loss model; 1 means random loss.
+fn equipment_impairment(loss: f64, gilbert: Option<(f64, f64)>) -> f64 {
+    let ppl = 100.0 * loss.clamp(0.0, 1.0);
+    let burst_r = match gilbert {
+        Some((p, r)) if p + r > 0.0 => (1.0 / (p + r)).max(1.0),
+        _ => 1.0,
+    };
+    CODEC_IE + (95.0 - CODEC_IE) * ppl / (ppl / burst_r + CODEC_BPL)
+}
+
+/// Transmission rating factor R, 0..=93.2.
+pub fn r_factor(inputs: &Inputs) -> f64 {
+    // One-way delay plus a jitter buffer sized at twice the mean jitter.
+    let ta_ms = inputs.rtt_ms / 2.0 + 2.0 * inputs.jitter_ms + CODEC_DELAY_MS;
+    let r = R_DEFAULT - delay_impairment(ta_ms) - equipment_impairment(inputs.loss, inputs.gilbert);
+    r.max(0.0)
+}
+
+/// Estimated mean opinion score (G.107 Annex B), 1.0..=4.5.
+pub fn mos(r: f64) -> f64 {
+    if r <= 0.0 {
+        1.0
+    } else if r >= 100.0 {
+        4.5
+    } else {
+        1.0 + 0.035 * r + r * (r - 60.0) * (100.0 - r) * 7e-6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn r(rtt_ms: f64, jitter_ms: f64, loss: f64, gilbert: Option<(f64, f64)>) -> f64 {
+        r_factor(&Inputs { rtt_ms, jitter_ms, loss, gilbert })
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn unimpaired_path_scores_the_default_rating() {
+        assert_eq!(r(10.0, 0.0, 0.0, None), 93.2);
+        // Mouth-to-ear delay up to 100 ms costs nothing.
+        assert_eq!(r(120.0, 10.0, 0.0, None), 93.2);
+        assert!(close(mos(93.2), 1.0 + 0.035 * 93.2 + 93.2 * 33.2 * 6.8 * 7e-6));
+        assert!((mos(93.2) - 4.41).abs() < 0.005);
+    }
+
+    #[test]
+    fn delay_impairment_follows_g107() {
+        // Ta = 200 ms: X = 1, Idd = 25 (2^(1/6) − 3 (1 + 3^-6)^(1/6) + 2) ≈ 3.044.
+        let idd = 25.0 * (2f64.powf(1.0 / 6.0) - 3.0 * (1.0 + 3f64.powi(-6)).powf(1.0 / 6.0) + 2.0);
+        assert!((idd - 3.044).abs() < 0.001);
+        assert!(close(r(360.0, 0.0, 0.0, None), 93.2 - idd));
+        // Jitter counts twice, as the buffer absorbing it.
+        assert!(close(r(160.0, 50.0, 0.0, None), 93.2 - idd));
+    }
+
+    #[test]
+    fn loss_impairment_grows_with_burstiness() {
+        // 1 % random loss: Ie,eff = 95 · 1 / (1 + 25.1).
+        assert!(close(r(10.0, 0.0, 0.01, None), 93.2 - 95.0 / 26.1));
+        // BurstR = 1 / (0.1 + 0.4) = 2: Ie,eff = 95 · 1 / (1/2 + 25.1).
+        assert!(close(r(10.0, 0.0, 0.01, Some((0.1, 0.4))), 93.2 - 95.0 / 25.6));
+        // Loss more random than random counts as random.
+        assert!(close(r(10.0, 0.0, 0.01, Some((0.9, 0.9))), r(10.0, 0.0, 0.01, None)));
+        // Total loss: Ie,eff = 95 · 100 / (100 + 25.1).
+        assert!(close(r(10.0, 0.0, 1.0, None), 93.2 - 95.0 * 100.0 / 125.1));
+    }
+
+    #[test]
+    fn mos_is_bounded() {
+        assert_eq!((mos(0.0), mos(-5.0), mos(100.0)), (1.0, 1.0, 4.5));
+        assert!(mos(50.0) > 2.5 && mos(50.0) < mos(80.0));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod burst;
+pub mod emodel;
+pub mod sla;
@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::calendar::{civil_from_days, days_from_civil};
+use crate::model::Packet;
+
+/// Packets are classified per slot of this many seconds. A slot in which no
+/// packet was sent is a monitoring gap and is left out of availability.
+pub const SLOT_SECONDS: u64 = 60;
+
+const DAY_SECONDS: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SlaThresholds {
+    /// A slot is down at or above this loss ratio...
+    pub down_loss: f64,
+    /// ...and degraded at or above this loss ratio or median RTT.
+    pub degraded_loss: f64,
+    pub degraded_rtt_micros: u64,
+}
+
+impl Default for SlaThresholds {
+    fn default() -> Self {
+        Self { down_loss: 0.5, degraded_loss: 0.02, degraded_rtt_micros: 150_000 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotState {
+    Up,
+    Degraded,
+    Down,
+}
+
+#[derive(Debug)]
+struct Slot {
+    start: u64,
+    sent: u64,
+    lost: u64,
+    rtts: Vec<u64>,
+}
+
+/// Calendar periods availability is reported over, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// ISO 8601 week, starting on Monday.
+    Week,
+    Month,
+}
+
+impl Period {
+    pub const ALL: [Period; 3] = [Period::Day, Period::Week, Period::Month];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "day" | "daily" => Some(Period::Day),
+            "week" | "weekly" => Some(Period::Week),
+            "month" | "monthly" => Some(Period::Month),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Period::Day => "day",
+            Period::Week => "week",
+            Period::Month => "month",
+        }
+    }
+
+    /// [start, end) in Unix seconds of the period containing `secs`.
+    fn bounds(self, secs: u64) -> (u64, u64) {
+        let days = (secs / DAY_SECONDS) as i64;
+        let (start, end) = match self {
+            Period::Day => (days, days + 1),
+            Period::Week => {
+                // 1970-01-01 was a Thursday.
+                let monday = days - (days + 3).rem_euclid(7);
+                (monday, monday + 7)
+            }
+            Period::Month => {
+                let (y, m, _) = civil_from_days(days);
+                let (next_y, next_m) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+                (days_from_civil(y, m, 1), days_from_civil(next_y, next_m, 1))
+            }
+        };
+        (start as u64 * DAY_SECONDS, end as u64 * DAY_SECONDS)
+    }
+
+    /// `2026-10-19`, `2026-W43` or `2026-10`.
+    fn label(self, start: u64) -> String {
+        let days = (start / DAY_SECONDS) as i64;
+        match self {
+            Period::Day => {
+                let (y, m, d) = civil_from_days(days);
+                format!("{:04}-{:02}-{:02}", y, m, d)
+            }
+            Period::Week => {
+                // The ISO year is the one containing the week's Thursday.
+                let thursday = days + 3;
+                let (y, _, _) = civil_from_days(thursday);
+                let week = (thursday - days_from_civil(y, 1, 1)) / 7 + 1;
+                format!("{:04}-W{:02}", y, week)
+            }
+            Period::Month => {
+                let (y, m, _) = civil_from_days(days);
+                format!("{:04}-{:02}", y, m)
+            }
+        }
+    }
+}
+
+/// Availability of one source over one calendar period. Durations are in
+/// seconds; up, degraded and down only cover monitored slots.
+#[derive(Debug, Clone)]
+pub struct PeriodReport {
+    pub period: Period,
+    pub label: String,
+    pub start: u64,
+    pub end: u64,
+    pub up: u64,
+    pub degraded: u64,
+    pub down: u64,
+    /// Elapsed time in the period (up to now) without any monitoring.
+    pub unmonitored: u64,
+}
+
+impl PeriodReport {
+    pub fn monitored(&self) -> u64 {
+        self.up + self.degraded + self.down
+    }
+
+    fn ratio(&self, secs: u64) -> Option<f64> {
+        let monitored = self.monitored();
+        (monitored > 0).then(|| secs as f64 / monitored as f64)
+    }
+
+    /// Share of monitored time that was not down; degraded time counts as available.
+    pub fn availability(&self) -> Option<f64> {
+        self.ratio(self.up + self.degraded)
+    }
+
+    pub fn degraded_ratio(&self) -> Option<f64> {
+        self.ratio(self.degraded)
+    }
+}
+
+/// Per-slot up/degraded/down classification of settled packets.
+#[derive(Debug, Default)]
+pub struct SlaTracker {
+    thresholds: SlaThresholds,
+    current: Option<Slot>,
+    /// (slot start in Unix seconds, state) of completed slots, oldest first.
+    slots: VecDeque<(u64, SlotState)>,
+}
+
+impl SlaTracker {
+    pub fn new(thresholds: SlaThresholds) -> Self {
+        Self { thresholds, current: None, slots: VecDeque::new() }
+    }
+
+    fn classify(&self, slot: &Slot) -> SlotState {
+        let t = &self.thresholds;
+        let loss = slot.lost as f64 / slot.sent as f64;
+        if loss >= t.down_loss {
+            return SlotState::Down;
+        }
+        let mut rtts = slot.rtts.clone();
+        rtts.sort_unstable();
+        let median = rtts.get(rtts.len() / 2).copied().unwrap_or(0);
+        if loss >= t.degraded_loss || median >= t.degraded_rtt_micros {
+            SlotState::Degraded
+        } else {
+            SlotState::Up
+        }
+    }
+
+    /// Account the final outcome of the next packet, in send order.
+    pub fn push(&mut self, p: &Packet) {
+        if p.duplicate || p.is_failed() {
+            return;
+        }
+        let secs = (p.timestamp / 1_000_000) as u64;
+        let start = secs - secs % SLOT_SECONDS;
+        if self.current.as_ref().is_some_and(|slot| slot.start != start) {
+            let slot = self.current.take().unwrap();
+            let state = self.classify(&slot);
+            self.slots.push_back((slot.start, state));
+        }
+        let slot = self
+            .current
+            .get_or_insert_with(|| Slot { start, sent: 0, lost: 0, rtts: Vec::new() });
+        slot.sent += 1;
+        // Offline reports also see packets saved while still pending.
+        if !p.is_received() {
+            slot.lost += 1;
+        } else {
+            slot.rtts.push(p.latency);
+        }
+    }
+
+    /// Forget slots that started before `cutoff` (microseconds since epoch).
+    pub fn prune_older_than(&mut self, cutoff: u128) {
+        let cutoff = (cutoff / 1_000_000) as u64;
+        while self.slots.front().is_some_and(|&(start, _)| start < cutoff) {
+            self.slots.pop_front();
+        }
+    }
+
+    /// Availability per `period`, oldest first. Monitored time covered by one
+    /// of `outages` ([start, end) in Unix seconds) counts as down whatever the
+    /// loss of its slot was.
+    pub fn report(&self, period: Period, now: u64, outages: &[(u64, u64)]) -> Vec<PeriodReport> {
+        let current = self.current.as_ref().map(|slot| (slot.start, self.classify(slot)));
+        let mut reports: BTreeMap<u64, PeriodReport> = BTreeMap::new();
+        for (start, state) in self.slots.iter().copied().chain(current) {
+            // The current slot has only been monitored up to now.
+            let end = (start + SLOT_SECONDS).min(now.max(start));
+            let in_outage: u64 = outages
+                .iter()
+                .map(|&(o_start, o_end)| o_end.min(end).saturating_sub(o_start.max(start)))
+                .sum();
+            let in_outage = in_outage.min(end - start);
+            let rest = end - start - in_outage;
+
+            let (p_start, p_end) = period.bounds(start);
+            let report = reports.entry(p_start).or_insert_with(|| PeriodReport {
+                period,
+                label: period.label(p_start),
+                start: p_start,
+                end: p_end,
+                up: 0,
+                degraded: 0,
+                down: 0,
+                unmonitored: 0,
+            });
+            report.down += in_outage;
+            match state {
+                SlotState::Up => report.up += rest,
+                SlotState::Degraded => report.degraded += rest,
+                SlotState::Down => report.down += rest,
+            }
+        }
+        reports
+            .into_values()
+            .map(|mut r| {
+                let elapsed = r.end.min(now).saturating_sub(r.start);
+                r.unmonitored = elapsed.saturating_sub(r.monitored());
+                r
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,78 @@
+//! Monotonic time for measuring delays, and a watch on the wall clock, which
+//! only dates packets. A Pi has no RTC: it boots with the clock at 1970, or
+//! at the last fake-hwclock save, until NTP steps it.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Earlier wall-clock readings mean the clock has not been set (2025-01-01).
+const MIN_PLAUSIBLE_MICROS: u128 = 1_735_689_600 * 1_000_000;
+/// Wall and monotonic time drifting apart by more than this between two
+/// checks is a step; NTP slews far slower.
+const JUMP_THRESHOLD_MICROS: i128 = 1_000_000;
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+static START: OnceLock<Instant> = OnceLock::new();
+/// Newest timestamp found in persisted history.
+static NEWEST_PERSISTED: AtomicU64 = AtomicU64::new(0);
+static JUMPS: AtomicU64 = AtomicU64::new(0);
+
+/// Microseconds on the monotonic clock since the process started. Only
+/// differences within one run mean anything.
+pub fn monotonic_micros() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
+
+pub fn wall_micros() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
+}
+
+/// Note a timestamp read back from disk: a wall clock well behind it has
+/// gone backwards, most likely to a stale boot-time value.
+pub fn observe_persisted(timestamp: u128) {
+    NEWEST_PERSISTED.fetch_max(timestamp as u64, Ordering::Relaxed);
+}
+
+/// Whether the wall clock can be trusted to age history out by.
+pub fn synchronised() -> bool {
+    let now = wall_micros();
+    let newest = NEWEST_PERSISTED.load(Ordering::Relaxed) as u128;
+    now >= MIN_PLAUSIBLE_MICROS && now + JUMP_THRESHOLD_MICROS as u128 >= newest
+}
+
+/// Wall-clock steps detected since startup.
+pub fn jumps() -> u64 {
+    JUMPS.load(Ordering::Relaxed)
+}
+
+/// Compare wall-clock and monotonic progress periodically and log steps of
+/// the wall clock, and changes in whether it looks synchronised.
+pub async fn start_watch() {
+    let mut synced = synchronised();
+    if !synced {
+        eprintln!("Wall clock looks unsynchronised; history older than 30 days is kept until it is set");
+    }
+    let mut last = (wall_micros() as i128, monotonic_micros() as i128);
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = (wall_micros() as i128, monotonic_micros() as i128);
+        let step = (now.0 - last.0) - (now.1 - last.1);
+        if step.abs() > JUMP_THRESHOLD_MICROS {
+            JUMPS.fetch_add(1, Ordering::Relaxed);
+            eprintln!("Wall clock jumped by {:+.3} s", step as f64 / 1e6);
+        }
+        last = now;
+        let now_synced = synchronised();
+        match (synced, now_synced) {
+            (false, true) => println!("Wall clock looks synchronised; history pruning resumes"),
+            (true, false) => eprintln!("Wall clock looks unsynchronised; history pruning paused"),
+            _ => {}
+        }
+        synced = now_synced;
+    }
+}
@@ -0,0 +1,63 @@
+use crate::analysis::burst::{BurstTracker, BURST_BUCKETS};
+use crate::persistence;
+
+fn fmt_opt(v: Option<f64>, precision: usize) -> String {
+    v.map_or("-".to_string(), |v| format!("{:.*}", precision, v))
+}
+
+pub fn run(files: &[String]) {
+    for (source, path) in super::sources(files) {
+        if !std::path::Path::new(&path).exists() {
+            eprintln!("{}: {} not found, skipping", source, path);
+            continue;
+        }
+        let packets = persistence::load(&path);
+        let mut tracker = BurstTracker::default();
+        for p in packets.iter().filter(|p| !p.is_failed()) {
+            tracker.push(!p.is_received());
+        }
+        let s = tracker.stats();
+
+        println!();
+        if source == path {
+            println!("{}", path);
+        } else {
+            println!("{} ({})", source, path);
+        }
+        if s.packets == 0 {
+            println!("  no packets");
+            continue;
+        }
+        println!(
+            "  packets      {:>10}   lost {} ({:.3}%)",
+            s.packets,
+            s.lost,
+            100.0 * s.lost as f64 / s.packets as f64
+        );
+        println!(
+            "  loss bursts  {:>10}   mean length {}   mean gap {}",
+            s.loss_runs,
+            fmt_opt(s.mean_burst_length, 2),
+            fmt_opt(s.mean_gap_length, 1)
+        );
+        println!(
+            "  gilbert      p={}  r={}  P(bad)={}",
+            fmt_opt(s.gilbert_p, 5),
+            fmt_opt(s.gilbert_r, 5),
+            fmt_opt(s.pi_bad(), 5)
+        );
+        println!("  burst length distribution:");
+        let mut lower = 1;
+        for (i, count) in s.loss_run_buckets.iter().enumerate() {
+            let label = match BURST_BUCKETS.get(i) {
+                Some(&upper) if upper == lower => format!("{}", upper),
+                Some(&upper) => format!("{}-{}", lower, upper),
+                None => format!(">{}", lower - 1),
+            };
+            if let Some(&upper) = BURST_BUCKETS.get(i) {
+                lower = upper + 1;
+            }
+            println!("    {:>9} {:>10}", label, count);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Offline subcommands that work on the persisted history files instead of
+//! running the monitor.
+
+mod analyze;
+mod outages;
+mod sla;
+
+/// Run the subcommand named by `args[0]`, if any. Returns false when the
+/// arguments do not name a subcommand and the monitor should start.
+pub fn run(args: &[String]) -> bool {
+    match args.first().map(String::as_str) {
+        Some("analyze") => analyze::run(&args[1..]),
+        Some("outages") => outages::run(&args[1..]),
+        Some("sla") => sla::run(&args[1..]),
+        Some("help" | "--help" | "-h") => usage(),
+        Some(other) => {
+            eprintln!("Unknown command '{}'", other);
+            usage();
+            std::process::exit(2);
+        }
+        None => return false,
+    }
+    true
+}
+
+fn usage() {
+    println!("Usage: loopback [COMMAND]");
+    println!();
+    println!("Without a command, runs the monitor.");
+    println!();
+    println!("Commands:");
+    println!("  analyze [FILE...]          Loss burst analysis of packet history files");
+    println!("  outages [--json] [FILE]    Print the outage event log");
+    println!("  sla [--period day|week|month] [--json] [FILE...]");
+    println!("                             Availability per calendar period");
+}
+
+/// Explicit files are labelled by path; otherwise use the configured histories.
+fn sources(files: &[String]) -> Vec<(String, String)> {
+    if files.is_empty() {
+        crate::config::history_files()
+    } else {
+        files.iter().map(|f| (f.clone(), f.clone())).collect()
+    }
+}
@@ -0,0 +1,52 @@
+use crate::calendar::format_time;
+use crate::json;
+use crate::outage::Outage;
+use crate::persistence;
+
+fn to_json(o: &Outage) -> String {
+    let end = o.end.unwrap_or(o.start);
+    json::object(&[
+        ("source", json::string(&o.source)),
+        ("start_us", o.start.to_string()),
+        ("end_us", end.to_string()),
+        ("duration_seconds", json::number(o.duration_micros(end) as f64 / 1e6)),
+        ("packets_sent", o.packets_sent.to_string()),
+        ("packets_lost", o.packets_lost.to_string()),
+        ("peak_loss", json::number(o.peak_loss)),
+    ])
+}
+
+/// `loopback outages [--json] [FILE]`: print the persisted outage log.
+pub fn run(args: &[String]) {
+    let as_json = args.iter().any(|a| a == "--json");
+    let path = args
+        .iter()
+        .find(|a| !a.starts_with("--"))
+        .cloned()
+        .unwrap_or_else(crate::config::outage_file);
+    let outages = persistence::load_outages(&path);
+
+    if as_json {
+        let items: Vec<String> = outages.iter().map(to_json).collect();
+        println!("{}", json::array(&items));
+        return;
+    }
+
+    println!(
+        "{:<20} {:<20} {:>10} {:>8} {:>8} {:>6}  source",
+        "start (UTC)", "end (UTC)", "duration", "sent", "lost", "peak"
+    );
+    for o in &outages {
+        let end = o.end.unwrap_or(o.start);
+        println!(
+            "{:<20} {:<20} {:>9.1}s {:>8} {:>8} {:>5.0}%  {}",
+            format_time(o.start),
+            format_time(end),
+            o.duration_micros(end) as f64 / 1e6,
+            o.packets_sent,
+            o.packets_lost,
+            o.peak_loss * 100.0,
+            o.source
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::analysis::sla::{Period, PeriodReport, SlaTracker};
+use crate::{config, json, persistence};
+
+fn fmt_ratio(v: Option<f64>) -> String {
+    v.map_or("-".to_string(), |v| format!("{:.3}%", v * 100.0))
+}
+
+fn fmt_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn to_json(source: &str, r: &PeriodReport) -> String {
+    json::object(&[
+        ("source", json::string(source)),
+        ("period", json::string(r.period.name())),
+        ("label", json::string(&r.label)),
+        ("start", r.start.to_string()),
+        ("end", r.end.to_string()),
+        ("availability", json::opt(r.availability())),
+        ("degraded_ratio", json::opt(r.degraded_ratio())),
+        ("up_seconds", r.up.to_string()),
+        ("degraded_seconds", r.degraded.to_string()),
+        ("down_seconds", r.down.to_string()),
+        ("unmonitored_seconds", r.unmonitored.to_string()),
+    ])
+}
+
+/// `loopback sla [--period day|week|month] [--json] [FILE...]`: availability
+/// per calendar period from the packet histories and the outage log.
+pub fn run(args: &[String]) {
+    let mut period = Period::Day;
+    let mut as_json = false;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => as_json = true,
+            "--period" => match args.next().and_then(|p| Period::parse(p)) {
+                Some(p) => period = p,
+                None => {
+                    eprintln!("--period must be day, week or month");
+                    std::process::exit(2);
+                }
+            },
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    let now_us = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros();
+    let thresholds = config::sla_thresholds();
+    let loss_timeouts = config::loss_timeouts();
+    let log = crate::outage::OutageLog {
+        closed: persistence::load_outages(&config::outage_file()),
+        open: Vec::new(),
+    };
+
+    let mut rows = Vec::new();
+    for (source, path) in super::sources(&files) {
+        if !std::path::Path::new(&path).exists() {
+            eprintln!("{}: {} not found, skipping", source, path);
+            continue;
+        }
+        let mut tracker = SlaTracker::new(thresholds);
+        let settled_before = now_us.saturating_sub(loss_timeouts.micros_for(&source) as u128);
+        for p in persistence::load(&path).iter().filter(|p| p.timestamp < settled_before) {
+            tracker.push(p);
+        }
+        let outages = log.intervals(&source, now_us);
+        for report in tracker.report(period, (now_us / 1_000_000) as u64, &outages) {
+            rows.push((source.clone(), report));
+        }
+    }
+
+    if as_json {
+        let items: Vec<String> = rows.iter().map(|(source, r)| to_json(source, r)).collect();
+        println!("{}", json::array(&items));
+        return;
+    }
+
+    println!(
+        "{:<16} {:<10} {:>12} {:>10} {:>9} {:>10} {:>11}",
+        "source", period.name(), "availability", "degraded", "down", "monitored", "unmonitored"
+    );
+    for (source, r) in &rows {
+        println!(
+            "{:<16} {:<10} {:>12} {:>10} {:>9} {:>10} {:>11}",
+            source,
+            r.label,
+            fmt_ratio(r.availability()),
+            fmt_ratio(r.degraded_ratio()),
+            fmt_duration(r.down),
+            fmt_duration(r.monitored()),
+            fmt_duration(r.unmonitored)
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::env;
+
+use super::remote_write::{secret, var, Secret};
+
+/// What a rule measures. Values are in the unit they are written in:
+/// loss in percent, RTT and jitter in milliseconds, MTU in bytes and silence
+/// (time since the last reply) in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertMetric {
+    Loss,
+    Rtt,
+    Jitter,
+    Mtu,
+    Silence,
+}
+
+impl AlertMetric {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlertMetric::Loss => "loss",
+            AlertMetric::Rtt => "rtt",
+            AlertMetric::Jitter => "jitter",
+            AlertMetric::Mtu => "mtu",
+            AlertMetric::Silence => "silence",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            AlertMetric::Loss => "%",
+            AlertMetric::Rtt | AlertMetric::Jitter => "ms",
+            AlertMetric::Mtu => " bytes",
+            AlertMetric::Silence => "s",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Above,
+    AtLeast,
+    Below,
+    AtMost,
+}
+
+impl Comparison {
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparison::Above => value > threshold,
+            Comparison::AtLeast => value >= threshold,
+            Comparison::Below => value < threshold,
+            Comparison::AtMost => value <= threshold,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Above => ">",
+            Comparison::AtLeast => ">=",
+            Comparison::Below => "<",
+            Comparison::AtMost => "<=",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AlertRule {
+    pub name: String,
+    /// The rule as written, for notifications.
+    pub expr: String,
+    /// `loopback` or a ping target.
+    pub source: String,
+    pub metric: AlertMetric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    /// The alert resolves once the value no longer compares past this
+    /// (hysteresis); defaults to the threshold.
+    pub clear: f64,
+    /// Loss, RTT and jitter are computed over this window.
+    pub window_micros: u128,
+    /// The condition must hold this long before firing, and be clear this
+    /// long before resolving.
+    pub for_micros: u128,
+}
+
+#[derive(Debug, Clone)]
+pub struct NtfyConfig {
+    /// Topic URL, e.g. `https://ntfy.sh/my-loopback`.
+    pub url: String,
+    pub token: Option<Secret>,
+    pub priority: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GotifyConfig {
+    /// Server base URL; messages are posted to `<url>/message`.
+    pub url: String,
+    pub token: Secret,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlertsConfig {
+    pub rules: Vec<AlertRule>,
+    /// Generic webhook receiving a JSON POST per notification.
+    pub webhook_url: Option<String>,
+    pub ntfy: Option<NtfyConfig>,
+    pub gotify: Option<GotifyConfig>,
+}
+
+const DEFAULT_WINDOW_MICROS: u128 = 5 * 60 * 1_000_000;
+
+/// `90`, `90s`, `500ms`, `5m`, `1h` → microseconds.
+fn parse_duration(s: &str) -> Option<u128> {
+    let (number, scale) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 1e3)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1e6)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60e6)
+    } else if let Some(n) = s.strip_suffix('h') {
+        (n, 3600e6)
+    } else {
+        (s, 1e6)
+    };
+    let v: f64 = number.parse().ok()?;
+    (v >= 0.0).then_some((v * scale) as u128)
+}
+
+/// A threshold in the metric's unit; an explicit unit suffix is accepted
+/// when it matches.
+fn parse_value(metric: AlertMetric, s: &str) -> Option<f64> {
+    if metric == AlertMetric::Silence {
+        return parse_duration(s).map(|us| us as f64 / 1e6);
+    }
+    let s = match metric {
+        AlertMetric::Loss => s.strip_suffix('%').unwrap_or(s),
+        AlertMetric::Rtt | AlertMetric::Jitter => s.strip_suffix("ms").unwrap_or(s),
+        AlertMetric::Mtu => s.strip_suffix('B').unwrap_or(s),
+        AlertMetric::Silence => s,
+    };
+    s.parse().ok()
+}
+
+/// Parse one rule:
+///
+///   [name: ]<source> <loss|rtt|jitter|mtu|silence> <op> <value> [over <dur>] [for <dur>] [clear <value>]
+///   [name: ]<source> unreachable <dur>        (same as `silence >= <dur>`)
+fn parse_rule(s: &str) -> Result<AlertRule, String> {
+    // `name: ` needs the space, so IPv6 targets are not mistaken for names.
+    let (name, expr) = match s.split_once(": ") {
+        Some((name, expr)) if !name.contains(char::is_whitespace) => (name.to_string(), expr.trim()),
+        _ => (s.to_string(), s),
+    };
+    let tokens: Vec<&str> = expr.split_whitespace().collect();
+    let [source, metric, rest @ ..] = tokens.as_slice() else {
+        return Err("expected '<source> <metric> <op> <value>'".to_string());
+    };
+
+    let (metric, comparison, threshold, mut rest) = if *metric == "unreachable" {
+        let [duration, rest @ ..] = rest else {
+            return Err("expected 'unreachable <duration>'".to_string());
+        };
+        let micros = parse_duration(duration).ok_or(format!("invalid duration '{}'", duration))?;
+        (AlertMetric::Silence, Comparison::AtLeast, micros as f64 / 1e6, rest)
+    } else {
+        let metric = match *metric {
+            "loss" => AlertMetric::Loss,
+            "rtt" => AlertMetric::Rtt,
+            "jitter" => AlertMetric::Jitter,
+            "mtu" => AlertMetric::Mtu,
+            "silence" => AlertMetric::Silence,
+            other => return Err(format!("unknown metric '{}'", other)),
+        };
+        let [op, value, rest @ ..] = rest else {
+            return Err("expected '<op> <value>' after the metric".to_string());
+        };
+        let comparison = match *op {
+            ">" => Comparison::Above,
+            ">=" => Comparison::AtLeast,
+            "<" => Comparison::Below,
+            "<=" => Comparison::AtMost,
+            other => return Err(format!("unknown comparison '{}'", other)),
+        };
+        let threshold = parse_value(metric, value).ok_or(format!("invalid value '{}'", value))?;
+        (metric, comparison, threshold, rest)
+    };
+
+    let mut rule = AlertRule {
+        name,
+        expr: expr.to_string(),
+        source: source.to_string(),
+        metric,
+        comparison,
+        threshold,
+        clear: threshold,
+        window_micros: DEFAULT_WINDOW_MICROS,
+        for_micros: 0,
+    };
+    let duration = |v: &str| parse_duration(v).ok_or(format!("invalid duration '{}'", v));
+    while let [keyword, value, tail @ ..] = rest {
+        match *keyword {
+            "over" => rule.window_micros = duration(value)?,
+            "for" => rule.for_micros = duration(value)?,
+            "clear" => {
+                rule.clear = parse_value(metric, value).ok_or(format!("invalid value '{}'", value))?
+            }
+            other => return Err(format!("unexpected '{}'", other)),
+        }
+        rest = tail;
+    }
+    if let [extra, ..] = rest {
+        return Err(format!("unexpected '{}'", extra));
+    }
+    Ok(rule)
+}
+
+/// `ALERT_RULES` (`;`-separated), `ALERT_WEBHOOK_URL`, `ALERT_NTFY_URL`,
+/// `ALERT_NTFY_TOKEN[_FILE]`, `ALERT_NTFY_PRIORITY`, `ALERT_GOTIFY_URL` and
+/// `ALERT_GOTIFY_TOKEN[_FILE]`.
+pub fn load() -> AlertsConfig {
+    let rules = env::var("ALERT_RULES")
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| match parse_rule(s) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                eprintln!("Ignoring alert rule '{}': {}", s, e);
+                None
+            }
+        })
+        .collect();
+
+    let gotify = var("ALERT", "GOTIFY_URL").and_then(|url| match secret("ALERT", "GOTIFY_TOKEN") {
+        Some(token) => Some(GotifyConfig { url, token }),
+        None => {
+            eprintln!("ALERT_GOTIFY_URL is set but ALERT_GOTIFY_TOKEN is not; Gotify disabled");
+            None
+        }
+    });
+
+    AlertsConfig {
+        rules,
+        webhook_url: var("ALERT", "WEBHOOK_URL"),
+        ntfy: var("ALERT", "NTFY_URL").map(|url| NtfyConfig {
+            url,
+            token: secret("ALERT", "NTFY_TOKEN"),
+            priority: var("ALERT", "NTFY_PRIORITY"),
+        }),
+        gotify,
+    }
+}
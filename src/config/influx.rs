@@ -0,0 +1,22 @@
+use super::remote_write::{secret, var, Secret};
+
+#[derive(Debug, Clone)]
+pub struct InfluxConfig {
+    /// Write endpoint including bucket/database, e.g.
+    /// `http://influx:8086/api/v2/write?org=home&bucket=loopback` or
+    /// `http://influx:8086/write?db=loopback`.
+    pub url: String,
+    /// Sent as `Authorization: Token <token>`.
+    pub token: Option<Secret>,
+}
+
+/// `INFLUX_URL`, `INFLUX_TOKEN[_FILE]`.
+pub fn load() -> Option<InfluxConfig> {
+    let mut url = var("INFLUX", "URL")?;
+    // Timestamps are written in milliseconds.
+    if !url.contains("precision=") {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str("precision=ms");
+    }
+    Some(InfluxConfig { url, token: secret("INFLUX", "TOKEN") })
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+/// Prometheus label names: `[a-zA-Z_][a-zA-Z0-9_]*`, with the `__` prefix
+/// reserved for internal use.
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__")
+}
+
+/// Parse `name=value,name=value`, dropping (and reporting) invalid names.
+pub fn parse(s: &str) -> Vec<(String, String)> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| {
+            let Some((name, value)) = pair.split_once('=') else {
+                eprintln!("Ignoring label '{}': expected name=value", pair);
+                return None;
+            };
+            let name = name.trim();
+            if !valid_name(name) {
+                eprintln!("Ignoring label '{}': invalid label name", name);
+                return None;
+            }
+            Some((name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parse per-target labels: `1.1.1.1{provider=cloudflare,kind=dns};8.8.8.8{provider=google}`.
+/// Braces rather than `:` keep IPv6 targets unambiguous.
+pub fn parse_per_target(s: &str) -> HashMap<String, Vec<(String, String)>> {
+    let mut map = HashMap::new();
+    let mut rest = s;
+    while let Some(open) = rest.find('{') {
+        let target = rest[..open].trim_matches(|c: char| c == ';' || c == ',' || c.is_whitespace());
+        let Some(close) = rest[open..].find('}') else {
+            eprintln!("Ignoring per-target labels for '{}': missing '}}'", target);
+            break;
+        };
+        if !target.is_empty() {
+            map.insert(target.to_string(), parse(&rest[open + 1..open + close]));
+        }
+        rest = &rest[open + close + 1..];
+    }
+    map
+}
@@ -1,47 +1,333 @@
+mod alerts;
+mod influx;
+mod labels;
+mod otlp;
+mod plaintext;
+mod remote_write;
+
+use std::collections::HashMap;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::analysis::sla::SlaThresholds;
+use crate::model::DEFAULT_LOSS_TIMEOUT_MICROS;
+use crate::network::schedule::Schedule;
+use crate::network::sizes::SizeMode;
+use crate::outage::OutageThresholds;
+
+pub use alerts::{AlertMetric, AlertRule, AlertsConfig, GotifyConfig, NtfyConfig};
+#[cfg(test)]
+pub use alerts::Comparison;
+pub use influx::InfluxConfig;
+pub use otlp::OtlpConfig;
+pub use plaintext::{PlaintextConfig, Protocol};
+pub use remote_write::{Auth, RemoteWriteConfig, Secret};
+
+/// Where computed stats are exported (`METRICS_SINKS`, comma-separated).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkKind {
+    RemoteWrite,
+    Otlp,
+    Influx,
+    Graphite,
+    Statsd,
+}
+
+impl SinkKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            SinkKind::RemoteWrite => "remote_write",
+            SinkKind::Otlp => "otlp",
+            SinkKind::Influx => "influx",
+            SinkKind::Graphite => "graphite",
+            SinkKind::Statsd => "statsd",
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    pub alerts: AlertsConfig,
     pub alternative_interface: Option<String>,
+    /// Send loopback probes no larger than the latest UDP MTU probe result
+    /// (`CLAMP_PACKET_SIZE_TO_MTU`).
+    pub clamp_to_mtu: bool,
+    /// Bearer token of the runtime control API; the API is disabled without one.
+    pub control_token: Option<Secret>,
     pub data_file: String,
+    /// Unix socket serving the live event stream; disabled when unset.
+    pub events_socket: Option<String>,
+    /// Labels added to every exported series (e.g. instance, site, isp, vpn_server).
+    pub external_labels: Vec<(String, String)>,
+    /// Address of the embedded HTTP server (dashboard); disabled when unset.
+    pub http_listen: Option<String>,
     pub interval_millis: u64,
+    pub loss_timeouts: LossTimeouts,
     pub max_mtu: u32,
     pub max_packet_size: usize,
     pub max_queue_size: usize,
     pub min_mtu: u32,
+    /// Smallest probe in the sweep and random size modes (`MIN_PACKET_SIZE`).
+    pub min_packet_size: usize,
+    pub outage_file: String,
+    pub outage_thresholds: OutageThresholds,
     pub ping_data_file: String,
     pub ping_targets: Vec<String>,
+    pub ping_target_labels: HashMap<String, Vec<(String, String)>>,
+    pub schedules: Schedules,
+    pub size_mode: SizeMode,
+    /// Shared secret signing loopback payloads; unsigned when unset.
+    pub probe_secret: Option<Secret>,
+    pub sla_thresholds: SlaThresholds,
+    /// STAMP session-reflector address (`STAMP_REFLECTOR`).
+    pub stamp_reflector: Option<String>,
+    /// STAMP reflectors to run sessions with, `ip` or `ip:port`.
+    pub stamp_targets: Vec<String>,
     pub target_port: u16,
-    pub mimir_url: String,
+    pub metrics_sinks: Vec<SinkKind>,
+    pub remote_write: Vec<RemoteWriteConfig>,
+    pub otlp: Option<OtlpConfig>,
+    pub influx: Option<InfluxConfig>,
+    pub graphite: Option<PlaintextConfig>,
+    pub statsd: Option<PlaintextConfig>,
 }
 
 impl Config {
+    /// Custom labels configured for one ping target.
+    pub fn labels_for(&self, target: &str) -> Vec<(String, String)> {
+        self.ping_target_labels.get(target).cloned().unwrap_or_default()
+    }
+
     /// Derive a per-target packet history path.
     pub fn ping_data_file_for(&self, target: &str) -> String {
-        let base = self
-            .ping_data_file
-            .strip_suffix(".bin")
-            .unwrap_or(&self.ping_data_file);
-        format!("{}_{}.bin", base, target)
+        derived_path(&self.ping_data_file, &format!("_{}", target))
     }
 
     /// Derive the UDP loopback MTU history path from the main data file.
     pub fn loopback_mtu_file(&self) -> String {
-        let base = self
-            .data_file
-            .strip_suffix(".bin")
-            .unwrap_or(&self.data_file);
-        format!("{}_mtu.bin", base)
+        derived_path(&self.data_file, "_mtu")
     }
 
     /// Derive a per-target ICMP MTU history path.
     pub fn ping_mtu_file_for(&self, target: &str) -> String {
-        let base = self
-            .ping_data_file
-            .strip_suffix(".bin")
-            .unwrap_or(&self.ping_data_file);
-        format!("{}_{}_mtu.bin", base, target)
+        derived_path(&self.ping_data_file, &format!("_{}_mtu", target))
+    }
+}
+
+/// `/dir/name.bin` + `_suffix` → `/dir/name_suffix.bin`.
+fn derived_path(file: &str, suffix: &str) -> String {
+    let base = file.strip_suffix(".bin").unwrap_or(file);
+    format!("{}{}.bin", base, suffix)
+}
+
+fn data_file() -> String {
+    env::var("DATA_FILE").unwrap_or_else(|_| "/var/lib/loopback/data.bin".to_string())
+}
+
+fn ping_data_file() -> String {
+    env::var("PING_DATA_FILE").unwrap_or_else(|_| "/var/lib/loopback/ping_data.bin".to_string())
+}
+
+/// Persisted outage event log.
+pub fn outage_file() -> String {
+    env::var("OUTAGE_FILE").unwrap_or_else(|_| "/var/lib/loopback/outages.bin".to_string())
+}
+
+/// How long each source waits for a reply before counting a packet lost.
+#[derive(Debug, Clone)]
+pub struct LossTimeouts {
+    pub default_millis: u64,
+    /// Overrides by source name.
+    pub per_source_millis: Vec<(String, u64)>,
+}
+
+impl LossTimeouts {
+    pub fn micros_for(&self, source: &str) -> u64 {
+        let millis = self
+            .per_source_millis
+            .iter()
+            .find(|(name, _)| name == source)
+            .map_or(self.default_millis, |&(_, ms)| ms);
+        millis * 1000
+    }
+}
+
+/// `LOSS_TIMEOUT_MS`, and `LOSS_TIMEOUT_MS_PER_SOURCE` as `source=ms` pairs.
+/// Shared with the offline `sla` command.
+pub fn loss_timeouts() -> LossTimeouts {
+    let default_millis = env::var("LOSS_TIMEOUT_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LOSS_TIMEOUT_MICROS / 1000);
+    let per_source_millis = parse_pairs(&env::var("LOSS_TIMEOUT_MS_PER_SOURCE").unwrap_or_default())
+        .into_iter()
+        .filter_map(|(source, ms)| match ms.parse() {
+            Ok(ms) => Some((source, ms)),
+            Err(_) => {
+                eprintln!("Ignoring loss timeout '{}' for {}: not a number", ms, source);
+                None
+            }
+        })
+        .collect();
+    LossTimeouts { default_millis, per_source_millis }
+}
+
+/// When each source sends its probes.
+#[derive(Debug, Clone)]
+pub struct Schedules {
+    pub default: Schedule,
+    /// Overrides by source name.
+    pub per_source: Vec<(String, Schedule)>,
+}
+
+impl Schedules {
+    pub fn for_source(&self, source: &str) -> Schedule {
+        self.per_source
+            .iter()
+            .find(|(name, _)| name == source)
+            .map_or(self.default, |&(_, schedule)| schedule)
+    }
+}
+
+fn parse_schedule(value: &str, what: &str) -> Option<Schedule> {
+    let schedule = Schedule::parse(value);
+    if schedule.is_none() {
+        eprintln!("Ignoring schedule '{}' for {}: expected fixed, poisson or random_start", value, what);
     }
+    schedule
+}
+
+/// `PROBE_SCHEDULE`, and `PROBE_SCHEDULE_PER_SOURCE` as `source=schedule` pairs.
+fn schedules() -> Schedules {
+    let default = env::var("PROBE_SCHEDULE")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .and_then(|v| parse_schedule(&v, "every source"))
+        .unwrap_or(Schedule::Fixed);
+    let per_source = parse_pairs(&env::var("PROBE_SCHEDULE_PER_SOURCE").unwrap_or_default())
+        .into_iter()
+        .filter_map(|(source, v)| parse_schedule(&v, &source).map(|s| (source, s)))
+        .collect();
+    Schedules { default, per_source }
+}
+
+/// `PACKET_SIZE_MODE`: fixed (every probe at the packet size), sweep or random.
+fn size_mode() -> SizeMode {
+    match env::var("PACKET_SIZE_MODE") {
+        Ok(v) if !v.trim().is_empty() => SizeMode::parse(&v).unwrap_or_else(|| {
+            eprintln!("Ignoring PACKET_SIZE_MODE '{}': expected fixed, sweep or random", v);
+            SizeMode::Fixed
+        }),
+        _ => SizeMode::Fixed,
+    }
+}
+
+/// What counts as down or degraded in availability reports. Shared with the
+/// offline `sla` command.
+pub fn sla_thresholds() -> SlaThresholds {
+    let defaults = SlaThresholds::default();
+    let percent = |key: &str, default: f64| {
+        env::var(key)
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .map_or(default, |v| v / 100.0)
+    };
+    SlaThresholds {
+        down_loss: percent("SLA_DOWN_LOSS_PERCENT", defaults.down_loss),
+        degraded_loss: percent("SLA_DEGRADED_LOSS_PERCENT", defaults.degraded_loss),
+        degraded_rtt_micros: env::var("SLA_DEGRADED_RTT_MS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .map_or(defaults.degraded_rtt_micros, |ms| (ms * 1000.0) as u64),
+    }
+}
+
+/// A listen address: `host:port`, or a bare port to listen on all interfaces.
+fn listen_addr(key: &str) -> Option<String> {
+    let v = env::var(key).ok()?.trim().to_string();
+    match v.parse::<u16>() {
+        Ok(port) => Some(format!("0.0.0.0:{}", port)),
+        Err(_) => Some(v).filter(|v| !v.is_empty()),
+    }
+}
+
+fn list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn ping_targets() -> Vec<String> {
+    list(&env::var("PING_TARGET").unwrap_or_else(|_| "1.1.1.1".to_string()))
+}
+
+/// `STAMP_TARGET` reflectors as `ip:port`, with the STAMP port by default.
+fn stamp_targets() -> Vec<String> {
+    list(&env::var("STAMP_TARGET").unwrap_or_default())
+        .into_iter()
+        .map(|target| match target.parse::<IpAddr>() {
+            Ok(ip) => SocketAddr::new(ip, crate::network::stamp::DEFAULT_PORT).to_string(),
+            Err(_) => target,
+        })
+        .collect()
+}
+
+/// Sources of one STAMP session as (name, direction): the round trip, then
+/// the forward and reverse directions.
+pub fn stamp_sources(reflector: &str) -> [(String, &'static str); 3] {
+    [
+        (format!("stamp:{}", reflector), "round_trip"),
+        (format!("stamp:{}:forward", reflector), "forward"),
+        (format!("stamp:{}:reverse", reflector), "reverse"),
+    ]
+}
+
+/// Packet history files as (source, path), where source is `loopback` or a
+/// ping target. Used by offline commands, which must work without the
+/// variables only the running service needs.
+pub fn history_files() -> Vec<(String, String)> {
+    let ping_data_file = ping_data_file();
+    let mut files = vec![("loopback".to_string(), data_file())];
+    for target in ping_targets() {
+        files.push((target.clone(), derived_path(&ping_data_file, &format!("_{}", target))));
+    }
+    for reflector in stamp_targets() {
+        for (name, _) in stamp_sources(&reflector) {
+            let path = derived_path(&ping_data_file, &format!("_{}", name));
+            files.push((name, path));
+        }
+    }
+    files
+}
+
+/// Parse `key=value,key=value` pairs (headers, attributes).
+fn parse_pairs(s: &str) -> Vec<(String, String)> {
+    s.split(',')
+        .filter_map(|pair| {
+            let (k, v) = pair.split_once('=')?;
+            let k = k.trim();
+            (!k.is_empty()).then(|| (k.to_string(), v.trim().to_string()))
+        })
+        .collect()
+}
+
+fn parse_sinks(s: &str) -> Vec<SinkKind> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|name| match name.to_ascii_lowercase().as_str() {
+            "remote_write" | "mimir" | "prometheus" => Some(SinkKind::RemoteWrite),
+            "otlp" => Some(SinkKind::Otlp),
+            "influx" | "influxdb" => Some(SinkKind::Influx),
+            "graphite" => Some(SinkKind::Graphite),
+            "statsd" => Some(SinkKind::Statsd),
+            _ => {
+                eprintln!("Unknown metrics sink '{}', ignoring", name);
+                None
+            }
+        })
+        .collect()
 }
 
 const VPN_PORT_FILE: &str = "/var/lib/loopback/vpn_port";
@@ -61,45 +347,80 @@ fn read_target_port() -> u16 {
 }
 
 pub fn load() -> Config {
-    let ping_targets = env::var("PING_TARGET")
-        .unwrap_or_else(|_| "1.1.1.1".to_string())
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-
+    let max_packet_size = env::var("MAX_PACKET_SIZE")
+        .expect("MAX_PACKET_SIZE must be set")
+        .parse()
+        .expect("MAX_PACKET_SIZE must be a number");
     Config {
-        data_file: env::var("DATA_FILE")
-            .unwrap_or_else(|_| "/var/lib/loopback/data.bin".to_string()),
-        ping_data_file: env::var("PING_DATA_FILE")
-            .unwrap_or_else(|_| "/var/lib/loopback/ping_data.bin".to_string()),
-        ping_targets,
+        alerts: alerts::load(),
+        control_token: remote_write::secret("CONTROL", "TOKEN"),
+        data_file: data_file(),
+        events_socket: env::var("EVENTS_SOCKET").ok().filter(|s| !s.is_empty()),
+        ping_data_file: ping_data_file(),
+        probe_secret: remote_write::secret("PROBE", "SECRET"),
+        ping_targets: ping_targets(),
+        ping_target_labels: labels::parse_per_target(
+            &env::var("PING_TARGET_LABELS").unwrap_or_default(),
+        ),
+        external_labels: labels::parse(&env::var("EXTERNAL_LABELS").unwrap_or_default()),
+        http_listen: listen_addr("HTTP_LISTEN"),
         alternative_interface: Some(
             env::var("ALTERNATIVE_INTERFACE").unwrap_or_else(|_| "wgproton".to_string()),
         )
         .filter(|s| !s.is_empty()),
+        clamp_to_mtu: env::var("CLAMP_PACKET_SIZE_TO_MTU")
+            .is_ok_and(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes")),
         min_mtu: env::var("MIN_MTU")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(576),
+        outage_file: outage_file(),
+        outage_thresholds: OutageThresholds {
+            min_losses: env::var("OUTAGE_MIN_LOSSES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            min_silence_micros: env::var("OUTAGE_MIN_SECONDS")
+                .ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .map(|s| (s * 1_000_000.0) as u128)
+                .unwrap_or(5_000_000),
+            recovery_packets: env::var("OUTAGE_RECOVERY_PACKETS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3),
+        },
+        sla_thresholds: sla_thresholds(),
+        stamp_reflector: listen_addr("STAMP_REFLECTOR"),
+        stamp_targets: stamp_targets(),
         max_mtu: env::var("MAX_MTU")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(1512),
-        max_packet_size: env::var("MAX_PACKET_SIZE")
-            .expect("MAX_PACKET_SIZE must be set")
-            .parse()
-            .expect("MAX_PACKET_SIZE must be a number"),
+        max_packet_size,
+        min_packet_size: env::var("MIN_PACKET_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(max_packet_size),
         max_queue_size: env::var("MAX_QUEUE_SIZE")
             .expect("MAX_QUEUE_SIZE must be set")
             .parse()
             .expect("MAX_QUEUE_SIZE must be a number"),
+        loss_timeouts: loss_timeouts(),
+        schedules: schedules(),
+        size_mode: size_mode(),
         interval_millis: env::var("INTERVAL_MILLIS")
             .expect("INTERVAL_MILLIS must be set")
             .parse()
             .expect("INTERVAL_MILLIS must be a number"),
         target_port: read_target_port(),
-        mimir_url: env::var("MIMIR_URL")
-            .unwrap_or_else(|_| "http://localhost:9009/api/v1/push".to_string()),
+        metrics_sinks: parse_sinks(
+            &env::var("METRICS_SINKS").unwrap_or_else(|_| "remote_write".to_string()),
+        ),
+        remote_write: remote_write::load(),
+        otlp: otlp::load(),
+        influx: influx::load(),
+        graphite: plaintext::load("GRAPHITE", Protocol::Tcp),
+        statsd: plaintext::load("STATSD", Protocol::Udp),
     }
 }
@@ -0,0 +1,40 @@
+use std::env;
+
+use super::parse_pairs;
+
+#[derive(Debug, Clone)]
+pub struct OtlpConfig {
+    /// Full OTLP/HTTP metrics URL, e.g. `http://collector:4318/v1/metrics`.
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// Resource attributes; always includes `service.name`.
+    pub resource_attributes: Vec<(String, String)>,
+}
+
+/// Uses the standard OpenTelemetry SDK variables:
+/// `OTEL_EXPORTER_OTLP_METRICS_ENDPOINT` (or `OTEL_EXPORTER_OTLP_ENDPOINT` +
+/// `/v1/metrics`), `OTEL_EXPORTER_OTLP_HEADERS`, `OTEL_RESOURCE_ATTRIBUTES`
+/// and `OTEL_SERVICE_NAME`.
+pub fn load() -> Option<OtlpConfig> {
+    let nonempty = |name: &str| env::var(name).ok().filter(|s| !s.trim().is_empty());
+
+    let url = nonempty("OTEL_EXPORTER_OTLP_METRICS_ENDPOINT").or_else(|| {
+        nonempty("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .map(|base| format!("{}/v1/metrics", base.trim().trim_end_matches('/')))
+    })?;
+
+    let mut resource_attributes =
+        parse_pairs(&nonempty("OTEL_RESOURCE_ATTRIBUTES").unwrap_or_default());
+    if let Some(name) = nonempty("OTEL_SERVICE_NAME") {
+        resource_attributes.retain(|(k, _)| k != "service.name");
+        resource_attributes.push(("service.name".to_string(), name));
+    } else if !resource_attributes.iter().any(|(k, _)| k == "service.name") {
+        resource_attributes.push(("service.name".to_string(), "loopback".to_string()));
+    }
+
+    Some(OtlpConfig {
+        url: url.trim().to_string(),
+        headers: parse_pairs(&nonempty("OTEL_EXPORTER_OTLP_HEADERS").unwrap_or_default()),
+        resource_attributes,
+    })
+}
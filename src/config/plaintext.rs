@@ -0,0 +1,35 @@
+use super::remote_write::var;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// A line-oriented TCP/UDP sink (Graphite plaintext or StatsD).
+#[derive(Debug, Clone)]
+pub struct PlaintextConfig {
+    pub addr: String,
+    pub protocol: Protocol,
+    /// Prepended to every metric name, e.g. `home.` (Graphite only).
+    pub prefix: String,
+}
+
+/// `<PREFIX>_ADDR`, `<PREFIX>_PROTOCOL` (tcp|udp) and `<PREFIX>_METRIC_PREFIX`.
+pub fn load(prefix: &str, default_protocol: Protocol) -> Option<PlaintextConfig> {
+    let addr = var(prefix, "ADDR")?;
+    let protocol = match var(prefix, "PROTOCOL").map(|s| s.to_ascii_lowercase()).as_deref() {
+        Some("tcp") => Protocol::Tcp,
+        Some("udp") => Protocol::Udp,
+        Some(other) => {
+            eprintln!("Unknown {}_PROTOCOL '{}', using default", prefix, other);
+            default_protocol
+        }
+        None => default_protocol,
+    };
+    Some(PlaintextConfig {
+        addr,
+        protocol,
+        prefix: var(prefix, "METRIC_PREFIX").unwrap_or_default(),
+    })
+}
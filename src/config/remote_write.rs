@@ -0,0 +1,117 @@
+use std::env;
+
+use super::parse_pairs;
+
+/// A credential given either inline or as a path to a file holding it.
+/// Files are re-read on every push so rotated tokens are picked up.
+#[derive(Debug, Clone)]
+pub enum Secret {
+    Value(String),
+    File(String),
+}
+
+impl Secret {
+    pub fn resolve(&self) -> Option<String> {
+        match self {
+            Secret::Value(v) => Some(v.clone()),
+            Secret::File(path) => match std::fs::read_to_string(path) {
+                Ok(s) => Some(s.trim().to_string()),
+                Err(e) => {
+                    eprintln!("Failed to read secret from {}: {}", path, e);
+                    None
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Auth {
+    None,
+    Basic { username: String, password: Secret },
+    Bearer(Secret),
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteWriteConfig {
+    pub name: String,
+    pub url: String,
+    pub tenant_id: Option<String>,
+    pub auth: Auth,
+    pub headers: Vec<(String, String)>,
+    pub ca_file: Option<String>,
+    pub client_cert_file: Option<String>,
+    pub client_key_file: Option<String>,
+}
+
+pub(super) fn var(prefix: &str, key: &str) -> Option<String> {
+    env::var(format!("{}_{}", prefix, key))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+pub(super) fn secret(prefix: &str, key: &str) -> Option<Secret> {
+    var(prefix, key)
+        .map(Secret::Value)
+        .or_else(|| var(prefix, &format!("{}_FILE", key)).map(Secret::File))
+}
+
+/// Read one endpoint from `<PREFIX>_URL`, `<PREFIX>_TENANT_ID`,
+/// `<PREFIX>_BASIC_AUTH_USERNAME`, `<PREFIX>_BASIC_AUTH_PASSWORD[_FILE]`,
+/// `<PREFIX>_BEARER_TOKEN[_FILE]`, `<PREFIX>_HEADERS`, `<PREFIX>_CA_FILE`,
+/// `<PREFIX>_CLIENT_CERT_FILE` and `<PREFIX>_CLIENT_KEY_FILE`.
+fn load_endpoint(name: &str, prefix: &str, url: String, tenant_id: Option<String>) -> RemoteWriteConfig {
+    let auth = if let Some(token) = secret(prefix, "BEARER_TOKEN") {
+        Auth::Bearer(token)
+    } else if let Some(username) = var(prefix, "BASIC_AUTH_USERNAME") {
+        Auth::Basic {
+            username,
+            password: secret(prefix, "BASIC_AUTH_PASSWORD").unwrap_or(Secret::Value(String::new())),
+        }
+    } else {
+        Auth::None
+    };
+
+    RemoteWriteConfig {
+        name: name.to_string(),
+        url,
+        tenant_id,
+        auth,
+        headers: var(prefix, "HEADERS").map(|s| parse_pairs(&s)).unwrap_or_default(),
+        ca_file: var(prefix, "CA_FILE"),
+        client_cert_file: var(prefix, "CLIENT_CERT_FILE"),
+        client_key_file: var(prefix, "CLIENT_KEY_FILE"),
+    }
+}
+
+/// The default endpoint comes from the `MIMIR_*` variables (set `MIMIR_URL=`
+/// to disable it). Extra endpoints are listed by name in
+/// `REMOTE_WRITE_ENDPOINTS` and configured through `REMOTE_WRITE_<NAME>_*`.
+pub fn load() -> Vec<RemoteWriteConfig> {
+    let mut endpoints = Vec::new();
+
+    let mimir_url = env::var("MIMIR_URL")
+        .unwrap_or_else(|_| "http://localhost:9009/api/v1/push".to_string());
+    if !mimir_url.trim().is_empty() {
+        // Mimir with multitenancy disabled expects the "anonymous" tenant.
+        let tenant_id = match env::var("MIMIR_TENANT_ID") {
+            Ok(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+            Err(_) => Some("anonymous".to_string()),
+        };
+        endpoints.push(load_endpoint("mimir", "MIMIR", mimir_url.trim().to_string(), tenant_id));
+    }
+
+    let names = env::var("REMOTE_WRITE_ENDPOINTS").unwrap_or_default();
+    for name in names.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let prefix = format!("REMOTE_WRITE_{}", name.to_uppercase().replace('-', "_"));
+        let Some(url) = var(&prefix, "URL") else {
+            eprintln!("Remote write endpoint '{}' has no {}_URL, skipping", name, prefix);
+            continue;
+        };
+        let tenant_id = var(&prefix, "TENANT_ID");
+        endpoints.push(load_endpoint(name, &prefix, url, tenant_id));
+    }
+
+    endpoints
+}
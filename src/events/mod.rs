@@ -0,0 +1,209 @@
+//! Live probe events for other tools: every packet outcome, MTU probe result
+//! and outage transition, as one JSON object per event. Served as NDJSON on a
+//! Unix socket (`EVENTS_SOCKET`) and, with the HTTP server, at `/api/events`
+//! (NDJSON, or WebSocket text messages when upgraded).
+//!
+//! Event types: `sent`, `received` and `reordered` (a reply that arrived
+//! after a later packet's), `duplicated`, `lost` (once the loss timeout has
+//! passed), `late` (a reply after the loss timeout), `failed` (a send that
+//! failed locally), `icmp_error` (a router's ICMP error about the packet),
+//! `mtu`, `outage_started` and `outage_ended`. `timestamp_us` is the packet's
+//! send time, the probe time for `mtu`, and the outage start or end for
+//! outage events.
+//!
+//! Consumers choose sources with `source=<name>[,<name>...]` (`*` for all):
+//! a query parameter over HTTP, or a line written at any time on the socket
+//! or WebSocket, which replaces the current filter.
+
+pub mod unix;
+
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::json;
+use crate::model::IcmpError;
+use crate::outage::Outage;
+
+/// Events buffered per consumer; slower consumers are told how many they
+/// missed.
+const CAPACITY: usize = 4096;
+
+pub enum Event<'a> {
+    Sent { size: u32 },
+    Received { latency: u64, size: u32, reordered: bool },
+    Duplicated,
+    Lost { size: u32 },
+    Failed { size: u32, error: &'a str },
+    Late { latency: u64, size: u32 },
+    Mtu { mtu: u32 },
+    Icmp(&'a IcmpError),
+    OutageStarted,
+    OutageEnded(&'a Outage),
+}
+
+/// One encoded event and the source it belongs to, for filtering.
+#[derive(Debug)]
+pub struct Message {
+    pub source: Arc<str>,
+    pub line: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Arc<Message>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        EventBus { tx: broadcast::channel(CAPACITY).0 }
+    }
+
+    pub fn publisher(&self, source: &str) -> Publisher {
+        Publisher { source: source.into(), bus: self.clone() }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Message>> {
+        self.tx.subscribe()
+    }
+}
+
+/// Publishes the events of one source.
+#[derive(Debug, Clone)]
+pub struct Publisher {
+    source: Arc<str>,
+    bus: EventBus,
+}
+
+impl Publisher {
+    pub fn publish(&self, timestamp: u128, event: Event) {
+        // Nothing is encoded while nobody listens.
+        if self.bus.tx.receiver_count() == 0 {
+            return;
+        }
+        let (kind, mut fields) = match event {
+            Event::Sent { size } => ("sent", vec![("size", size.to_string())]),
+            Event::Received { latency, size, reordered } => (
+                if reordered { "reordered" } else { "received" },
+                vec![("latency_us", latency.to_string()), ("size", size.to_string())],
+            ),
+            Event::Duplicated => ("duplicated", Vec::new()),
+            Event::Lost { size } => ("lost", vec![("size", size.to_string())]),
+            Event::Failed { size, error } => ("failed", vec![("size", size.to_string()), ("error", json::string(error))]),
+            Event::Late { latency, size } => {
+                ("late", vec![("latency_us", latency.to_string()), ("size", size.to_string())])
+            }
+            Event::Mtu { mtu } => ("mtu", vec![("mtu", mtu.to_string())]),
+            Event::Icmp(e) => (
+                "icmp_error",
+                vec![
+                    ("type", json::string(e.kind.name())),
+                    ("router", e.router.map_or("null".to_string(), |r| json::string(&r.to_string()))),
+                    ("mtu", e.mtu.map_or("null".to_string(), |mtu| mtu.to_string())),
+                ],
+            ),
+            Event::OutageStarted => ("outage_started", Vec::new()),
+            Event::OutageEnded(o) => (
+                "outage_ended",
+                vec![
+                    ("start_us", o.start.to_string()),
+                    ("duration_s", json::number(o.duration_micros(timestamp) as f64 / 1e6)),
+                    ("packets_sent", o.packets_sent.to_string()),
+                    ("packets_lost", o.packets_lost.to_string()),
+                    ("peak_loss", json::number(o.peak_loss)),
+                ],
+            ),
+        };
+        let mut all = vec![
+            ("type", json::string(kind)),
+            ("source", json::string(&self.source)),
+            ("timestamp_us", timestamp.to_string()),
+        ];
+        all.append(&mut fields);
+        let message = Message { source: Arc::clone(&self.source), line: json::object(&all) };
+        let _ = self.bus.tx.send(Arc::new(message));
+    }
+}
+
+// ── Consumers ─────────────────────────────────────────────────────────────────
+
+/// The sources a consumer wants; None for all of them.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    sources: Option<Vec<String>>,
+}
+
+impl Filter {
+    /// `<name>[,<name>...]`, or `*` or nothing for every source.
+    pub fn parse(list: &str) -> Self {
+        let sources: Vec<String> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect();
+        let all = sources.is_empty() || sources.iter().any(|s| s == "*");
+        Filter { sources: (!all).then_some(sources) }
+    }
+
+    /// A `source=...` line sent by a consumer.
+    pub fn parse_line(line: &str) -> Option<Self> {
+        line.trim().strip_prefix("source=").map(Filter::parse)
+    }
+
+    fn matches(&self, source: &str) -> bool {
+        self.sources.as_ref().is_none_or(|s| s.iter().any(|name| name == source))
+    }
+}
+
+/// What a consumer's reader task tells its writer.
+pub enum Control {
+    Filter(Filter),
+    /// Bytes to write as-is, such as a WebSocket pong.
+    Reply(Vec<u8>),
+    Close,
+}
+
+/// Write matching events to `out`, each encoded by `frame`, until the
+/// consumer goes away. The control channel closing means it disconnected.
+pub async fn forward<W: AsyncWrite + Unpin>(
+    mut out: W,
+    bus: &EventBus,
+    mut filter: Filter,
+    mut control: mpsc::Receiver<Control>,
+    frame: impl Fn(&str) -> Vec<u8>,
+) {
+    let mut rx = bus.subscribe();
+    loop {
+        let bytes = tokio::select! {
+            message = rx.recv() => match message {
+                Ok(m) if filter.matches(&m.source) => frame(&m.line),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(missed)) => frame(&json::object(&[
+                    ("type", json::string("lagged")),
+                    ("missed", missed.to_string()),
+                ])),
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            control = control.recv() => match control {
+                Some(Control::Filter(f)) => {
+                    filter = f;
+                    continue;
+                }
+                Some(Control::Reply(bytes)) => bytes,
+                Some(Control::Close) | None => return,
+            },
+        };
+        if out.write_all(&bytes).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// NDJSON framing.
+pub fn line(json: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(json.len() + 1);
+    bytes.extend_from_slice(json.as_bytes());
+    bytes.push(b'\n');
+    bytes
+}
@@ -0,0 +1,58 @@
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+
+use super::{forward, line, Control, EventBus, Filter};
+use crate::json;
+
+/// Serve NDJSON events to every client of the socket at `path`.
+pub async fn start_socket(path: String, bus: EventBus) {
+    // A socket file left by a previous run would make bind fail.
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Event stream: cannot listen on {}: {}", path, e);
+            return;
+        }
+    };
+    // Owner only: the stream reveals every probe, so no umask default.
+    if let Err(e) = std::fs::set_permissions(&path, Permissions::from_mode(0o600)) {
+        eprintln!("Event stream: cannot restrict {}: {}", path, e);
+        let _ = std::fs::remove_file(&path);
+        return;
+    }
+    println!("Event stream listening on {}", path);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve(stream, bus.clone()));
+            }
+            Err(e) => eprintln!("Event stream: accept failed: {}", e),
+        }
+    }
+}
+
+async fn serve(stream: UnixStream, bus: EventBus) {
+    let (read, write) = stream.into_split();
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(l)) = lines.next_line().await {
+            let control = match Filter::parse_line(&l) {
+                Some(filter) => Control::Filter(filter),
+                None if l.trim().is_empty() => continue,
+                None => Control::Reply(line(&json::object(&[
+                    ("type", json::string("error")),
+                    ("error", json::string("expected source=<name>[,<name>...]")),
+                ]))),
+            };
+            if tx.send(control).await.is_err() {
+                return;
+            }
+        }
+    });
+    forward(write, &bus, Filter::default(), rx, line).await;
+}
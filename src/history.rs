@@ -0,0 +1,783 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::analysis::burst::{BurstStats, BurstTracker, LossRunCounter};
+use crate::analysis::sla::{SlaThresholds, SlaTracker};
+use crate::clock;
+use crate::events::{Event, Publisher};
+use crate::model::{IcmpError, Packet, PacketState};
+use crate::outage::{OutageDetector, OutageThresholds};
+
+/// RTT and windowed loss statistics only consider packets sent within this window.
+pub const RTT_WINDOW_MICROS: u128 = 60 * 1_000_000;
+
+/// Upper bounds of the RTT histogram buckets; a final overflow bucket catches the rest.
+pub const RTT_BUCKETS_MICROS: [u64; 10] = [
+    1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000,
+];
+
+/// Running counters over every packet currently held in a history.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    /// Excludes packets whose send failed.
+    pub sent: u64,
+    /// Still within the loss timeout.
+    pub pending: u64,
+    pub received: u64,
+    /// Includes late packets.
+    pub lost: u64,
+    /// Lost packets whose reply arrived after the loss timeout.
+    pub late: u64,
+    pub reordered: u64,
+    pub duplicated: u64,
+    /// Sends that failed locally.
+    pub failed: u64,
+    /// Received packets per RTT bucket (not cumulative).
+    pub rtt_buckets: [u64; RTT_BUCKETS_MICROS.len() + 1],
+    pub rtt_sum_micros: u64,
+}
+
+fn rtt_bucket(latency: u64) -> usize {
+    RTT_BUCKETS_MICROS.partition_point(|&bound| bound < latency)
+}
+
+impl Totals {
+    fn add(&mut self, p: &Packet) {
+        if p.is_failed() {
+            self.failed += 1;
+            return;
+        }
+        self.sent += 1;
+        if p.duplicate {
+            self.duplicated += 1;
+            return;
+        }
+        match p.state {
+            PacketState::Pending => self.pending += 1,
+            PacketState::Failed => {}
+            PacketState::Lost => self.lost += 1,
+            PacketState::Late => {
+                self.lost += 1;
+                self.late += 1;
+            }
+            PacketState::Received => {
+                self.received += 1;
+                if p.reordered {
+                    self.reordered += 1;
+                }
+                self.rtt_buckets[rtt_bucket(p.latency)] += 1;
+                self.rtt_sum_micros += p.latency;
+            }
+        }
+    }
+
+    fn sub(&mut self, p: &Packet) {
+        if p.is_failed() {
+            self.failed -= 1;
+            return;
+        }
+        self.sent -= 1;
+        if p.duplicate {
+            self.duplicated -= 1;
+            return;
+        }
+        match p.state {
+            PacketState::Pending => self.pending -= 1,
+            PacketState::Failed => {}
+            PacketState::Lost => self.lost -= 1,
+            PacketState::Late => {
+                self.lost -= 1;
+                self.late -= 1;
+            }
+            PacketState::Received => {
+                self.received -= 1;
+                if p.reordered {
+                    self.reordered -= 1;
+                }
+                self.rtt_buckets[rtt_bucket(p.latency)] -= 1;
+                self.rtt_sum_micros -= p.latency;
+            }
+        }
+    }
+}
+
+/// Packets sent within one interval of a range query.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bucket {
+    /// Interval start, microseconds since epoch.
+    pub start: u128,
+    pub sent: u64,
+    pub received: u64,
+    pub lost: u64,
+    pub reordered: u64,
+    pub duplicated: u64,
+    pub rtt_min: Option<u64>,
+    pub rtt_max: Option<u64>,
+    pub rtt_sum: u64,
+}
+
+impl Bucket {
+    pub fn rtt_avg(&self) -> Option<f64> {
+        (self.received > 0).then(|| self.rtt_sum as f64 / self.received as f64)
+    }
+}
+
+/// Settled packets of one probe-size range.
+#[derive(Debug, Clone, Default)]
+pub struct SizeBucket {
+    pub sent: u64,
+    pub lost: u64,
+    pub rtts: Vec<u64>,
+}
+
+/// Packet history with aggregates maintained as packets are sent, received
+/// and expire, so reading them costs O(1) regardless of retention.
+///
+/// All mutation must go through these methods to keep the aggregates exact.
+#[derive(Debug, Default)]
+pub struct PacketHistory {
+    packets: VecDeque<Packet>,
+    /// Slot number of the oldest packet. Slots count every packet pushed, so
+    /// they stay valid while older packets are evicted.
+    first_slot: u64,
+    totals: Totals,
+    /// The same counts over every packet settled since construction. Unlike
+    /// `totals` they never go down as packets are evicted or pruned, so they
+    /// can be exported as counters.
+    counters: Totals,
+    /// (slot, send timestamp, latency) of received packets, oldest first.
+    recent_rtts: VecDeque<(u64, u128, u64)>,
+    /// (send timestamp, user-space minus kernel RTT) of replies the kernel
+    /// timestamped, oldest first.
+    recent_host_delays: VecDeque<(u128, u64)>,
+    /// (send timestamp, error) of packets a router sent an ICMP error
+    /// about, in send order; evicted with their packets.
+    icmp_errors: VecDeque<(u128, IcmpError)>,
+    /// Time a reply may take before its packet counts as lost.
+    loss_timeout: u64,
+    /// Loss runs over the first `settled` packets, i.e. those whose loss
+    /// timeout has passed, so that they are received or lost for good.
+    bursts: BurstTracker,
+    settled: usize,
+    /// Loss runs over every packet settled since construction, like `counters`.
+    loss_runs: LossRunCounter,
+    /// The same, restricted to settled packets sent within the RTT window.
+    recent_bursts: BurstTracker,
+    recent_settled: VecDeque<u128>,
+    /// Availability slots over every settled packet, including reloaded ones.
+    sla: SlaTracker,
+    /// Only sees packets settled after construction, so reloading history
+    /// does not re-detect outages that are already in the log.
+    outages: Option<OutageDetector>,
+    /// Receives a `lost` event as each lost packet settles.
+    events: Option<Publisher>,
+    /// Monotonic time of the newest push, to carry the history's timeline on
+    /// while the wall clock is behind its newest packet.
+    pushed_at: u64,
+}
+
+fn now_micros() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
+}
+
+impl PacketHistory {
+    pub fn new(
+        mut packets: VecDeque<Packet>,
+        loss_timeout_micros: u64,
+        thresholds: OutageThresholds,
+        sla_thresholds: SlaThresholds,
+    ) -> Self {
+        let mut totals = Totals::default();
+        let cutoff = now_micros().saturating_sub(RTT_WINDOW_MICROS);
+        let mut recent_rtts = VecDeque::new();
+        let mut next = 0;
+        for (slot, p) in packets.iter_mut().enumerate() {
+            // A file saved across a backward clock step, as in `push`.
+            p.timestamp = p.timestamp.max(next);
+            next = p.timestamp + 1;
+            totals.add(p);
+            if p.timestamp >= cutoff && !p.duplicate && p.is_received() {
+                recent_rtts.push_back((slot as u64, p.timestamp, p.latency));
+            }
+        }
+        let mut history = Self {
+            packets,
+            totals,
+            recent_rtts,
+            loss_timeout: loss_timeout_micros,
+            sla: SlaTracker::new(sla_thresholds),
+            pushed_at: clock::monotonic_micros(),
+            ..Default::default()
+        };
+        history.settle(history.now());
+        history.outages = Some(OutageDetector::new(thresholds));
+        history
+    }
+
+    /// Mark packets still pending past the loss timeout as lost, and feed
+    /// every packet whose loss timeout has passed into the trackers.
+    fn settle(&mut self, now: u128) {
+        let cutoff = now.saturating_sub(self.loss_timeout as u128);
+        let window_start = now.saturating_sub(RTT_WINDOW_MICROS);
+        while let Some(p) = self.packets.get_mut(self.settled) {
+            if p.timestamp >= cutoff {
+                break;
+            }
+            if p.is_pending() {
+                self.totals.sub(p);
+                p.state = PacketState::Lost;
+                self.totals.add(p);
+            }
+            let p = &self.packets[self.settled];
+            self.counters.add(p);
+            if p.is_failed() {
+                self.settled += 1;
+                continue;
+            }
+            self.bursts.push(p.is_lost());
+            self.loss_runs.push(p.is_lost());
+            self.sla.push(p);
+            if let (Some(events), true) = (&self.events, p.is_lost() && !p.duplicate) {
+                events.publish(p.timestamp, Event::Lost { size: p.size });
+            }
+            if let Some(detector) = &mut self.outages {
+                detector.record(p.timestamp, p.is_lost());
+            }
+            if p.timestamp >= window_start {
+                self.recent_bursts.push(p.is_lost());
+                self.recent_settled.push_back(p.timestamp);
+            }
+            self.settled += 1;
+        }
+        while self.recent_settled.front().is_some_and(|&ts| ts < window_start) {
+            self.recent_settled.pop_front();
+            self.recent_bursts.pop_front();
+        }
+    }
+
+    /// Current time on the history's timeline: the wall clock, or, while that
+    /// is behind the newest packet after stepping back, the newest send time
+    /// plus the monotonic time since it was pushed.
+    fn now_at(&self, wall: u128, monotonic: u64) -> u128 {
+        match self.packets.back() {
+            Some(last) => wall.max(last.timestamp + monotonic.saturating_sub(self.pushed_at) as u128),
+            None => wall,
+        }
+    }
+
+    fn now(&self) -> u128 {
+        self.now_at(now_micros(), clock::monotonic_micros())
+    }
+
+    pub fn set_events(&mut self, publisher: Publisher) {
+        self.events = Some(publisher);
+    }
+
+    pub fn loss_timeout(&self) -> u64 {
+        self.loss_timeout
+    }
+
+    /// Bring packet states up to date: pending ones past the loss timeout
+    /// become lost.
+    pub fn refresh(&mut self) {
+        self.settle(self.now());
+    }
+
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Packet> {
+        self.packets.iter()
+    }
+
+    /// Packets sent in [from, to), found by binary search on the send time.
+    pub fn range(&self, from: u128, to: u128) -> impl DoubleEndedIterator<Item = &Packet> {
+        let start = self.packets.partition_point(|p| p.timestamp < from);
+        let end = self.packets.partition_point(|p| p.timestamp < to);
+        self.packets.range(start..end.max(start))
+    }
+
+    /// Packets sent in [from, to) aggregated per `step`. Every interval gets a
+    /// bucket, empty ones included; packets still awaiting a reply are left out.
+    pub fn buckets(&mut self, from: u128, to: u128, step: u128) -> Vec<Bucket> {
+        self.refresh();
+        let step = step.max(1);
+        let count = to.saturating_sub(from).div_ceil(step) as usize;
+        let mut buckets: Vec<Bucket> = (0..count)
+            .map(|i| Bucket { start: from + i as u128 * step, ..Default::default() })
+            .collect();
+        for p in self.range(from, to) {
+            if (p.is_pending() && !p.duplicate) || p.is_failed() {
+                continue;
+            }
+            let b = &mut buckets[((p.timestamp - from) / step) as usize];
+            b.sent += 1;
+            if p.duplicate {
+                b.duplicated += 1;
+            } else if !p.is_received() {
+                b.lost += 1;
+            } else {
+                b.received += 1;
+                if p.reordered {
+                    b.reordered += 1;
+                }
+                b.rtt_min = Some(b.rtt_min.map_or(p.latency, |v| v.min(p.latency)));
+                b.rtt_max = Some(b.rtt_max.map_or(p.latency, |v| v.max(p.latency)));
+                b.rtt_sum += p.latency;
+            }
+        }
+        buckets
+    }
+
+    /// Settled, non-duplicate packets sent in [from, to) split by size, by
+    /// the inclusive upper `bounds` of each bucket; larger packets go in the last.
+    pub fn size_breakdown(&mut self, from: u128, to: u128, bounds: &[u32]) -> Vec<SizeBucket> {
+        self.refresh();
+        let mut buckets = vec![SizeBucket::default(); bounds.len()];
+        if buckets.is_empty() {
+            return buckets;
+        }
+        for p in self.range(from, to) {
+            if p.is_pending() || p.is_failed() || p.duplicate {
+                continue;
+            }
+            let b = &mut buckets[bounds.partition_point(|&bound| bound < p.size).min(bounds.len() - 1)];
+            b.sent += 1;
+            if p.is_received() {
+                b.rtts.push(p.latency);
+            } else {
+                b.lost += 1;
+            }
+        }
+        buckets
+    }
+
+    /// Lifetime counters: see `counters`.
+    pub fn counters(&mut self) -> Totals {
+        self.refresh();
+        self.counters
+    }
+
+    /// Totals of the packets sent before `t`.
+    pub fn totals_before(&mut self, t: u128) -> Totals {
+        self.refresh();
+        let mut totals = self.totals;
+        let start = self.packets.partition_point(|p| p.timestamp < t);
+        for p in self.packets.range(start..) {
+            totals.sub(p);
+        }
+        totals
+    }
+
+    /// Append a packet, evicting the oldest one once `max_len` is reached.
+    /// Returns its slot.
+    ///
+    /// Lookups, settling and pruning rely on send times increasing, so after
+    /// the wall clock steps back packets are dated on the history's timeline
+    /// (see `now_at`) until it catches up.
+    pub fn push(&mut self, packet: Packet, max_len: usize) -> u64 {
+        self.push_at(packet, max_len, clock::monotonic_micros())
+    }
+
+    fn push_at(&mut self, mut packet: Packet, max_len: usize, monotonic: u64) -> u64 {
+        if let Some(last) = self.packets.back().filter(|last| packet.timestamp <= last.timestamp) {
+            let elapsed = monotonic.saturating_sub(self.pushed_at).max(1);
+            packet.timestamp = last.timestamp + elapsed as u128;
+        }
+        self.pushed_at = monotonic;
+        if self.packets.len() >= max_len {
+            self.pop_front();
+        }
+        self.totals.add(&packet);
+        let slot = self.first_slot + self.packets.len() as u64;
+        if !packet.duplicate && packet.is_received() {
+            self.recent_rtts.push_back((slot, packet.timestamp, packet.latency));
+        }
+        let now = packet.timestamp;
+        self.packets.push_back(packet);
+        self.settle(now);
+        slot
+    }
+
+    fn pop_front(&mut self) -> Option<Packet> {
+        let p = self.packets.pop_front()?;
+        self.first_slot += 1;
+        let oldest = self.packets.front().map_or(u128::MAX, |p| p.timestamp);
+        while self.icmp_errors.front().is_some_and(|&(ts, _)| ts < oldest) {
+            self.icmp_errors.pop_front();
+        }
+        self.totals.sub(&p);
+        if self.settled > 0 {
+            // Failed packets settle without entering the loss runs.
+            if !p.is_failed() {
+                self.bursts.pop_front();
+            }
+            self.settled -= 1;
+        }
+        Some(p)
+    }
+
+    /// Modify the packet in `slot` in place, re-accounting it in the
+    /// aggregates. False if the packet has been evicted.
+    pub fn update(&mut self, slot: u64, f: impl FnOnce(&mut Packet)) -> bool {
+        let index = slot.checked_sub(self.first_slot).and_then(|i| usize::try_from(i).ok());
+        let Some(packet) = index.and_then(|i| self.packets.get_mut(i)) else { return false };
+        let was_received = !packet.duplicate && packet.is_received();
+        let (was_late, was_duplicate) = (packet.state == PacketState::Late, packet.duplicate);
+        self.totals.sub(packet);
+        f(packet);
+        self.totals.add(packet);
+        let is_received = !packet.duplicate && packet.is_received();
+        // A settled packet is already in the counters; only count what
+        // happened to it since.
+        if index.is_some_and(|i| i < self.settled) {
+            if !was_duplicate && packet.duplicate {
+                self.counters.duplicated += 1;
+            } else if !was_late && packet.state == PacketState::Late && !packet.duplicate {
+                self.counters.late += 1;
+            }
+        }
+        if !was_received && is_received {
+            self.recent_rtts.push_back((slot, packet.timestamp, packet.latency));
+        } else if was_received && !is_received {
+            // E.g. a second reply made it a duplicate: the totals no longer
+            // count its RTT, so neither may the window.
+            if let Some(i) = self.recent_rtts.iter().rposition(|&(s, _, _)| s == slot) {
+                self.recent_rtts.remove(i);
+            }
+        }
+        true
+    }
+
+    /// Note an ICMP error a router returned about the packet in `slot`.
+    /// Returns the packet's send time, or None if it has been evicted.
+    pub fn record_icmp_error(&mut self, slot: u64, error: IcmpError) -> Option<u128> {
+        let index = slot.checked_sub(self.first_slot).and_then(|i| usize::try_from(i).ok());
+        let timestamp = self.packets.get(index?)?.timestamp;
+        let at = self.icmp_errors.partition_point(|&(ts, _)| ts <= timestamp);
+        self.icmp_errors.insert(at, (timestamp, error));
+        Some(timestamp)
+    }
+
+    /// ICMP error returned about the packet sent at `timestamp`, if any.
+    pub fn icmp_error(&self, timestamp: u128) -> Option<&IcmpError> {
+        let i = self.icmp_errors.partition_point(|&(ts, _)| ts < timestamp);
+        self.icmp_errors.get(i).filter(|&&(ts, _)| ts == timestamp).map(|(_, e)| e)
+    }
+
+    /// ICMP errors about the packets held, by their packet's send time, in
+    /// send order.
+    pub fn icmp_errors(&self) -> impl Iterator<Item = &(u128, IcmpError)> {
+        self.icmp_errors.iter()
+    }
+
+    /// Put back ICMP errors saved with the history, dropping those about
+    /// packets no longer held.
+    pub fn restore_icmp_errors(&mut self, mut errors: VecDeque<(u128, IcmpError)>) {
+        let oldest = self.packets.front().map_or(u128::MAX, |p| p.timestamp);
+        errors.retain(|&(ts, _)| ts >= oldest);
+        self.icmp_errors = errors;
+    }
+
+    /// Drop packets sent before `cutoff`. Packets are stored in send order, so
+    /// this only touches the expired prefix. Returns the number removed.
+    pub fn prune_older_than(&mut self, cutoff: u128) -> usize {
+        let mut removed = 0;
+        while self.packets.front().is_some_and(|p| p.timestamp < cutoff) {
+            self.pop_front();
+            removed += 1;
+        }
+        self.sla.prune_older_than(cutoff);
+        removed
+    }
+
+    /// Latencies of packets received within the RTT window, in arrival order.
+    pub fn recent_rtts(&mut self) -> Vec<u64> {
+        let cutoff = self.now().saturating_sub(RTT_WINDOW_MICROS);
+        while self.recent_rtts.front().is_some_and(|&(_, ts, _)| ts < cutoff) {
+            self.recent_rtts.pop_front();
+        }
+        self.recent_rtts.iter().map(|&(_, _, latency)| latency).collect()
+    }
+
+    /// Record how much user-space scheduling added to a reply's RTT.
+    pub fn record_host_delay(&mut self, timestamp: u128, delay: u64) {
+        let cutoff = timestamp.saturating_sub(RTT_WINDOW_MICROS);
+        while self.recent_host_delays.front().is_some_and(|&(ts, _)| ts < cutoff) {
+            self.recent_host_delays.pop_front();
+        }
+        self.recent_host_delays.push_back((timestamp, delay));
+    }
+
+    /// Host-induced delays of replies within the RTT window, in arrival order.
+    pub fn recent_host_delays(&mut self) -> Vec<u64> {
+        let cutoff = self.now().saturating_sub(RTT_WINDOW_MICROS);
+        while self.recent_host_delays.front().is_some_and(|&(ts, _)| ts < cutoff) {
+            self.recent_host_delays.pop_front();
+        }
+        self.recent_host_delays.iter().map(|&(_, delay)| delay).collect()
+    }
+
+    /// Outage detector, brought up to date with the packets settled by now.
+    pub fn outages(&mut self) -> Option<&mut OutageDetector> {
+        self.settle(self.now());
+        self.outages.as_mut()
+    }
+
+    /// Loss-burst statistics over all settled packets.
+    pub fn burst_stats(&mut self) -> BurstStats {
+        self.settle(self.now());
+        self.bursts.stats()
+    }
+
+    /// Completed loss runs since construction.
+    pub fn loss_runs(&mut self) -> LossRunCounter {
+        self.settle(self.now());
+        self.loss_runs
+    }
+
+    /// Loss-burst statistics over settled packets sent within the RTT window.
+    pub fn window_burst_stats(&mut self) -> BurstStats {
+        self.settle(self.now());
+        self.recent_bursts.stats()
+    }
+
+    /// Availability tracker, brought up to date with the packets settled by now.
+    pub fn sla(&mut self) -> &SlaTracker {
+        self.settle(self.now());
+        &self.sla
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::inflight::InFlight;
+    use crate::network::listener::SeenWindow;
+
+    const T0: u128 = 1_700_000_000_000_000;
+    const LOSS_TIMEOUT: u64 = 1_000_000;
+    const MS: u128 = 1_000;
+    const THRESHOLDS: OutageThresholds =
+        OutageThresholds { min_losses: 5, min_silence_micros: 5_000_000, recovery_packets: 5 };
+
+    /// A history fed the way the listener feeds it, at fixed times.
+    struct Prober {
+        history: PacketHistory,
+        in_flight: InFlight<u64>,
+        seen: SeenWindow,
+        max_len: usize,
+        /// Monotonic clock, advanced 10 ms per send.
+        monotonic: u64,
+    }
+
+    impl Prober {
+        fn new(max_len: usize) -> Self {
+            Prober {
+                history: PacketHistory::new(VecDeque::new(), LOSS_TIMEOUT, THRESHOLDS, SlaThresholds::default()),
+                in_flight: InFlight::new(max_len * 2),
+                seen: SeenWindow::default(),
+                max_len,
+                monotonic: 0,
+            }
+        }
+
+        /// Send packet `counter`, dated 10 ms apart by the wall clock.
+        fn send(&mut self, counter: u64) {
+            let packet = Packet::pending(T0 + counter as u128 * 10 * MS, 100);
+            self.monotonic += 10 * MS as u64;
+            let slot = self.history.push_at(packet, self.max_len, self.monotonic);
+            self.in_flight.insert(counter, slot);
+        }
+
+        fn reply(&mut self, counter: u64, latency: u64) -> bool {
+            let (is_duplicate, is_reordered) = self.seen.observe(counter);
+            let Some(slot) = self.in_flight.get(&counter) else { return false };
+            let loss_timeout = self.history.loss_timeout();
+            self.history.update(slot, |packet| {
+                if is_duplicate {
+                    packet.duplicate = true;
+                } else {
+                    packet.reply(latency, loss_timeout);
+                    packet.reordered = is_reordered;
+                }
+            })
+        }
+
+        fn states(&self) -> Vec<PacketState> {
+            self.history.iter().map(|p| p.state).collect()
+        }
+    }
+
+    #[test]
+    fn unanswered_packet_is_lost_once_settled() {
+        let mut prober = Prober::new(100);
+        prober.send(0);
+        prober.send(1);
+        prober.reply(1, 5_000);
+
+        prober.history.settle(T0 + 500 * MS);
+        assert_eq!(prober.states(), [PacketState::Pending, PacketState::Received]);
+        assert_eq!(prober.history.totals.pending, 1);
+
+        prober.history.settle(T0 + 2_000 * MS);
+        assert_eq!(prober.states(), [PacketState::Lost, PacketState::Received]);
+        let totals = prober.history.totals;
+        assert_eq!((totals.sent, totals.pending, totals.received, totals.lost), (2, 0, 1, 1));
+        assert_eq!(prober.history.settled, 2);
+    }
+
+    #[test]
+    fn reply_after_the_loss_timeout_is_late() {
+        let mut prober = Prober::new(100);
+        prober.send(0);
+        prober.history.settle(T0 + 1_500 * MS);
+        assert_eq!(prober.states(), [PacketState::Lost]);
+
+        assert!(prober.reply(0, 1_400_000));
+        assert_eq!(prober.states(), [PacketState::Late]);
+        assert_eq!(prober.history.iter().next().unwrap().latency, 1_400_000);
+        let totals = prober.history.totals;
+        assert_eq!((totals.sent, totals.received, totals.lost, totals.late), (1, 0, 1, 1));
+        assert_eq!(totals.rtt_sum_micros, 0);
+        assert!(prober.history.recent_rtts.is_empty());
+    }
+
+    #[test]
+    fn second_reply_marks_a_duplicate() {
+        let mut prober = Prober::new(100);
+        prober.send(0);
+        prober.send(1);
+        prober.reply(0, 5_000);
+        prober.reply(1, 6_000);
+        assert_eq!(prober.history.recent_rtts.len(), 2);
+
+        assert!(prober.reply(0, 7_000));
+        prober.history.settle(T0 + 2_000 * MS);
+        let packet = prober.history.iter().next().unwrap();
+        assert!(packet.duplicate);
+        let totals = prober.history.totals;
+        assert_eq!((totals.sent, totals.received, totals.duplicated, totals.lost), (2, 1, 1, 0));
+        assert_eq!(totals.rtt_sum_micros, 6_000);
+        assert_eq!(prober.history.recent_rtts.iter().map(|&(_, _, rtt)| rtt).collect::<Vec<_>>(), [6_000]);
+    }
+
+    #[test]
+    fn replies_out_of_order_are_reordered() {
+        let mut prober = Prober::new(100);
+        for counter in 0..4 {
+            prober.send(counter);
+        }
+        for counter in [0, 2, 1, 3] {
+            prober.reply(counter, 5_000);
+        }
+        prober.history.settle(T0 + 2_000 * MS);
+        let reordered: Vec<bool> = prober.history.iter().map(|p| p.reordered).collect();
+        assert_eq!(reordered, [false, true, false, false]);
+        let totals = prober.history.totals;
+        assert_eq!((totals.sent, totals.received, totals.reordered, totals.lost), (4, 4, 1, 0));
+    }
+
+    #[test]
+    fn evicts_the_oldest_packets_at_capacity() {
+        let mut prober = Prober::new(3);
+        for counter in 0..5 {
+            prober.send(counter);
+        }
+        assert_eq!(prober.history.len(), 3);
+        assert_eq!(prober.history.first_slot, 2);
+        assert_eq!(prober.history.iter().next().unwrap().timestamp, T0 + 20 * MS);
+
+        // An evicted packet's reply finds its slot gone.
+        assert!(!prober.reply(0, 5_000));
+        assert!(prober.reply(3, 5_000));
+        prober.history.settle(T0 + 2_000 * MS);
+        assert_eq!(prober.states(), [PacketState::Lost, PacketState::Received, PacketState::Lost]);
+        let totals = prober.history.totals;
+        assert_eq!((totals.sent, totals.received, totals.lost), (3, 1, 2));
+
+        // Settled packets evicted later leave the totals too.
+        prober.send(5);
+        assert_eq!(prober.states(), [PacketState::Received, PacketState::Lost, PacketState::Pending]);
+        let totals = prober.history.totals;
+        assert_eq!((totals.sent, totals.received, totals.lost, totals.pending), (3, 1, 1, 1));
+        assert_eq!(prober.history.settled, 2);
+    }
+
+    #[test]
+    fn counters_only_grow() {
+        let mut prober = Prober::new(2);
+        prober.send(0);
+        prober.send(1);
+        prober.reply(1, 5_000);
+        prober.history.settle(T0 + 2_000 * MS);
+        let counters = prober.history.counters;
+        assert_eq!((counters.sent, counters.received, counters.lost), (2, 1, 1));
+
+        // A late reply and a duplicate after settling add to the counters.
+        prober.reply(0, 1_500_000);
+        prober.reply(1, 6_000);
+        let counters = prober.history.counters;
+        assert_eq!((counters.lost, counters.late, counters.duplicated), (1, 1, 1));
+        assert_eq!((counters.received, counters.rtt_buckets.iter().sum::<u64>()), (1, 1));
+
+        // Evicting both packets empties the totals but not the counters.
+        prober.send(2);
+        prober.send(3);
+        prober.history.settle(T0 + 4_000 * MS);
+        assert_eq!((prober.history.totals.sent, prober.history.totals.lost), (2, 2));
+        let counters = prober.history.counters;
+        assert_eq!((counters.sent, counters.received, counters.lost, counters.late), (4, 1, 3, 1));
+    }
+
+    #[test]
+    fn send_times_never_decrease_across_a_clock_step() {
+        let mut prober = Prober::new(100);
+        prober.send(100);
+        prober.send(101);
+        // The wall clock steps back a second.
+        prober.send(2);
+        prober.send(3);
+        let times: Vec<u128> = prober.history.iter().map(|p| p.timestamp).collect();
+        let last = T0 + 1_010 * MS;
+        assert_eq!(times, [T0 + 1_000 * MS, last, last + 10 * MS, last + 20 * MS]);
+        assert_eq!(prober.history.range(last, u128::MAX).count(), 3);
+
+        assert!(prober.reply(3, 5_000));
+        prober.history.settle(last + 2_000 * MS);
+        assert_eq!(prober.states(), [PacketState::Lost, PacketState::Lost, PacketState::Lost, PacketState::Received]);
+        assert_eq!(prober.history.prune_older_than(last + 1), 2);
+        assert_eq!(prober.history.len(), 2);
+
+        let reloaded = PacketHistory::new(
+            [5, 3, 4].into_iter().map(|ts| Packet::pending(ts, 100)).collect(),
+            LOSS_TIMEOUT,
+            THRESHOLDS,
+            SlaThresholds::default(),
+        );
+        assert_eq!(reloaded.iter().map(|p| p.timestamp).collect::<Vec<_>>(), [5, 6, 7]);
+    }
+
+    #[test]
+    fn packets_expire_while_the_wall_clock_is_behind() {
+        let mut prober = Prober::new(100);
+        prober.send(1_000);
+        prober.send(1_001);
+        // The wall clock steps back ten seconds and stays behind.
+        prober.send(2);
+        prober.send(3);
+        assert!(prober.reply(2, 5_000));
+
+        // 1.5 s later by the monotonic clock, the wall clock still reads
+        // before the first packet, yet the loss timeout has passed.
+        let now = prober.history.now_at(T0 + 1_530 * MS, prober.monotonic + 1_500_000);
+        assert_eq!(now, T0 + 10_030 * MS + 1_500 * MS);
+        prober.history.settle(now);
+        assert_eq!(prober.states(), [PacketState::Lost, PacketState::Lost, PacketState::Received, PacketState::Lost]);
+        assert_eq!(prober.history.settled, 4);
+    }
+}
@@ -0,0 +1,180 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{HttpState, Request, Response};
+use crate::json;
+use crate::model::{IcmpError, PacketState};
+use crate::source::Source;
+
+// ── JSON query API ────────────────────────────────────────────────────────────
+//
+// Times are Unix milliseconds, in parameters and responses alike, except raw
+// packet timestamps which keep their microsecond resolution.
+
+const MAX_BUCKETS: u128 = 10_000;
+const DEFAULT_PACKET_LIMIT: usize = 10_000;
+const MAX_PACKET_LIMIT: usize = 100_000;
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// The `source` parameter, `loopback` by default.
+fn source(request: &Request, state: &HttpState) -> Result<Source, Response> {
+    let name = request.param("source").unwrap_or("loopback");
+    state
+        .source(name)
+        .ok_or_else(|| Response::error(404, &format!("unknown source '{}'", name)))
+}
+
+/// `from`/`to`, defaulting to the last hour.
+fn time_range(request: &Request) -> Result<(u128, u128), Response> {
+    let to = request.ms_param("to")?.unwrap_or_else(now_ms);
+    let from = request.ms_param("from")?.unwrap_or(to.saturating_sub(3_600_000));
+    if from > to {
+        return Err(Response::error(400, "from is after to"));
+    }
+    Ok((from, to))
+}
+
+/// `GET /api/targets`: every source with its retained range and latest MTU.
+pub async fn targets(state: &HttpState) -> Response {
+    let mut items = Vec::new();
+    for source in state.sources() {
+        let (packets, first, last) = {
+            let history = source.history.lock().await;
+            let first = history.iter().next().map(|p| p.timestamp / 1000);
+            let last = history.iter().next_back().map(|p| p.timestamp / 1000);
+            (history.len(), first, last)
+        };
+        let mtu = source.mtu_history.lock().await.back().map(|&(_, mtu)| mtu);
+        let kind = source.kind.name();
+        items.push(json::object(&[
+            ("name", json::string(&source.name)),
+            ("kind", json::string(kind)),
+            ("packets", packets.to_string()),
+            ("first_ms", json::opt(first.map(|v| v as f64))),
+            ("last_ms", json::opt(last.map(|v| v as f64))),
+            ("mtu", json::opt(mtu.map(f64::from))),
+        ]));
+    }
+    Response::json(json::array(&items))
+}
+
+/// `GET /api/history?source=&from=&to=&step=`: per-step aggregates, RTTs in
+/// milliseconds. Loss counts only packets whose reply window has closed.
+pub async fn history(request: &Request, state: &HttpState) -> Response {
+    let params = (source(request, state), time_range(request), request.ms_param("step"));
+    let (source, (from, to), step) = match params {
+        (Ok(source), Ok(range), Ok(step)) => (source, range, step.unwrap_or(60_000).max(1)),
+        (Err(r), _, _) | (_, Err(r), _) | (_, _, Err(r)) => return r,
+    };
+    if (to - from).div_ceil(step) > MAX_BUCKETS {
+        return Response::error(400, &format!("more than {} steps; increase step", MAX_BUCKETS));
+    }
+
+    let buckets = source.history.lock().await.buckets(from * 1000, to * 1000, step * 1000);
+    let ms = |v: Option<u64>| json::opt(v.map(|us| us as f64 / 1000.0));
+    let items: Vec<String> = buckets
+        .iter()
+        .map(|b| {
+            let settled = b.received + b.lost;
+            json::object(&[
+                ("timestamp_ms", (b.start / 1000).to_string()),
+                ("sent", b.sent.to_string()),
+                ("received", b.received.to_string()),
+                ("lost", b.lost.to_string()),
+                ("reordered", b.reordered.to_string()),
+                ("duplicated", b.duplicated.to_string()),
+                ("loss_ratio", json::opt((settled > 0).then(|| b.lost as f64 / settled as f64))),
+                ("rtt_min_ms", ms(b.rtt_min)),
+                ("rtt_avg_ms", json::opt(b.rtt_avg().map(|us| us / 1000.0))),
+                ("rtt_max_ms", ms(b.rtt_max)),
+            ])
+        })
+        .collect();
+    Response::json(json::object(&[
+        ("source", json::string(&source.name)),
+        ("from", from.to_string()),
+        ("to", to.to_string()),
+        ("step", step.to_string()),
+        ("buckets", json::array(&items)),
+    ]))
+}
+
+fn icmp_error(e: &IcmpError) -> String {
+    json::object(&[
+        ("type", json::string(e.kind.name())),
+        ("router", e.router.map_or("null".to_string(), |r| json::string(&r.to_string()))),
+        ("mtu", e.mtu.map_or("null".to_string(), |mtu| mtu.to_string())),
+    ])
+}
+
+/// `GET /api/packets?source=&from=&to=&limit=`: raw records, oldest first.
+/// `truncated` is set when the range held more than `limit` packets.
+pub async fn packets(request: &Request, state: &HttpState) -> Response {
+    let params = (source(request, state), time_range(request), request.ms_param("limit"));
+    let (source, (from, to), limit) = match params {
+        (Ok(source), Ok(range), Ok(limit)) => (source, range, limit),
+        (Err(r), _, _) | (_, Err(r), _) | (_, _, Err(r)) => return r,
+    };
+    let limit = limit.map_or(DEFAULT_PACKET_LIMIT, |l| (l as usize).min(MAX_PACKET_LIMIT));
+
+    // Copy the records out so formatting does not hold up the prober.
+    let (packets, truncated) = {
+        let mut history = source.history.lock().await;
+        history.refresh();
+        let mut range = history.range(from * 1000, to * 1000);
+        let packets: Vec<_> = range
+            .by_ref()
+            .take(limit)
+            .map(|p| (p.clone(), history.icmp_error(p.timestamp).copied()))
+            .collect();
+        (packets, range.next().is_some())
+    };
+    let items: Vec<String> = packets
+        .iter()
+        .map(|(p, icmp)| {
+            let state = if p.duplicate { "duplicate" } else { p.state.name() };
+            let replied = p.is_received() || p.state == PacketState::Late;
+            json::object(&[
+                ("timestamp_us", p.timestamp.to_string()),
+                ("state", json::string(state)),
+                ("latency_us", json::opt(replied.then_some(p.latency as f64))),
+                ("size", p.size.to_string()),
+                ("reordered", p.reordered.to_string()),
+                ("icmp_error", icmp.as_ref().map_or("null".to_string(), icmp_error)),
+            ])
+        })
+        .collect();
+
+    Response::json(json::object(&[
+        ("source", json::string(&source.name)),
+        ("truncated", truncated.to_string()),
+        ("packets", json::array(&items)),
+    ]))
+}
+
+/// `GET /api/mtu?source=&from=&to=`: MTU probe results in the range.
+pub async fn mtu(request: &Request, state: &HttpState) -> Response {
+    let (source, (from, to)) = match (source(request, state), time_range(request)) {
+        (Ok(source), Ok(range)) => (source, range),
+        (Err(r), _) | (_, Err(r)) => return r,
+    };
+    let items: Vec<String> = source
+        .mtu_history
+        .lock()
+        .await
+        .iter()
+        .filter(|&&(ts, _)| ts >= from * 1000 && ts < to * 1000)
+        .map(|&(ts, mtu)| {
+            json::object(&[("timestamp_ms", (ts / 1000).to_string()), ("mtu", mtu.to_string())])
+        })
+        .collect();
+    Response::json(json::object(&[
+        ("source", json::string(&source.name)),
+        ("mtu", json::array(&items)),
+    ]))
+}
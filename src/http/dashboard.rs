@@ -0,0 +1,81 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{HttpState, Request, Response};
+use crate::json;
+
+/// Self-contained page: inline CSS and canvas charts, no external assets.
+const PAGE: &str = include_str!("dashboard.html");
+
+/// Upper bound on buckets per source and request.
+const MAX_BUCKETS: u128 = 5000;
+
+pub fn page() -> Response {
+    Response::html(PAGE)
+}
+
+/// `GET /dashboard/data?from=&to=&step=` (Unix milliseconds): bucketed
+/// history and MTU changes of every source.
+///
+/// Buckets are `[start, sent, received, lost, reordered, duplicated,
+/// rtt_min, rtt_avg, rtt_max]` with RTTs in milliseconds.
+pub async fn data(request: &Request, state: &HttpState) -> Response {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let (from, to, step) = match (
+        request.ms_param("from"),
+        request.ms_param("to"),
+        request.ms_param("step"),
+    ) {
+        (Ok(from), Ok(to), Ok(step)) => (from, to, step),
+        (Err(response), _, _) | (_, Err(response), _) | (_, _, Err(response)) => return response,
+    };
+    let to = to.unwrap_or(now);
+    let from = from.unwrap_or(to.saturating_sub(3_600_000)).min(to);
+    let step = step.unwrap_or((to - from) / 300).max(1000).max((to - from).div_ceil(MAX_BUCKETS));
+
+    let ms = |v: Option<u64>| json::opt(v.map(|us| us as f64 / 1000.0));
+    let mut sources = Vec::new();
+    for source in state.sources() {
+        let buckets = source.history.lock().await.buckets(from * 1000, to * 1000, step * 1000);
+        let buckets: Vec<String> = buckets
+            .iter()
+            .map(|b| {
+                json::array(&[
+                    (b.start / 1000).to_string(),
+                    b.sent.to_string(),
+                    b.received.to_string(),
+                    b.lost.to_string(),
+                    b.reordered.to_string(),
+                    b.duplicated.to_string(),
+                    ms(b.rtt_min),
+                    json::opt(b.rtt_avg().map(|us| us / 1000.0)),
+                    ms(b.rtt_max),
+                ])
+            })
+            .collect();
+
+        // The last probe before the range gives the starting value.
+        let mtu = source.mtu_history.lock().await;
+        let first = mtu.partition_point(|&(ts, _)| ts < from * 1000).saturating_sub(1);
+        let mtu: Vec<String> = mtu
+            .range(first..)
+            .take_while(|&&(ts, _)| ts < to * 1000)
+            .map(|&(ts, mtu)| json::array(&[(ts / 1000).to_string(), mtu.to_string()]))
+            .collect();
+
+        sources.push(json::object(&[
+            ("name", json::string(&source.name)),
+            ("buckets", json::array(&buckets)),
+            ("mtu", json::array(&mtu)),
+        ]));
+    }
+
+    Response::json(json::object(&[
+        ("from", from.to_string()),
+        ("to", to.to_string()),
+        ("step", step.to_string()),
+        ("sources", json::array(&sources)),
+    ]))
+}
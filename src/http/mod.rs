@@ -0,0 +1,335 @@
+//! Optional embedded HTTP server (`HTTP_LISTEN`): the dashboard, the JSON
+//! query API, a Prometheus-compatible query API, the live event stream and
+//! the runtime control API.
+
+mod api;
+mod control;
+mod dashboard;
+mod prom;
+mod promql;
+mod websocket;
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::events::{self, Filter};
+use crate::runtime::Runtime;
+use crate::source::Source;
+
+/// Requests larger than this (head plus body) are rejected.
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Everything handlers read from.
+pub struct HttpState {
+    pub runtime: Arc<Runtime>,
+}
+
+impl HttpState {
+    fn sources(&self) -> Vec<Source> {
+        self.runtime.sources.snapshot()
+    }
+
+    fn source(&self, name: &str) -> Option<Source> {
+        self.runtime.sources.get(name)
+    }
+}
+
+/// 9999-12-31T23:59:59.999Z in Unix milliseconds.
+const MAX_MS: f64 = 253_402_300_799_999.0;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// A non-negative number parameter (Unix milliseconds for times), at most
+    /// the end of year 9999 so callers can scale it to microseconds.
+    pub fn ms_param(&self, key: &str) -> Result<Option<u128>, Response> {
+        match self.param(key) {
+            None | Some("") => Ok(None),
+            Some(v) => v
+                .parse::<f64>()
+                .ok()
+                .filter(|v| (0.0..=MAX_MS).contains(v))
+                .map(|v| Some(v as u128))
+                .ok_or_else(|| Response::error(400, &format!("invalid {}", key))),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(body: String) -> Self {
+        Response { status: 200, content_type: "application/json", body: body.into_bytes() }
+    }
+
+    pub fn html(body: &str) -> Self {
+        Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message).into_bytes(),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Decode `%XX` escapes and `+` in a query component.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b'+', _) => {
+                out.push(b' ');
+                i += 1;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_query(s: &str) -> Vec<(String, String)> {
+    s.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect()
+}
+
+/// Read one request; None on malformed input or a closed connection.
+async fn read_request(stream: &mut TcpStream) -> Result<Option<Request>, Response> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(Response::error(413, "request too large"));
+        }
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Ok(None),
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err(Response::error(400, "malformed request line"));
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: percent_decode(path),
+        query: parse_query(query),
+        headers,
+        body: buf[head_end + 4..].to_vec(),
+    };
+
+    let length: usize = request.header("Content-Length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    if length > MAX_REQUEST_BYTES {
+        return Err(Response::error(413, "request too large"));
+    }
+    while request.body.len() < length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return Ok(None),
+            Ok(n) => request.body.extend_from_slice(&chunk[..n]),
+        }
+    }
+    request.body.truncate(length);
+    Ok(Some(request))
+}
+
+async fn route(request: &Request, state: &HttpState) -> Response {
+    if request.path.starts_with("/control/") {
+        return match control::authorize(request, state) {
+            Ok(()) => route_control(request, state).await,
+            Err(response) => response,
+        };
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => dashboard::page(),
+        ("GET", "/dashboard/data") => dashboard::data(request, state).await,
+        ("GET", "/api/targets") => api::targets(state).await,
+        ("GET", "/api/history") => api::history(request, state).await,
+        ("GET", "/api/packets") => api::packets(request, state).await,
+        ("GET", "/api/mtu") => api::mtu(request, state).await,
+        ("GET" | "POST", "/api/v1/query") => prom::query(request, state).await,
+        ("GET" | "POST", "/api/v1/query_range") => prom::query_range(request, state).await,
+        ("GET" | "POST", "/api/v1/series") => prom::series(request, state).await,
+        ("GET" | "POST", "/api/v1/labels") => prom::labels(request, state).await,
+        ("GET", "/api/v1/metadata") => prom::metadata(state),
+        ("GET", "/api/v1/status/buildinfo") => prom::buildinfo(),
+        ("GET", path) if path.starts_with("/api/v1/label/") && path.ends_with("/values") => {
+            let name = &path["/api/v1/label/".len()..path.len() - "/values".len()];
+            prom::label_values(name, request, state).await
+        }
+        (_, "/" | "/dashboard/data" | "/api/targets" | "/api/history" | "/api/packets" | "/api/mtu" | "/api/events") => {
+            Response::error(405, "method not allowed")
+        }
+        _ => Response::error(404, "not found"),
+    }
+}
+
+async fn route_control(request: &Request, state: &HttpState) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/control/config") => control::config(state),
+        ("POST", "/control/pause") => control::pause(request, state, true),
+        ("POST", "/control/resume") => control::pause(request, state, false),
+        ("POST", "/control/mtu-probe") => control::mtu_probe(request, state),
+        ("POST", "/control/interval") => control::interval(request, state),
+        ("POST", "/control/packet-size") => control::packet_size(request, state),
+        ("POST" | "DELETE", "/control/targets") => control::targets(request, state).await,
+        ("POST", "/control/save") => control::save(state).await,
+        ("POST", "/control/push") => control::push(state),
+        (
+            _,
+            "/control/config" | "/control/pause" | "/control/resume" | "/control/mtu-probe" | "/control/interval"
+            | "/control/packet-size" | "/control/targets" | "/control/save" | "/control/push",
+        ) => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found"),
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: &Response) {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    if stream.write_all(head.as_bytes()).await.is_ok() {
+        let _ = stream.write_all(&response.body).await;
+    }
+}
+
+/// `GET /api/events?source=`: NDJSON until the client disconnects, or
+/// WebSocket messages when the client asks for an upgrade.
+async fn stream_events(mut stream: TcpStream, request: &Request, state: &HttpState) {
+    let sources: Vec<&str> =
+        request.query.iter().filter(|(k, _)| k == "source").map(|(_, v)| v.as_str()).collect();
+    let filter = Filter::parse(&sources.join(","));
+    let upgrade = request.header("Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    if upgrade {
+        match request.header("Sec-WebSocket-Key") {
+            Some(key) => websocket::serve(stream, key, &state.runtime.events, filter).await,
+            None => write_response(&mut stream, &Response::error(400, "missing Sec-WebSocket-Key")).await,
+        }
+        return;
+    }
+
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n";
+    if stream.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+    let (mut read, write) = stream.into_split();
+    // Nothing is expected from the client; EOF means it went away.
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut buf = [0u8; 512];
+        while matches!(read.read(&mut buf).await, Ok(n) if n > 0) {}
+        drop(tx);
+    });
+    events::forward(write, &state.runtime.events, filter, rx, events::line).await;
+}
+
+/// One request per connection; responses close it.
+async fn handle(mut stream: TcpStream, state: Arc<HttpState>) {
+    let read = tokio::time::timeout(Duration::from_secs(10), read_request(&mut stream));
+    let response = match read.await {
+        Ok(Ok(Some(request))) if request.method == "GET" && request.path == "/api/events" => {
+            return stream_events(stream, &request, &state).await;
+        }
+        Ok(Ok(Some(request))) => route(&request, &state).await,
+        Ok(Ok(None)) | Err(_) => return,
+        Ok(Err(response)) => response,
+    };
+    write_response(&mut stream, &response).await;
+    let _ = stream.shutdown().await;
+}
+
+pub async fn start_server(addr: String, state: HttpState) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("HTTP server: cannot listen on {}: {}", addr, e);
+            return;
+        }
+    };
+    println!("HTTP server listening on http://{}", addr);
+    let state = Arc::new(state);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle(stream, Arc::clone(&state)));
+            }
+            Err(e) => eprintln!("HTTP server: accept failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,801 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::promql::{self, BinOp, Expr, Func, Matcher};
+use super::{parse_query, HttpState, Request, Response};
+use crate::calendar::days_from_civil;
+use crate::history::Totals;
+use crate::json;
+use crate::model::{Packet, PacketState};
+use crate::source::Source;
+
+// ── Prometheus HTTP API ───────────────────────────────────────────────────────
+//
+// Enough of /api/v1 for Grafana's Prometheus data source, evaluated straight
+// from the in-memory histories. Every source exposes event counters, which
+// rate() and increase() count exactly over their window instead of
+// extrapolating, and per-packet samples for the *_over_time functions.
+
+/// Instant selectors on sampled series look back this far.
+const LOOKBACK_MICROS: u128 = 5 * 60 * 1_000_000;
+/// Prometheus's own cap on points per series.
+const MAX_STEPS: u128 = 11_000;
+/// Cap on range × steps: the range vector windows of one query together
+/// span at most this, bounding the samples it reads.
+const MAX_SPAN_MICROS: u128 = 31 * 24 * 3600 * 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Sent,
+    Received,
+    Lost,
+    Late,
+    Failed,
+    Reordered,
+    Duplicated,
+    /// Round-trip time of each reply, in microseconds.
+    Rtt,
+    /// 1 for each lost packet and 0 for each reply.
+    LostSample,
+    Mtu,
+}
+
+impl Kind {
+    const ALL: [Kind; 10] = [
+        Kind::Sent,
+        Kind::Received,
+        Kind::Lost,
+        Kind::Late,
+        Kind::Failed,
+        Kind::Reordered,
+        Kind::Duplicated,
+        Kind::Rtt,
+        Kind::LostSample,
+        Kind::Mtu,
+    ];
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Kind::Sent => "packets_sent_total",
+            Kind::Received => "packets_received_total",
+            Kind::Lost => "packets_lost_total",
+            Kind::Late => "packets_late_total",
+            Kind::Failed => "packets_failed_total",
+            Kind::Reordered => "packets_reordered_total",
+            Kind::Duplicated => "packets_duplicated_total",
+            Kind::Rtt => "packet_rtt_microseconds",
+            Kind::LostSample => "packet_lost",
+            Kind::Mtu => "mtu_bytes",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Kind::Sent => "Packets sent.",
+            Kind::Received => "Replies received, excluding duplicates.",
+            Kind::Lost => "Packets without a reply within the loss timeout, late ones included.",
+            Kind::Late => "Lost packets whose reply arrived after the loss timeout.",
+            Kind::Failed => "Packets whose send failed locally, e.g. larger than the path MTU.",
+            Kind::Reordered => "Replies that arrived after a later packet's.",
+            Kind::Duplicated => "Duplicate replies.",
+            Kind::Rtt => "Round-trip time of each reply, at its send time.",
+            Kind::LostSample => "1 for each lost packet and 0 for each reply, at its send time.",
+            Kind::Mtu => "Path MTU probe results.",
+        }
+    }
+
+    fn is_counter(self) -> bool {
+        !matches!(self, Kind::Rtt | Kind::LostSample | Kind::Mtu)
+    }
+
+    /// Counter value over the packets counted in `totals`.
+    fn total(self, totals: &Totals) -> f64 {
+        let count = match self {
+            Kind::Sent => totals.sent,
+            Kind::Received => totals.received,
+            Kind::Lost => totals.lost,
+            Kind::Late => totals.late,
+            Kind::Failed => totals.failed,
+            Kind::Reordered => totals.reordered,
+            Kind::Duplicated => totals.duplicated,
+            Kind::Rtt | Kind::LostSample | Kind::Mtu => 0,
+        };
+        count as f64
+    }
+
+    /// The sample a packet contributes, if any. Counters count 1 per event.
+    fn sample(self, p: &Packet) -> Option<f64> {
+        let settled = !p.is_pending() && !p.is_failed();
+        let received = !p.duplicate && p.is_received();
+        let hit = match self {
+            Kind::Sent => !p.is_failed(),
+            Kind::Received => received,
+            Kind::Lost => !p.duplicate && p.is_lost(),
+            Kind::Late => !p.duplicate && p.state == PacketState::Late,
+            Kind::Failed => p.is_failed(),
+            Kind::Reordered => received && p.reordered,
+            Kind::Duplicated => p.duplicate,
+            Kind::Rtt => return received.then_some(p.latency as f64),
+            Kind::LostSample => {
+                return (!p.duplicate && settled).then_some(p.is_lost() as u8 as f64)
+            }
+            Kind::Mtu => false,
+        };
+        hit.then_some(1.0)
+    }
+}
+
+struct SeriesDef {
+    /// Sorted by name, `__name__` first.
+    labels: Vec<(String, String)>,
+    source: usize,
+    kind: Kind,
+}
+
+/// The part of one source's history a query reads.
+struct Window {
+    /// Packets sent within the window, oldest first.
+    packets: Vec<Packet>,
+    /// Totals of the packets sent before it, where counters start from.
+    before: Totals,
+    mtus: Vec<(u128, u32)>,
+}
+
+/// Every source's history within [from, to), copied under the locks so that
+/// all steps see the same data and evaluation does not hold up the probers.
+struct Snapshot {
+    windows: Vec<Window>,
+    series: Vec<SeriesDef>,
+}
+
+fn now_micros() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
+}
+
+/// Series of every source, `loopback_*` for the loopback, `ping_*` with a
+/// `target` label for ping targets and `stamp_*` with `reflector` and
+/// `direction` labels for STAMP sessions, like the pushed metrics.
+fn series_defs(sources: &[Source]) -> Vec<SeriesDef> {
+    let mut defs = Vec::new();
+    for (i, source) in sources.iter().enumerate() {
+        let (prefix, extra) = source.series();
+        for kind in Kind::ALL {
+            let mut labels = vec![(
+                "__name__".to_string(),
+                format!("{}_{}", prefix, kind.suffix()),
+            )];
+            labels.extend(extra.clone());
+            defs.push(SeriesDef {
+                labels,
+                source: i,
+                kind,
+            });
+        }
+    }
+    defs
+}
+
+async fn snapshot(sources: &[Source], from: u128, to: u128) -> Snapshot {
+    let mut windows = Vec::new();
+    for source in sources {
+        let (packets, before) = {
+            let mut history = source.history.lock().await;
+            let before = history.totals_before(from);
+            (history.range(from, to).cloned().collect(), before)
+        };
+        let mtus = source
+            .mtu_history
+            .lock()
+            .await
+            .iter()
+            .filter(|&&(ts, _)| ts >= from && ts < to)
+            .copied()
+            .collect();
+        windows.push(Window {
+            packets,
+            before,
+            mtus,
+        });
+    }
+    Snapshot {
+        windows,
+        series: series_defs(sources),
+    }
+}
+
+/// Longest range vector selector in `expr`.
+fn max_range(expr: &Expr) -> u128 {
+    match expr {
+        Expr::Number(_) => 0,
+        Expr::Selector { range, .. } => range.unwrap_or(0),
+        Expr::Call { arg, .. } | Expr::Neg(arg) => max_range(arg),
+        Expr::Binary { lhs, rhs, .. } => max_range(lhs).max(max_range(rhs)),
+    }
+}
+
+/// Copy what `expr` reads at `times` and evaluate it off the async runtime.
+async fn evaluate(sources: &[Source], expr: Expr, times: &[u128]) -> Result<Value, Response> {
+    let range = max_range(&expr);
+    if range.saturating_mul(times.len() as u128) > MAX_SPAN_MICROS {
+        return Err(error(
+            400,
+            &format!(
+                "range times steps is more than {} days; shorten the range or increase step",
+                MAX_SPAN_MICROS / 86_400_000_000
+            ),
+        ));
+    }
+    let first = times.first().copied().unwrap_or_default();
+    let last = times.last().copied().unwrap_or_default();
+    let from = (first + 1).saturating_sub(range.max(LOOKBACK_MICROS));
+    let snapshot = snapshot(sources, from, last + 1).await;
+    let times = times.to_vec();
+    tokio::task::spawn_blocking(move || eval(&expr, &snapshot, &times).map_err(|e| error(422, &e)))
+        .await
+        .unwrap_or_else(|_| Err(error(500, "evaluation failed")))
+}
+
+impl Snapshot {
+    /// Sample values in [from, to), oldest first.
+    fn samples(&self, def: &SeriesDef, from: u128, to: u128) -> Vec<f64> {
+        let window = &self.windows[def.source];
+        if def.kind == Kind::Mtu {
+            return window
+                .mtus
+                .iter()
+                .filter(|&&(ts, _)| ts >= from && ts < to)
+                .map(|&(_, mtu)| mtu as f64)
+                .collect();
+        }
+        let start = window.packets.partition_point(|p| p.timestamp < from);
+        let end = window.packets.partition_point(|p| p.timestamp < to);
+        window.packets[start..end.max(start)]
+            .iter()
+            .filter_map(|p| def.kind.sample(p))
+            .collect()
+    }
+
+    /// Counter values (events up to and including each time) at ascending
+    /// times, in one pass over the window.
+    fn cumulative(&self, def: &SeriesDef, times: &[u128]) -> Vec<f64> {
+        let window = &self.windows[def.source];
+        let mut packets = window.packets.iter().peekable();
+        let mut count = def.kind.total(&window.before);
+        times
+            .iter()
+            .map(|&t| {
+                while let Some(p) = packets.next_if(|p| p.timestamp <= t) {
+                    count += def.kind.sample(p).unwrap_or(0.0);
+                }
+                count
+            })
+            .collect()
+    }
+}
+
+// ── Evaluation ────────────────────────────────────────────────────────────────
+
+struct Series {
+    labels: Vec<(String, String)>,
+    /// One per evaluation time; None where the series has no value.
+    points: Vec<Option<f64>>,
+}
+
+enum Value {
+    Scalar(Vec<f64>),
+    Vector(Vec<Series>),
+}
+
+fn without_name(labels: &[(String, String)]) -> Vec<(String, String)> {
+    labels
+        .iter()
+        .filter(|(k, _)| k != "__name__")
+        .cloned()
+        .collect()
+}
+
+fn quantile(q: f64, values: &mut [f64]) -> f64 {
+    if !(0.0..=1.0).contains(&q) {
+        return if q < 0.0 {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        };
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = q * (values.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    values[lo] + (values[hi] - values[lo]) * (rank - lo as f64)
+}
+
+fn over_time(func: Func, param: Option<f64>, mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let n = values.len() as f64;
+    Some(match func {
+        Func::AvgOverTime => values.iter().sum::<f64>() / n,
+        Func::MinOverTime => values.iter().copied().fold(f64::INFINITY, f64::min),
+        Func::MaxOverTime => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        Func::SumOverTime => values.iter().sum(),
+        Func::CountOverTime => n,
+        Func::QuantileOverTime => quantile(param.unwrap_or(0.5), &mut values),
+        Func::Rate | Func::Increase => unreachable!("counter functions are handled by the caller"),
+    })
+}
+
+fn select<'s>(
+    snapshot: &'s Snapshot,
+    matchers: &'s [Matcher],
+) -> impl Iterator<Item = &'s SeriesDef> {
+    snapshot
+        .series
+        .iter()
+        .filter(move |def| promql::matches(matchers, &def.labels))
+}
+
+fn eval(expr: &Expr, snapshot: &Snapshot, times: &[u128]) -> Result<Value, String> {
+    match expr {
+        Expr::Number(n) => Ok(Value::Scalar(vec![*n; times.len()])),
+        Expr::Selector { matchers, .. } => Ok(Value::Vector(
+            select(snapshot, matchers)
+                .map(|def| {
+                    let points = if def.kind.is_counter() {
+                        snapshot
+                            .cumulative(def, times)
+                            .into_iter()
+                            .map(Some)
+                            .collect()
+                    } else {
+                        times
+                            .iter()
+                            .map(|&t| {
+                                snapshot
+                                    .samples(def, (t + 1).saturating_sub(LOOKBACK_MICROS), t + 1)
+                                    .pop()
+                            })
+                            .collect()
+                    };
+                    Series {
+                        labels: def.labels.clone(),
+                        points,
+                    }
+                })
+                .collect(),
+        )),
+        Expr::Call { func, param, arg } => {
+            let Expr::Selector {
+                matchers,
+                range: Some(range),
+            } = arg.as_ref()
+            else {
+                return Err("expected a range vector selector".to_string());
+            };
+            let seconds = *range as f64 / 1e6;
+            let series = select(snapshot, matchers)
+                .filter(|def| def.kind.is_counter() == func.on_counters())
+                .map(|def| {
+                    let points = times
+                        .iter()
+                        .map(|&t| {
+                            let values =
+                                snapshot.samples(def, (t + 1).saturating_sub(*range), t + 1);
+                            match func {
+                                Func::Rate => Some(values.len() as f64 / seconds),
+                                Func::Increase => Some(values.len() as f64),
+                                _ => over_time(*func, *param, values),
+                            }
+                        })
+                        .collect();
+                    Series {
+                        labels: without_name(&def.labels),
+                        points,
+                    }
+                })
+                .collect();
+            Ok(Value::Vector(series))
+        }
+        Expr::Neg(inner) => binary(
+            BinOp::Sub,
+            Value::Scalar(vec![0.0; times.len()]),
+            eval(inner, snapshot, times)?,
+        ),
+        Expr::Binary { op, lhs, rhs } => binary(
+            *op,
+            eval(lhs, snapshot, times)?,
+            eval(rhs, snapshot, times)?,
+        ),
+    }
+}
+
+/// Scalars apply to every series; two vectors match one-to-one on their
+/// labels other than the metric name.
+fn binary(op: BinOp, lhs: Value, rhs: Value) -> Result<Value, String> {
+    let zip = |a: &[Option<f64>], b: &[Option<f64>]| -> Vec<Option<f64>> {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| Some(op.apply((*a)?, (*b)?)))
+            .collect()
+    };
+    Ok(match (lhs, rhs) {
+        (Value::Scalar(a), Value::Scalar(b)) => {
+            Value::Scalar(a.iter().zip(&b).map(|(a, b)| op.apply(*a, *b)).collect())
+        }
+        (Value::Vector(v), Value::Scalar(s)) => {
+            let s: Vec<Option<f64>> = s.into_iter().map(Some).collect();
+            Value::Vector(
+                v.into_iter()
+                    .map(|series| Series {
+                        labels: without_name(&series.labels),
+                        points: zip(&series.points, &s),
+                    })
+                    .collect(),
+            )
+        }
+        (Value::Scalar(s), Value::Vector(v)) => {
+            let s: Vec<Option<f64>> = s.into_iter().map(Some).collect();
+            Value::Vector(
+                v.into_iter()
+                    .map(|series| Series {
+                        labels: without_name(&series.labels),
+                        points: zip(&s, &series.points),
+                    })
+                    .collect(),
+            )
+        }
+        (Value::Vector(a), Value::Vector(b)) => {
+            let keys: Vec<_> = b.iter().map(|s| without_name(&s.labels)).collect();
+            let mut out = Vec::new();
+            for series in a {
+                let key = without_name(&series.labels);
+                let mut matching = keys.iter().enumerate().filter(|(_, k)| **k == key);
+                let Some((j, _)) = matching.next() else {
+                    continue;
+                };
+                if matching.next().is_some() {
+                    return Err("many-to-many matching not allowed".to_string());
+                }
+                out.push(Series {
+                    points: zip(&series.points, &b[j].points),
+                    labels: key,
+                });
+            }
+            Value::Vector(out)
+        }
+    })
+}
+
+// ── Parameters ────────────────────────────────────────────────────────────────
+
+/// Query parameters plus form-encoded body parameters, as Grafana POSTs.
+fn params(request: &Request) -> Vec<(String, String)> {
+    let mut params = request.query.clone();
+    let form = request
+        .header("Content-Type")
+        .is_some_and(|t| t.starts_with("application/x-www-form-urlencoded"));
+    if form {
+        params.extend(parse_query(&String::from_utf8_lossy(&request.body)));
+    }
+    params
+}
+
+fn param<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+/// Unix seconds with an optional fraction, or RFC 3339 → microseconds.
+fn parse_time(s: &str) -> Option<u128> {
+    if let Ok(secs) = s.parse::<f64>() {
+        return (secs >= 0.0).then_some((secs * 1e6).round() as u128);
+    }
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+    let mut ymd = date.splitn(3, '-').map(str::parse::<i64>);
+    let (y, m, d) = (ymd.next()?.ok()?, ymd.next()?.ok()?, ymd.next()?.ok()?);
+    let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else {
+        let split = time.rfind(['+', '-'])?;
+        let (hh, mm) = time[split + 1..].split_once(':')?;
+        let minutes = hh.parse::<i64>().ok()? * 60 + mm.parse::<i64>().ok()?;
+        (
+            &time[..split],
+            if &time[split..split + 1] == "-" {
+                -minutes
+            } else {
+                minutes
+            },
+        )
+    };
+    let mut hms = clock.splitn(3, ':');
+    let (h, min) = (
+        hms.next()?.parse::<i64>().ok()?,
+        hms.next()?.parse::<i64>().ok()?,
+    );
+    let sec: f64 = hms.next()?.parse().ok()?;
+    let days = days_from_civil(y, m as u32, d as u32);
+    let secs = days * 86_400 + h * 3600 + min * 60 - offset * 60;
+    let micros = secs as f64 * 1e6 + sec * 1e6;
+    (micros >= 0.0).then_some(micros.round() as u128)
+}
+
+fn time_param(params: &[(String, String)], key: &str) -> Result<Option<u128>, Response> {
+    match param(params, key) {
+        None => Ok(None),
+        Some(v) => parse_time(v)
+            .map(Some)
+            .ok_or_else(|| error(400, &format!("invalid {} '{}'", key, v))),
+    }
+}
+
+// ── Responses ─────────────────────────────────────────────────────────────────
+
+fn success(data: String) -> Response {
+    Response::json(json::object(&[
+        ("status", json::string("success")),
+        ("data", data),
+    ]))
+}
+
+fn error(status: u16, message: &str) -> Response {
+    let kind = if status == 422 {
+        "execution"
+    } else {
+        "bad_data"
+    };
+    let mut response = Response::json(json::object(&[
+        ("status", json::string("error")),
+        ("errorType", json::string(kind)),
+        ("error", json::string(message)),
+    ]));
+    response.status = status;
+    response
+}
+
+fn format_value(v: f64) -> String {
+    let s = if v.is_nan() {
+        "NaN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        v.to_string()
+    };
+    json::string(&s)
+}
+
+fn sample(t: u128, v: f64) -> String {
+    format!("[{},{}]", t as f64 / 1e6, format_value(v))
+}
+
+fn labels_object(labels: &[(String, String)]) -> String {
+    let fields: Vec<(&str, String)> = labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), json::string(v)))
+        .collect();
+    json::object(&fields)
+}
+
+fn parse_expr(params: &[(String, String)]) -> Result<Expr, Response> {
+    let query = param(params, "query").ok_or_else(|| error(400, "missing query"))?;
+    promql::parse(query).map_err(|e| error(400, &format!("invalid query: {}", e)))
+}
+
+// ── Handlers ──────────────────────────────────────────────────────────────────
+
+/// `/api/v1/query?query=&time=`
+pub async fn query(request: &Request, state: &HttpState) -> Response {
+    let params = params(request);
+    let (expr, time) = match (parse_expr(&params), time_param(&params, "time")) {
+        (Ok(expr), Ok(time)) => (expr, time.unwrap_or_else(now_micros)),
+        (Err(r), _) | (_, Err(r)) => return r,
+    };
+    let result = match evaluate(&state.sources(), expr, &[time]).await {
+        Ok(Value::Scalar(v)) => json::object(&[
+            ("resultType", json::string("scalar")),
+            ("result", sample(time, v[0])),
+        ]),
+        Ok(Value::Vector(series)) => {
+            let items: Vec<String> = series
+                .iter()
+                .filter_map(|s| {
+                    let v = s.points[0]?;
+                    Some(json::object(&[
+                        ("metric", labels_object(&s.labels)),
+                        ("value", sample(time, v)),
+                    ]))
+                })
+                .collect();
+            json::object(&[
+                ("resultType", json::string("vector")),
+                ("result", json::array(&items)),
+            ])
+        }
+        Err(r) => return r,
+    };
+    success(result)
+}
+
+/// `/api/v1/query_range?query=&start=&end=&step=`
+pub async fn query_range(request: &Request, state: &HttpState) -> Response {
+    let params = params(request);
+    let (expr, start, end) = match (
+        parse_expr(&params),
+        time_param(&params, "start"),
+        time_param(&params, "end"),
+    ) {
+        (Ok(expr), Ok(Some(start)), Ok(Some(end))) => (expr, start, end),
+        (Err(r), _, _) | (_, Err(r), _) | (_, _, Err(r)) => return r,
+        _ => return error(400, "start and end are required"),
+    };
+    let step = match param(&params, "step") {
+        Some(s) => match s
+            .parse::<f64>()
+            .ok()
+            .map(|secs| (secs * 1e6) as u128)
+            .or_else(|| promql::parse_duration(s))
+        {
+            Some(step) if step > 0 => step,
+            _ => return error(400, &format!("invalid step '{}'", s)),
+        },
+        None => return error(400, "step is required"),
+    };
+    if end < start {
+        return error(400, "end is before start");
+    }
+    if (end - start) / step + 1 > MAX_STEPS {
+        return error(
+            400,
+            &format!("more than {} points per series; increase step", MAX_STEPS),
+        );
+    }
+    let times: Vec<u128> = (0..=(end - start) / step)
+        .map(|i| start + i * step)
+        .collect();
+
+    let series = match evaluate(&state.sources(), expr, &times).await {
+        Ok(Value::Scalar(v)) => {
+            vec![Series {
+                labels: Vec::new(),
+                points: v.into_iter().map(Some).collect(),
+            }]
+        }
+        Ok(Value::Vector(series)) => series,
+        Err(r) => return r,
+    };
+    let items: Vec<String> = series
+        .iter()
+        .filter_map(|s| {
+            let values: Vec<String> = times
+                .iter()
+                .zip(&s.points)
+                .filter_map(|(&t, v)| Some(sample(t, (*v)?)))
+                .collect();
+            (!values.is_empty()).then(|| {
+                json::object(&[
+                    ("metric", labels_object(&s.labels)),
+                    ("values", json::array(&values)),
+                ])
+            })
+        })
+        .collect();
+    success(json::object(&[
+        ("resultType", json::string("matrix")),
+        ("result", json::array(&items)),
+    ]))
+}
+
+/// Series selected by the `match[]` parameters, or all of them.
+fn matched_series(
+    request: &Request,
+    state: &HttpState,
+    required: bool,
+) -> Result<Vec<SeriesDef>, Response> {
+    let params = params(request);
+    let selectors = params
+        .iter()
+        .filter(|(k, _)| k == "match[]")
+        .map(|(_, v)| {
+            promql::parse_selector(v).map_err(|e| error(400, &format!("invalid match[]: {}", e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if required && selectors.is_empty() {
+        return Err(error(400, "no match[] parameter provided"));
+    }
+    Ok(series_defs(&state.sources())
+        .into_iter()
+        .filter(|def| {
+            selectors.is_empty() || selectors.iter().any(|m| promql::matches(m, &def.labels))
+        })
+        .collect())
+}
+
+/// `/api/v1/series?match[]=`
+pub async fn series(request: &Request, state: &HttpState) -> Response {
+    match matched_series(request, state, true) {
+        Ok(defs) => {
+            let items: Vec<String> = defs.iter().map(|def| labels_object(&def.labels)).collect();
+            success(json::array(&items))
+        }
+        Err(r) => r,
+    }
+}
+
+/// `/api/v1/labels`
+pub async fn labels(request: &Request, state: &HttpState) -> Response {
+    match matched_series(request, state, false) {
+        Ok(defs) => {
+            let mut names: Vec<&str> = defs
+                .iter()
+                .flat_map(|d| d.labels.iter().map(|(k, _)| k.as_str()))
+                .collect();
+            names.sort_unstable();
+            names.dedup();
+            success(json::array(
+                &names.iter().map(|n| json::string(n)).collect::<Vec<_>>(),
+            ))
+        }
+        Err(r) => r,
+    }
+}
+
+/// `/api/v1/label/<name>/values`
+pub async fn label_values(name: &str, request: &Request, state: &HttpState) -> Response {
+    match matched_series(request, state, false) {
+        Ok(defs) => {
+            let mut values: Vec<&str> = defs
+                .iter()
+                .flat_map(|d| {
+                    d.labels
+                        .iter()
+                        .filter(|(k, _)| k == name)
+                        .map(|(_, v)| v.as_str())
+                })
+                .collect();
+            values.sort_unstable();
+            values.dedup();
+            success(json::array(
+                &values.iter().map(|v| json::string(v)).collect::<Vec<_>>(),
+            ))
+        }
+        Err(r) => r,
+    }
+}
+
+/// `/api/v1/metadata`
+pub fn metadata(state: &HttpState) -> Response {
+    let defs = series_defs(&state.sources());
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    for def in &defs {
+        let name = def.labels[0].1.as_str();
+        if fields.iter().any(|(n, _)| *n == name) {
+            continue;
+        }
+        let kind = if def.kind.is_counter() {
+            "counter"
+        } else {
+            "gauge"
+        };
+        let entry = json::object(&[
+            ("type", json::string(kind)),
+            ("help", json::string(def.kind.help())),
+            ("unit", json::string("")),
+        ]);
+        fields.push((name, json::array(&[entry])));
+    }
+    success(json::object(&fields))
+}
+
+/// `/api/v1/status/buildinfo`, which Grafana reads to detect the flavour.
+pub fn buildinfo() -> Response {
+    success(json::object(&[
+        ("version", json::string(env!("CARGO_PKG_VERSION"))),
+        ("revision", json::string("")),
+        ("branch", json::string("")),
+        ("goVersion", json::string("")),
+    ]))
+}
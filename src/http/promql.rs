@@ -0,0 +1,462 @@
+use regex::Regex;
+
+// ── PromQL subset ─────────────────────────────────────────────────────────────
+//
+//   expr     := term (('+' | '-') term)*
+//   term     := unary (('*' | '/') unary)*
+//   unary    := '-' unary | primary
+//   primary  := number | '(' expr ')' | func '(' [number ','] expr ')' | selector
+//   selector := [name] ['{' matcher (',' matcher)* '}'] ['[' duration ']']
+//
+// Functions: rate, increase, avg_over_time, min_over_time, max_over_time,
+// sum_over_time, count_over_time, quantile_over_time.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Func {
+    Rate,
+    Increase,
+    AvgOverTime,
+    MinOverTime,
+    MaxOverTime,
+    SumOverTime,
+    CountOverTime,
+    QuantileOverTime,
+}
+
+impl Func {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "rate" => Func::Rate,
+            "increase" => Func::Increase,
+            "avg_over_time" => Func::AvgOverTime,
+            "min_over_time" => Func::MinOverTime,
+            "max_over_time" => Func::MaxOverTime,
+            "sum_over_time" => Func::SumOverTime,
+            "count_over_time" => Func::CountOverTime,
+            "quantile_over_time" => Func::QuantileOverTime,
+            _ => return None,
+        })
+    }
+
+    /// rate and increase count events; the rest aggregate sampled values.
+    pub fn on_counters(self) -> bool {
+        matches!(self, Func::Rate | Func::Increase)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinOp {
+    pub fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            BinOp::Add => a + b,
+            BinOp::Sub => a - b,
+            BinOp::Mul => a * b,
+            BinOp::Div => a / b,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Equal(String, String),
+    NotEqual(String, String),
+    Regex(String, Regex),
+    NotRegex(String, Regex),
+}
+
+impl Matcher {
+    fn name(&self) -> &str {
+        match self {
+            Matcher::Equal(n, _)
+            | Matcher::NotEqual(n, _)
+            | Matcher::Regex(n, _)
+            | Matcher::NotRegex(n, _) => n,
+        }
+    }
+
+    /// Match against the value of this matcher's label ("" when absent).
+    fn matches_value(&self, value: &str) -> bool {
+        match self {
+            Matcher::Equal(_, v) => value == v,
+            Matcher::NotEqual(_, v) => value != v,
+            Matcher::Regex(_, re) => re.is_match(value),
+            Matcher::NotRegex(_, re) => !re.is_match(value),
+        }
+    }
+}
+
+/// Does a (sorted or not) label set satisfy every matcher?
+pub fn matches(matchers: &[Matcher], labels: &[(String, String)]) -> bool {
+    matchers.iter().all(|m| {
+        let value = labels
+            .iter()
+            .find(|(k, _)| k == m.name())
+            .map_or("", |(_, v)| v.as_str());
+        m.matches_value(value)
+    })
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Number(f64),
+    Selector {
+        matchers: Vec<Matcher>,
+        /// Range in microseconds, for range vector selectors.
+        range: Option<u128>,
+    },
+    Call {
+        func: Func,
+        /// The φ of quantile_over_time.
+        param: Option<f64>,
+        arg: Box<Expr>,
+    },
+    Binary {
+        op: BinOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Neg(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Duration(u128),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: [&str; 14] = [
+    "!=", "=~", "!~", "{", "}", "(", ")", "[", "]", ",", "=", "+", "-", "*",
+];
+
+/// `5m`, `1h30m`, `500ms`, `2d`, `1w` → microseconds.
+pub fn parse_duration(s: &str) -> Option<u128> {
+    let mut total = 0u128;
+    let mut rest = s;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return None;
+        }
+        let n: u128 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let (unit, len) = if rest.starts_with("ms") {
+            (1_000, 2)
+        } else {
+            let scale = match rest.chars().next()? {
+                's' => 1_000_000,
+                'm' => 60_000_000,
+                'h' => 3_600_000_000,
+                'd' => 86_400_000_000,
+                'w' => 604_800_000_000,
+                'y' => 31_536_000_000_000,
+                _ => return None,
+            };
+            (scale, 1)
+        };
+        total += n * unit;
+        rest = &rest[len..];
+    }
+    Some(total)
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = s.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' || c == '\'' || c == '`' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".to_string()),
+                    Some(&q) if q == c => break,
+                    Some('\\') if c != '`' => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(&other) => value.push(other),
+                            None => return Err("unterminated string".to_string()),
+                        }
+                    }
+                    Some(&other) => value.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(value));
+        } else if c == '[' {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| c == ']')
+                .ok_or("unclosed '['")?
+                + i;
+            let inner: String = chars[i + 1..end].iter().collect();
+            let d = parse_duration(inner.trim()).ok_or(format!("invalid duration '{}'", inner))?;
+            tokens.push(Token::Duration(d));
+            i = end + 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                // Exponent sign, as in 1e-3.
+                if (chars[i] == 'e' || chars[i] == 'E')
+                    && matches!(chars.get(i + 1), Some('+' | '-'))
+                {
+                    i += 1;
+                }
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse()
+                .map_err(|_| format!("invalid number '{}'", text))?;
+            tokens.push(Token::Number(n));
+        } else if c.is_ascii_alphabetic() || c == '_' || c == ':' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == ':')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c == '/' {
+            tokens.push(Token::Punct("/"));
+            i += 1;
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let p = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .ok_or(format!("unexpected character '{}'", c))?;
+            tokens.push(Token::Punct(p));
+            i += p.len();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn eat(&mut self, p: &str) -> bool {
+        if self.peek() == Some(&Token::Punct(punct(p))) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), String> {
+        if self.eat(p) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", p))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat("+") {
+                BinOp::Add
+            } else if self.eat("-") {
+                BinOp::Sub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.term()?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                BinOp::Mul
+            } else if self.eat("/") {
+                BinOp::Div
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?;
+            lhs = Expr::Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.peek().cloned() {
+            Some(Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(n))
+            }
+            Some(Token::Punct("(")) => {
+                self.pos += 1;
+                let e = self.expr()?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Some(Token::Ident(name))
+                if self.tokens.get(self.pos + 1) == Some(&Token::Punct("(")) =>
+            {
+                let func = Func::parse(&name).ok_or(format!("unsupported function '{}'", name))?;
+                self.pos += 2;
+                let param = if func == Func::QuantileOverTime {
+                    let Some(Token::Number(q)) = self.next() else {
+                        return Err("quantile_over_time expects a number first".to_string());
+                    };
+                    self.expect(",")?;
+                    Some(q)
+                } else {
+                    None
+                };
+                let arg = self.expr()?;
+                self.expect(")")?;
+                if !matches!(arg, Expr::Selector { range: Some(_), .. }) {
+                    return Err(format!("{} expects a range vector selector", name));
+                }
+                Ok(Expr::Call {
+                    func,
+                    param,
+                    arg: Box::new(arg),
+                })
+            }
+            Some(Token::Ident(_)) | Some(Token::Punct("{")) => self.selector(),
+            Some(t) => Err(format!("unexpected {:?}", t)),
+            None => Err("unexpected end of query".to_string()),
+        }
+    }
+
+    fn selector(&mut self) -> Result<Expr, String> {
+        let mut matchers = Vec::new();
+        if let Some(Token::Ident(name)) = self.peek().cloned() {
+            self.pos += 1;
+            matchers.push(Matcher::Equal("__name__".to_string(), name));
+        }
+        if self.eat("{") {
+            while !self.eat("}") {
+                let Some(Token::Ident(label)) = self.next() else {
+                    return Err("expected a label name".to_string());
+                };
+                let op = match self.next() {
+                    Some(Token::Punct(op)) if matches!(op, "=" | "!=" | "=~" | "!~") => op,
+                    _ => return Err("expected '=', '!=', '=~' or '!~'".to_string()),
+                };
+                let Some(Token::Str(value)) = self.next() else {
+                    return Err("expected a quoted label value".to_string());
+                };
+                let regex = || {
+                    Regex::new(&format!("^(?:{})$", value))
+                        .map_err(|e| format!("invalid regex: {}", e))
+                };
+                matchers.push(match op {
+                    "=" => Matcher::Equal(label, value),
+                    "!=" => Matcher::NotEqual(label, value),
+                    "=~" => Matcher::Regex(label, regex()?),
+                    _ => Matcher::NotRegex(label, regex()?),
+                });
+                if !self.eat(",") {
+                    self.expect("}")?;
+                    break;
+                }
+            }
+        }
+        if matchers.is_empty() {
+            return Err("empty selector".to_string());
+        }
+        let range = match self.peek() {
+            Some(&Token::Duration(d)) => {
+                self.pos += 1;
+                Some(d)
+            }
+            _ => None,
+        };
+        Ok(Expr::Selector { matchers, range })
+    }
+}
+
+/// Intern a punctuation string so tokens can be compared by value.
+fn punct(p: &str) -> &'static str {
+    PUNCTUATION
+        .iter()
+        .chain(["/"].iter())
+        .find(|q| **q == p)
+        .copied()
+        .unwrap_or("")
+}
+
+pub fn parse(query: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+    };
+    let expr = parser.expr()?;
+    if let Some(t) = parser.peek() {
+        return Err(format!("unexpected {:?}", t));
+    }
+    if matches!(expr, Expr::Selector { range: Some(_), .. }) {
+        return Err("range vector selectors need a function such as rate()".to_string());
+    }
+    Ok(expr)
+}
+
+/// Parse a bare selector, as in `match[]` parameters.
+pub fn parse_selector(s: &str) -> Result<Vec<Matcher>, String> {
+    match parse(s)? {
+        Expr::Selector {
+            matchers,
+            range: None,
+        } => Ok(matchers),
+        _ => Err(format!("'{}' is not a series selector", s)),
+    }
+}
@@ -0,0 +1,117 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use crate::events::{self, Control, EventBus, Filter};
+
+// ── WebSocket event stream (RFC 6455) ─────────────────────────────────────────
+//
+// Server-to-client text messages only; client text messages update the
+// source filter. Just enough of the protocol for that: no extensions and no
+// fragmented client messages.
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Client messages are filters and control frames; anything larger is refused.
+const MAX_CLIENT_FRAME: u64 = 4096;
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// `Sec-WebSocket-Accept` for a client's `Sec-WebSocket-Key`.
+fn accept_key(key: &str) -> String {
+    let digest = Sha1::new().chain_update(key).chain_update(ACCEPT_GUID).finalize();
+    STANDARD.encode(digest)
+}
+
+/// An unmasked server frame.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x80 | opcode];
+    match payload.len() {
+        n if n < 126 => out.push(n as u8),
+        n if n <= u16::MAX as usize => {
+            out.push(126);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        n => {
+            out.push(127);
+            out.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Read one client frame as (opcode, unmasked payload).
+async fn read_frame(read: &mut OwnedReadHalf) -> Option<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    read.read_exact(&mut head).await.ok()?;
+    let len = match head[1] & 0x7F {
+        126 => read.read_u16().await.ok()? as u64,
+        127 => read.read_u64().await.ok()?,
+        n => n as u64,
+    };
+    // Clients must mask their frames.
+    if head[1] & 0x80 == 0 || len > MAX_CLIENT_FRAME {
+        return None;
+    }
+    let mut mask = [0u8; 4];
+    read.read_exact(&mut mask).await.ok()?;
+    let mut payload = vec![0u8; len as usize];
+    read.read_exact(&mut payload).await.ok()?;
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+    Some((head[0] & 0x0F, payload))
+}
+
+/// Complete the upgrade and stream events until either side closes.
+pub async fn serve(mut stream: TcpStream, key: &str, bus: &EventBus, filter: Filter) {
+    let accept = accept_key(key);
+    let head = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    );
+    if stream.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let (mut read, write) = stream.into_split();
+    let (tx, rx) = mpsc::channel(16);
+    tokio::spawn(async move {
+        while let Some((opcode, payload)) = read_frame(&mut read).await {
+            let control = match opcode {
+                OP_TEXT => match Filter::parse_line(&String::from_utf8_lossy(&payload)) {
+                    Some(filter) => Control::Filter(filter),
+                    None => continue,
+                },
+                OP_PING => Control::Reply(frame(OP_PONG, &payload)),
+                OP_CLOSE => {
+                    let _ = tx.send(Control::Reply(frame(OP_CLOSE, &payload))).await;
+                    Control::Close
+                }
+                _ => continue,
+            };
+            if tx.send(control).await.is_err() {
+                return;
+            }
+        }
+    });
+    events::forward(write, bus, filter, rx, |json| frame(OP_TEXT, json.as_bytes())).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_matches_rfc_6455() {
+        // RFC 6455 §1.3.
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+}
@@ -0,0 +1,45 @@
+//! Minimal JSON output helpers; everything we emit is flat enough that a
+//! serializer dependency isn't worth it.
+
+/// Quote and escape a string.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A number, or `null` for NaN/infinity which JSON cannot represent.
+pub fn number(v: f64) -> String {
+    if v.is_finite() {
+        format!("{}", v)
+    } else {
+        "null".to_string()
+    }
+}
+
+/// `null` for None.
+pub fn opt(v: Option<f64>) -> String {
+    v.map_or("null".to_string(), number)
+}
+
+/// Build an object from already-encoded values.
+pub fn object(fields: &[(&str, String)]) -> String {
+    let body: Vec<String> = fields.iter().map(|(k, v)| format!("{}:{}", string(k), v)).collect();
+    format!("{{{}}}", body.join(","))
+}
+
+pub fn array(items: &[String]) -> String {
+    format!("[{}]", items.join(","))
+}
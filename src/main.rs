@@ -1,22 +1,42 @@
+mod alert;
+mod analysis;
+mod calendar;
+mod clock;
+mod commands;
 mod config;
+mod events;
+mod history;
+mod http;
+mod json;
 mod metrics;
 mod model;
 mod network;
+mod outage;
 mod persistence;
+mod runtime;
+mod source;
+#[cfg(test)]
+mod test_http;
 
 use dotenvy::dotenv;
-use std::collections::VecDeque;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
 
-use metrics::PingSource;
-use model::Packet;
+use events::EventBus;
+use history::PacketHistory;
+use outage::OutageLog;
+use runtime::Runtime;
+use source::{Source, SourceControl, SourceKind, Sources};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if commands::run(&args) {
+        return;
+    }
     let config = config::load();
 
     // Random-ish session ID: low 32 bits of the startup timestamp in microseconds.
@@ -28,40 +48,97 @@ async fn main() {
         .as_micros() as u32;
     println!("Session ID: 0x{:08X}", session_id);
 
-    let sent_counter = Arc::new(AtomicU64::new(0));
+    // Resolved once: both ends of the loopback path are this process.
+    let key = config.probe_secret.as_ref().and_then(|s| s.resolve()).map(|s| network::auth::PayloadKey::new(&s));
+    if key.is_some() {
+        println!("Loopback payloads are signed; unsigned datagrams are rejected");
+    }
+    let session = Arc::new(network::sender::Session::new(session_id, key));
+    let events = EventBus::new();
 
     // ── Loopback history ───────────────────────────────────────────────────────
-    let history: Arc<Mutex<VecDeque<Packet>>> =
-        Arc::new(Mutex::new(persistence::load(&config.data_file)));
-
-    let loopback_mtu: Arc<Mutex<VecDeque<(u128, u32)>>> =
-        Arc::new(Mutex::new(persistence::load_mtu(&config.loopback_mtu_file())));
-
-    // ── ICMP ping histories ────────────────────────────────────────────────────
-    let ping_sources: Vec<PingSource> = config
-        .ping_targets
-        .iter()
-        .map(|target| PingSource {
-            target: target.clone(),
-            history: Arc::new(Mutex::new(persistence::load(
-                &config.ping_data_file_for(target),
-            ))),
-            mtu_history: Arc::new(Mutex::new(persistence::load_mtu(
-                &config.ping_mtu_file_for(target),
-            ))),
-        })
-        .collect();
+    let mut loopback_history = PacketHistory::new(
+        persistence::load(&config.data_file),
+        config.loss_timeouts.micros_for("loopback"),
+        config.outage_thresholds,
+        config.sla_thresholds,
+    );
+    loopback_history.restore_icmp_errors(persistence::load_icmp_errors(&config.data_file));
+    loopback_history.set_events(events.publisher("loopback"));
+    let loopback = Source {
+        name: "loopback".to_string(),
+        labels: Vec::new(),
+        history: Arc::new(Mutex::new(loopback_history)),
+        mtu_history: Arc::new(Mutex::new(persistence::load_mtu(&config.loopback_mtu_file()))),
+        control: Arc::new(SourceControl::new(
+            config.interval_millis,
+            config.max_packet_size as u32,
+            config.schedules.for_source("loopback"),
+            config.min_packet_size as u32,
+            config.size_mode,
+        )),
+        kind: SourceKind::Loopback,
+    };
+    let history = Arc::clone(&loopback.history);
+    let loopback_mtu = Arc::clone(&loopback.mtu_history);
+
+    // Every monitored path, for the consumers that treat them alike.
+    let sources = Sources::default();
+    sources.add(loopback.clone());
+
+    // ── Outage event log ───────────────────────────────────────────────────────
+    let outages = Arc::new(Mutex::new(OutageLog {
+        closed: persistence::load_outages(&config.outage_file),
+        open: Vec::new(),
+    }));
+    let runtime = Arc::new(Runtime::new(
+        config.clone(),
+        sources.clone(),
+        events.clone(),
+        Arc::clone(&outages),
+        Arc::clone(&session),
+    ));
+    {
+        let sources = sources.clone();
+        let outages = Arc::clone(&outages);
+        let path = config.outage_file.clone();
+        let events = events.clone();
+        tokio::spawn(async move {
+            outage::start_outage_log(path, sources, outages, events).await;
+        });
+    }
+
+    // ── ICMP pingers + MTU probers ────────────────────────────────────────────
+    // Each ping target gets its own history, probers and periodic saves.
+    for target in &config.ping_targets {
+        if let Err(e) = runtime.add_target(target).await {
+            eprintln!("Ping target skipped: {}", e);
+        }
+    }
+
+    // ── STAMP sessions and reflector ──────────────────────────────────────────
+    for reflector in &config.stamp_targets {
+        if let Err(e) = runtime.add_stamp_session(reflector).await {
+            eprintln!("STAMP session skipped: {}", e);
+        }
+    }
+    if let Some(addr) = config.stamp_reflector.clone() {
+        tokio::spawn(async move {
+            network::stamp::start_reflector(addr).await;
+        });
+    }
 
     // ── Listener ───────────────────────────────────────────────────────────────
     {
-        let sent_counter = Arc::clone(&sent_counter);
+        let session = Arc::clone(&session);
         let history = Arc::clone(&history);
+        let events = events.publisher("loopback");
         tokio::spawn(async move {
             network::listener::start_listener(
                 config.target_port,
-                session_id,
-                sent_counter,
+                session,
                 history,
+                events,
             )
             .await;
         });
@@ -69,10 +146,12 @@ async fn main() {
 
     // ── Sender (discovers public IP first) ────────────────────────────────────
     {
-        let sent_counter = Arc::clone(&sent_counter);
+        let session = Arc::clone(&session);
         let history = Arc::clone(&history);
         let loopback_mtu = Arc::clone(&loopback_mtu);
+        let control = Arc::clone(&loopback.control);
         let config = config.clone();
+        let events = events.publisher("loopback");
         tokio::spawn(async move {
             let public_ip = loop {
                 match network::ip::discover(config.alternative_interface.as_deref()).await {
@@ -88,16 +167,17 @@ async fn main() {
                 let loopback_mtu = Arc::clone(&loopback_mtu);
                 let addr = address.clone();
                 let min_mtu = config.min_mtu;
-                let max_mtu = config.max_mtu;
                 let max_queue_size = config.max_queue_size;
+                let control = Arc::clone(&control);
+                let events = events.clone();
                 tokio::spawn(async move {
                     network::mtu::start_probing_udp(
-                        "0.0.0.0:0".to_string(),
                         addr,
                         min_mtu,
-                        max_mtu,
                         max_queue_size,
                         loopback_mtu,
+                        control,
+                        events,
                     )
                     .await;
                 });
@@ -105,61 +185,52 @@ async fn main() {
             network::sender::start_sending(
                 &config,
                 public_ip,
-                session_id,
-                sent_counter,
+                session,
                 history,
+                control,
+                events,
             )
             .await;
         });
     }
 
-    // ── ICMP pingers + MTU probers ────────────────────────────────────────────
-    for src in &ping_sources {
-        let target = src.target.clone();
-        let history = Arc::clone(&src.history);
-        let mtu_history = Arc::clone(&src.mtu_history);
-        let interval_millis = config.interval_millis;
-        let max_packet_size = config.max_packet_size as u32;
-        let max_queue_size = config.max_queue_size;
-        let min_mtu = config.min_mtu;
-        let max_mtu = config.max_mtu;
+    // ── Wall-clock watch ──────────────────────────────────────────────────────
+    tokio::spawn(clock::start_watch());
 
+    // ── Metrics push ──────────────────────────────────────────────────────────
+    {
+        let runtime = Arc::clone(&runtime);
         tokio::spawn(async move {
-            network::pinger::start_pinging(
-                target.clone(),
-                interval_millis,
-                max_packet_size,
-                max_queue_size,
-                history,
-            )
-            .await;
+            metrics::start_push_loop(runtime).await;
         });
+    }
 
-        let target2 = src.target.clone();
+    // ── Alerting ──────────────────────────────────────────────────────────────
+    if !config.alerts.rules.is_empty() {
+        let sources = sources.clone();
+        let alerts = config.alerts.clone();
         tokio::spawn(async move {
-            network::mtu::start_probing_icmp(
-                target2,
-                min_mtu,
-                max_mtu,
-                max_queue_size,
-                mtu_history,
-            )
-            .await;
+            alert::start_alerting(alerts, sources).await;
         });
     }
 
-    // ── Mimir push ────────────────────────────────────────────────────────────
-    {
-        let history = Arc::clone(&history);
-        let loopback_mtu = Arc::clone(&loopback_mtu);
-        let ping_sources = ping_sources.clone();
-        let mimir_url = config.mimir_url.clone();
+    // ── Live event stream ─────────────────────────────────────────────────────
+    if let Some(path) = config.events_socket.clone() {
+        let events = events.clone();
+        tokio::spawn(async move {
+            events::unix::start_socket(path, events).await;
+        });
+    }
+
+    // ── HTTP server (dashboard) ───────────────────────────────────────────────
+    if let Some(addr) = config.http_listen.clone() {
+        let state = http::HttpState { runtime: Arc::clone(&runtime) };
         tokio::spawn(async move {
-            metrics::start_push_loop(mimir_url, history, loopback_mtu, ping_sources).await;
+            http::start_server(addr, state).await;
         });
     }
 
-    // ── Periodic save: packet histories ───────────────────────────────────────
+    // ── Periodic save: loopback histories (ping targets save their own) ───────
     {
         let history = Arc::clone(&history);
         let path = config.data_file.clone();
@@ -174,37 +245,15 @@ async fn main() {
             persistence::start_periodic_save_mtu(path, loopback_mtu).await;
         });
     }
-    for src in &ping_sources {
-        let path = config.ping_data_file_for(&src.target);
-        let history = Arc::clone(&src.history);
-        tokio::spawn(async move {
-            persistence::start_periodic_save(path, history).await;
-        });
-
-        let path = config.ping_mtu_file_for(&src.target);
-        let mtu_history = Arc::clone(&src.mtu_history);
-        tokio::spawn(async move {
-            persistence::start_periodic_save_mtu(path, mtu_history).await;
-        });
-    }
 
     println!("Program is running. Press Ctrl+C to stop.");
     tokio::signal::ctrl_c()
         .await
         .expect("Failed to listen for Ctrl+C");
     println!("Shutting down...");
-    println!("Total packets sent: {}", sent_counter.load(Ordering::Relaxed));
+    println!("Total packets sent: {}", session.sent.load(Ordering::Relaxed));
 
     // ── Final save ────────────────────────────────────────────────────────────
-    persistence::save(&config.data_file, &*history.lock().await);
-    persistence::save_mtu(&config.loopback_mtu_file(), &*loopback_mtu.lock().await);
+    runtime.save().await;
     println!("Data saved to {}", config.data_file);
-
-    for src in &ping_sources {
-        let path = config.ping_data_file_for(&src.target);
-        persistence::save(&path, &*src.history.lock().await);
-        let mtu_path = config.ping_mtu_file_for(&src.target);
-        persistence::save_mtu(&mtu_path, &*src.mtu_history.lock().await);
-        println!("Ping data for {} saved", src.target);
-    }
 }
@@ -0,0 +1,136 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use super::{Metric, Value};
+use crate::config::InfluxConfig;
+
+// ── InfluxDB line protocol ────────────────────────────────────────────────────
+//
+//   <metric name>,<label>=<value>,... value=<f64> <timestamp ms>
+//
+// Histograms become one point with `count`, `sum` and one cumulative
+// `le_<bound>` field per bucket (`le_inf` for the overflow bucket). Line
+// protocol has no NaN or infinity, so points or fields holding one are left out.
+
+/// Escape commas, spaces and equals signs in measurement names, tag keys and tag values.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, ',' | ' ' | '=' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn encode(metrics: &[Metric], ts_ms: i64) -> String {
+    let mut body = String::new();
+    for m in metrics {
+        if matches!(m.value, Value::Counter(v) | Value::Gauge(v) if !v.is_finite()) {
+            continue;
+        }
+        body.push_str(&escape(&m.name));
+        for (k, v) in &m.labels {
+            // Empty tag values are not allowed in line protocol.
+            if !v.is_empty() {
+                let _ = write!(body, ",{}={}", escape(k), escape(v));
+            }
+        }
+        body.push(' ');
+        match &m.value {
+            Value::Counter(v) | Value::Gauge(v) => {
+                let _ = write!(body, "value={}", v);
+            }
+            Value::Histogram(h) => {
+                let _ = write!(body, "count={}i", h.count);
+                if h.sum.is_finite() {
+                    let _ = write!(body, ",sum={}", h.sum);
+                }
+                let mut cumulative = 0;
+                for (i, count) in h.counts.iter().enumerate() {
+                    cumulative += count;
+                    match h.bounds.get(i) {
+                        Some(bound) => {
+                            let _ = write!(body, ",le_{}={}i", bound, cumulative);
+                        }
+                        None => {
+                            let _ = write!(body, ",le_inf={}i", cumulative);
+                        }
+                    }
+                }
+            }
+        }
+        let _ = writeln!(body, " {}", ts_ms);
+    }
+    body
+}
+
+pub struct Writer {
+    config: InfluxConfig,
+    client: reqwest::Client,
+}
+
+impl Writer {
+    pub fn new(config: InfluxConfig) -> Option<Self> {
+        match reqwest::Client::builder().timeout(Duration::from_secs(20)).build() {
+            Ok(client) => {
+                println!("Writing InfluxDB line protocol to {}", config.url);
+                Some(Writer { config, client })
+            }
+            Err(e) => {
+                eprintln!("InfluxDB: cannot build HTTP client: {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn push(&self, metrics: &[Metric], ts_ms: i64) {
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.config.token {
+            let Some(token) = token.resolve() else { return };
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        match request.body(encode(metrics, ts_ms)).send().await {
+            Ok(r) if r.status().is_success() => {}
+            Ok(r) => eprintln!("InfluxDB write failed: HTTP {}", r.status()),
+            Err(e) => eprintln!("InfluxDB write error: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{metric, Histogram};
+
+    #[test]
+    fn escapes_tags_and_drops_empty_ones() {
+        let labels = [
+            ("target".to_string(), "a b,c=d\\e".to_string()),
+            ("empty".to_string(), String::new()),
+        ];
+        let body = encode(&[metric("ping_rtt_median_microseconds", &labels, Value::Gauge(1500.5))], 1_000);
+        assert_eq!(body, "ping_rtt_median_microseconds,target=a\\ b\\,c\\=d\\\\e value=1500.5 1000\n");
+    }
+
+    #[test]
+    fn writes_histograms_as_cumulative_fields() {
+        let histogram = Histogram { bounds: vec![1000.0, 2500.0], counts: vec![1, 2, 0], sum: 4000.0, count: 3 };
+        let body = encode(&[metric("rtt", &[], Value::Histogram(histogram))], 1_000);
+        assert_eq!(body, "rtt count=3i,sum=4000,le_1000=1i,le_2500=3i,le_inf=3i 1000\n");
+    }
+
+    #[test]
+    fn leaves_out_values_that_are_not_finite() {
+        let metrics = [
+            metric("nan", &[], Value::Gauge(f64::NAN)),
+            metric("inf", &[], Value::Counter(f64::INFINITY)),
+            metric("rtt", &[], Value::Histogram(Histogram { bounds: vec![], counts: vec![0], sum: f64::NAN, count: 0 })),
+        ];
+        assert_eq!(encode(&metrics, 1_000), "rtt count=0i,le_inf=0i 1000\n");
+    }
+}
@@ -1,57 +1,81 @@
-use prost::Message as _;
-use std::collections::VecDeque;
+mod influx;
+mod otlp;
+mod plaintext;
+mod remote_write;
+
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tokio::sync::Mutex;
 
-use crate::model::Packet;
+use crate::clock;
+use crate::config::{Config, SinkKind};
+use crate::analysis::burst::{BurstStats, LossRunCounter, BURST_BUCKETS};
+use crate::analysis::emodel;
+use crate::analysis::sla::{Period, SlaTracker};
+use crate::history::{PacketHistory, Totals, RTT_BUCKETS_MICROS, RTT_WINDOW_MICROS};
+use crate::network::sizes::{self, SizeMode};
+use crate::outage::OutageLog;
+use crate::runtime::Runtime;
+use crate::source::SourceKind;
 
-pub struct PingSource {
-    pub target: String,
-    pub history: Arc<Mutex<VecDeque<Packet>>>,
-    pub mtu_history: Arc<Mutex<VecDeque<(u128, u32)>>>,
-}
+// ── Sink-neutral metric model ─────────────────────────────────────────────────
+//
+// Stats are collected once per push into `Metric`s; each sink then encodes
+// them in its own wire format.
 
-impl Clone for PingSource {
-    fn clone(&self) -> Self {
-        PingSource {
-            target: self.target.clone(),
-            history: Arc::clone(&self.history),
-            mtu_history: Arc::clone(&self.mtu_history),
-        }
-    }
+pub enum Value {
+    Counter(f64),
+    Gauge(f64),
+    Histogram(Histogram),
 }
 
-// ── Prometheus remote_write protobuf types ────────────────────────────────────
-
-#[derive(Clone, PartialEq, prost::Message)]
-struct WriteRequest {
-    #[prost(message, repeated, tag = "1")]
-    timeseries: Vec<TimeSeries>,
+pub struct Histogram {
+    /// Upper bounds of every bucket except the final overflow (+Inf) bucket.
+    pub bounds: Vec<f64>,
+    /// Per-bucket counts (not cumulative); one longer than `bounds`.
+    pub counts: Vec<u64>,
+    pub sum: f64,
+    pub count: u64,
 }
 
-#[derive(Clone, PartialEq, prost::Message)]
-struct TimeSeries {
-    #[prost(message, repeated, tag = "1")]
-    labels: Vec<Label>,
-    #[prost(message, repeated, tag = "2")]
-    samples: Vec<Sample>,
+pub struct Metric {
+    pub name: String,
+    /// Sorted by name, unique.
+    pub labels: Vec<(String, String)>,
+    pub value: Value,
 }
 
-#[derive(Clone, PartialEq, prost::Message)]
-struct Label {
-    #[prost(string, tag = "1")]
-    name: String,
-    #[prost(string, tag = "2")]
-    value: String,
+impl Metric {
+    /// Visit the metric as flat Prometheus-style samples `(name, le, value)`.
+    /// Histograms expand to cumulative `_bucket` samples (with `le` set),
+    /// `_sum` and `_count`.
+    pub fn for_each_sample(&self, mut f: impl FnMut(&str, Option<&str>, f64)) {
+        match &self.value {
+            Value::Counter(v) | Value::Gauge(v) => f(&self.name, None, *v),
+            Value::Histogram(h) => {
+                let bucket = format!("{}_bucket", self.name);
+                let mut cumulative = 0;
+                for (i, count) in h.counts.iter().enumerate() {
+                    cumulative += count;
+                    let le = h.bounds.get(i).map_or("+Inf".to_string(), |b| b.to_string());
+                    f(&bucket, Some(&le), cumulative as f64);
+                }
+                f(&format!("{}_sum", self.name), None, h.sum);
+                f(&format!("{}_count", self.name), None, h.count as f64);
+            }
+        }
+    }
 }
 
-#[derive(Clone, PartialEq, prost::Message)]
-struct Sample {
-    #[prost(double, tag = "1")]
-    value: f64,
-    #[prost(int64, tag = "2")]
-    timestamp: i64,
+/// `extra_labels` is ordered most-specific first: when a name repeats, the
+/// earliest occurrence wins (e.g. `target` beats an external label of the same name).
+fn metric(name: &str, extra_labels: &[(String, String)], value: Value) -> Metric {
+    let mut labels = extra_labels.to_vec();
+    // Prometheus requires labels sorted by name and unique. The sort is stable,
+    // so dedup keeps the first (most specific) value for each name.
+    labels.sort_by(|a, b| a.0.cmp(&b.0));
+    labels.dedup_by(|b, a| a.0 == b.0);
+    Metric { name: name.to_string(), labels, value }
 }
 
 // ── Helpers ───────────────────────────────────────────────────────────────────
@@ -63,199 +87,405 @@ fn now_ms() -> i64 {
         .as_millis() as i64
 }
 
-fn make_ts(name: &str, extra_labels: &[(&str, &str)], value: f64, ts_ms: i64) -> TimeSeries {
-    let mut labels = vec![Label {
-        name: "__name__".into(),
-        value: name.into(),
-    }];
-    for &(k, v) in extra_labels {
-        labels.push(Label { name: k.into(), value: v.into() });
-    }
-    // Prometheus requires labels sorted by name.
-    labels.sort_by(|a, b| a.name.cmp(&b.name));
-    TimeSeries {
-        labels,
-        samples: vec![Sample { value, timestamp: ts_ms }],
-    }
-}
-
 // ── Stats computation ─────────────────────────────────────────────────────────
 
 struct Stats {
-    sent: u64,
-    received: u64,
-    lost: u64,
-    reordered: u64,
-    duplicated: u64,
+    counters: Totals,
     rtt_min: Option<u64>,
     rtt_max: Option<u64>,
     rtt_median: Option<u64>,
+    /// Mean absolute difference between consecutive RTTs in the window.
+    jitter: Option<f64>,
+    bursts: BurstStats,
+    loss_runs: LossRunCounter,
+    r_factor: Option<f64>,
+    /// Time user space added to kernel-timestamped RTTs in the window.
+    host_delay_median: Option<u64>,
+    host_delay_max: Option<u64>,
 }
 
-fn compute_stats(queue: &VecDeque<Packet>) -> Stats {
-    let now_us = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_micros() as u128;
-    let cutoff = now_us.saturating_sub(60 * 1_000_000); // RTT window: last 60 s
-
-    let mut sent = 0u64;
-    let mut received = 0u64;
-    let mut lost = 0u64;
-    let mut reordered = 0u64;
-    let mut duplicated = 0u64;
-    let mut recent_rtts: Vec<u64> = Vec::new();
-
-    for p in queue {
-        sent += 1;
-        if p.duplicate {
-            duplicated += 1;
-        } else if p.is_lost() {
-            lost += 1;
-        } else {
-            received += 1;
-            if p.reordered {
-                reordered += 1;
-            }
-            if p.timestamp >= cutoff {
-                recent_rtts.push(p.latency);
-            }
-        }
+fn median(sorted: &[u64]) -> Option<u64> {
+    let n = sorted.len();
+    match n {
+        0 => None,
+        _ if n.is_multiple_of(2) => Some((sorted[n / 2 - 1] + sorted[n / 2]) / 2),
+        _ => Some(sorted[n / 2]),
     }
+}
+
+/// Reads the history's lifetime counters; only the short RTT window is scanned,
+/// so the cost does not grow with retention.
+fn compute_stats(history: &mut PacketHistory) -> Stats {
+    let counters = history.counters();
+    let mut recent_rtts = history.recent_rtts();
+
+    let jitter = (recent_rtts.len() >= 2).then(|| {
+        let diffs: u64 = recent_rtts.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+        diffs as f64 / (recent_rtts.len() - 1) as f64
+    });
+
+    recent_rtts.sort_unstable();
+    let (rtt_min, rtt_max, rtt_median) =
+        (recent_rtts.first().copied(), recent_rtts.last().copied(), median(&recent_rtts));
+
+    let mut host_delays = history.recent_host_delays();
+    host_delays.sort_unstable();
+    let (host_delay_median, host_delay_max) = (median(&host_delays), host_delays.last().copied());
+
+    let bursts = history.burst_stats();
+    let loss_runs = history.loss_runs();
 
-    let (rtt_min, rtt_max, rtt_median) = if recent_rtts.is_empty() {
-        (None, None, None)
-    } else {
-        recent_rtts.sort_unstable();
-        let n = recent_rtts.len();
-        let median = if n % 2 == 0 {
-            (recent_rtts[n / 2 - 1] + recent_rtts[n / 2]) / 2
-        } else {
-            recent_rtts[n / 2]
-        };
-        (
-            Some(*recent_rtts.first().unwrap()),
-            Some(*recent_rtts.last().unwrap()),
-            Some(median),
-        )
+    // E-model over the window: needs an RTT and at least one settled packet.
+    let window = history.window_burst_stats();
+    let r_factor = match rtt_median {
+        Some(rtt) if window.packets > 0 => Some(emodel::r_factor(&emodel::Inputs {
+            rtt_ms: rtt as f64 / 1000.0,
+            jitter_ms: jitter.unwrap_or(0.0) / 1000.0,
+            loss: window.lost as f64 / window.packets as f64,
+            gilbert: window.gilbert_p.zip(window.gilbert_r),
+        })),
+        _ => None,
     };
 
-    Stats { sent, received, lost, reordered, duplicated, rtt_min, rtt_max, rtt_median }
+    Stats {
+        counters,
+        rtt_min,
+        rtt_max,
+        rtt_median,
+        jitter,
+        bursts,
+        loss_runs,
+        r_factor,
+        host_delay_median,
+        host_delay_max,
+    }
 }
 
-fn push_stats(
-    series: &mut Vec<TimeSeries>,
-    prefix: &str,
-    extra: &[(&str, &str)],
-    s: &Stats,
-    ts_ms: i64,
-) {
-    series.push(make_ts(&format!("{prefix}_packets_sent_total"), extra, s.sent as f64, ts_ms));
-    series.push(make_ts(
-        &format!("{prefix}_packets_received_total"),
-        extra,
-        s.received as f64,
-        ts_ms,
-    ));
-    series.push(make_ts(&format!("{prefix}_packets_lost_total"), extra, s.lost as f64, ts_ms));
-    series.push(make_ts(
-        &format!("{prefix}_packets_reordered_total"),
+fn push_stats(out: &mut Vec<Metric>, prefix: &str, extra: &[(String, String)], s: &Stats) {
+    let t = &s.counters;
+    let counters = [
+        ("packets_sent_total", t.sent),
+        ("packets_received_total", t.received),
+        ("packets_lost_total", t.lost),
+        ("packets_late_total", t.late),
+        ("packets_failed_total", t.failed),
+        ("packets_reordered_total", t.reordered),
+        ("packets_duplicated_total", t.duplicated),
+    ];
+    for (name, v) in counters {
+        out.push(metric(&format!("{prefix}_{name}"), extra, Value::Counter(v as f64)));
+    }
+
+    let gauges = [
+        ("rtt_min_microseconds", s.rtt_min.map(|v| v as f64)),
+        ("rtt_max_microseconds", s.rtt_max.map(|v| v as f64)),
+        ("rtt_median_microseconds", s.rtt_median.map(|v| v as f64)),
+        ("jitter_microseconds", s.jitter),
+        ("host_delay_median_microseconds", s.host_delay_median.map(|v| v as f64)),
+        ("host_delay_max_microseconds", s.host_delay_max.map(|v| v as f64)),
+        ("voip_r_factor", s.r_factor),
+        ("voip_mos", s.r_factor.map(emodel::mos)),
+    ];
+    for (name, v) in gauges {
+        if let Some(v) = v {
+            out.push(metric(&format!("{prefix}_{name}"), extra, Value::Gauge(v)));
+        }
+    }
+
+    out.push(metric(
+        &format!("{prefix}_rtt_microseconds"),
         extra,
-        s.reordered as f64,
-        ts_ms,
+        Value::Histogram(Histogram {
+            bounds: RTT_BUCKETS_MICROS.iter().map(|&b| b as f64).collect(),
+            counts: t.rtt_buckets.to_vec(),
+            sum: t.rtt_sum_micros as f64,
+            count: t.received,
+        }),
     ));
-    series.push(make_ts(
-        &format!("{prefix}_packets_duplicated_total"),
+
+    let runs = &s.loss_runs;
+    out.push(metric(
+        &format!("{prefix}_loss_burst_length_packets"),
         extra,
-        s.duplicated as f64,
-        ts_ms,
+        Value::Histogram(Histogram {
+            bounds: BURST_BUCKETS.iter().map(|&b| b as f64).collect(),
+            counts: runs.buckets.to_vec(),
+            sum: runs.lost as f64,
+            count: runs.runs,
+        }),
     ));
-    if let Some(v) = s.rtt_min {
-        series.push(make_ts(&format!("{prefix}_rtt_min_microseconds"), extra, v as f64, ts_ms));
+    let b = &s.bursts;
+    let burst_gauges = [
+        ("loss_burst_mean_length_packets", b.mean_burst_length),
+        ("loss_gap_mean_length_packets", b.mean_gap_length),
+        ("gilbert_p", b.gilbert_p),
+        ("gilbert_r", b.gilbert_r),
+        ("gilbert_bad_state_probability", b.pi_bad()),
+    ];
+    for (name, v) in burst_gauges {
+        if let Some(v) = v {
+            out.push(metric(&format!("{prefix}_{name}"), extra, Value::Gauge(v)));
+        }
     }
-    if let Some(v) = s.rtt_max {
-        series.push(make_ts(&format!("{prefix}_rtt_max_microseconds"), extra, v as f64, ts_ms));
+}
+
+/// Loss and median RTT over the RTT window per probe-size bucket, labelled
+/// `size="lo-hi"` in bytes, for sources whose probe sizes vary.
+fn push_sizes(
+    out: &mut Vec<Metric>,
+    prefix: &str,
+    extra: &[(String, String)],
+    history: &mut PacketHistory,
+    (min, max): (u32, u32),
+    now_us: u128,
+) {
+    let bounds = sizes::bucket_bounds(min, max);
+    let buckets = history.size_breakdown(now_us.saturating_sub(RTT_WINDOW_MICROS), now_us, &bounds);
+    let mut lo = min;
+    for (&hi, mut b) in bounds.iter().zip(buckets) {
+        let mut labels = vec![("size".to_string(), format!("{lo}-{hi}"))];
+        labels.extend(extra.iter().cloned());
+        lo = hi + 1;
+        b.rtts.sort_unstable();
+        let gauges = [
+            ("size_packets", Some(b.sent as f64)),
+            ("size_loss_ratio", (b.sent > 0).then(|| b.lost as f64 / b.sent as f64)),
+            ("size_rtt_median_microseconds", median(&b.rtts).map(|v| v as f64)),
+        ];
+        for (name, v) in gauges {
+            if let Some(v) = v {
+                out.push(metric(&format!("{prefix}_{name}"), &labels, Value::Gauge(v)));
+            }
+        }
     }
-    if let Some(v) = s.rtt_median {
-        series.push(make_ts(
-            &format!("{prefix}_rtt_median_microseconds"),
-            extra,
-            v as f64,
-            ts_ms,
-        ));
+}
+
+/// ICMP errors about the packets held, by type and reporting router, and the
+/// latest MTU each router reported.
+fn push_icmp_errors(out: &mut Vec<Metric>, prefix: &str, extra: &[(String, String)], history: &PacketHistory) {
+    let mut counts: Vec<((&str, String), u64)> = Vec::new();
+    let mut mtus: Vec<(String, u32)> = Vec::new();
+    for (_, e) in history.icmp_errors() {
+        let router = e.router.map_or_else(String::new, |r| r.to_string());
+        match counts.iter_mut().find(|((kind, r), _)| *kind == e.kind.name() && *r == router) {
+            Some((_, n)) => *n += 1,
+            None => counts.push(((e.kind.name(), router.clone()), 1)),
+        }
+        if let Some(mtu) = e.mtu {
+            match mtus.iter_mut().find(|(r, _)| *r == router) {
+                Some((_, m)) => *m = mtu,
+                None => mtus.push((router, mtu)),
+            }
+        }
+    }
+    for ((kind, router), n) in counts {
+        let mut labels = vec![("type".to_string(), kind.to_string()), ("router".to_string(), router)];
+        labels.extend(extra.iter().cloned());
+        out.push(metric(&format!("{prefix}_icmp_errors_total"), &labels, Value::Counter(n as f64)));
+    }
+    for (router, mtu) in mtus {
+        let mut labels = vec![("router".to_string(), router)];
+        labels.extend(extra.iter().cloned());
+        out.push(metric(&format!("{prefix}_icmp_reported_mtu_bytes"), &labels, Value::Gauge(mtu as f64)));
     }
 }
 
-// ── Push loop ─────────────────────────────────────────────────────────────────
+/// Outage counters for one source over the retained event log.
+fn push_outages(
+    out: &mut Vec<Metric>,
+    prefix: &str,
+    extra: &[(String, String)],
+    log: &OutageLog,
+    source: &str,
+    now_us: u128,
+) {
+    let mut count = 0u64;
+    let mut seconds = 0.0;
+    let mut active = false;
+    for o in log.iter().filter(|o| o.source == source) {
+        count += 1;
+        seconds += o.duration_micros(now_us) as f64 / 1e6;
+        active |= o.end.is_none();
+    }
+    out.push(metric(&format!("{prefix}_outages_total"), extra, Value::Counter(count as f64)));
+    out.push(metric(&format!("{prefix}_outage_seconds_total"), extra, Value::Counter(seconds)));
+    out.push(metric(&format!("{prefix}_outage_active"), extra, Value::Gauge(active as u8 as f64)));
+}
 
-pub async fn start_push_loop(
-    mimir_url: String,
-    history: Arc<Mutex<VecDeque<Packet>>>,
-    loopback_mtu: Arc<Mutex<VecDeque<(u128, u32)>>>,
-    ping_sources: Vec<PingSource>,
+/// Availability over the current day, week and month, labelled by `period`.
+fn push_sla(
+    out: &mut Vec<Metric>,
+    prefix: &str,
+    extra: &[(String, String)],
+    sla: &SlaTracker,
+    outages: &[(u64, u64)],
+    now_us: u128,
 ) {
-    let client = reqwest::Client::new();
-    let mut interval = tokio::time::interval(Duration::from_secs(30));
-    interval.tick().await; // discard immediate first tick; wait a full interval
+    let now = (now_us / 1_000_000) as u64;
+    for period in Period::ALL {
+        let reports = sla.report(period, now, outages);
+        let Some(r) = reports.last().filter(|r| r.end > now) else { continue };
+        let mut labels = vec![("period".to_string(), period.name().to_string())];
+        labels.extend(extra.iter().cloned());
+        let gauges = [
+            ("availability_ratio", r.availability()),
+            ("degraded_ratio", r.degraded_ratio()),
+            ("down_seconds", Some(r.down as f64)),
+            ("unmonitored_seconds", Some(r.unmonitored as f64)),
+        ];
+        for (name, v) in gauges {
+            if let Some(v) = v {
+                out.push(metric(&format!("{prefix}_{name}"), &labels, Value::Gauge(v)));
+            }
+        }
+    }
+}
 
-    loop {
-        interval.tick().await;
+// ── Sinks ─────────────────────────────────────────────────────────────────────
 
-        let ts_ms = now_ms();
-        let mut series: Vec<TimeSeries> = Vec::new();
+enum Sink {
+    RemoteWrite(Vec<remote_write::Endpoint>),
+    Otlp(otlp::Exporter),
+    Influx(influx::Writer),
+    Plaintext(plaintext::Writer),
+}
 
-        // Loopback packet metrics
-        {
-            let q = history.lock().await;
-            let stats = compute_stats(&q);
-            push_stats(&mut series, "loopback", &[], &stats, ts_ms);
+impl Sink {
+    fn new(kind: SinkKind, config: &Config) -> Option<Sink> {
+        match kind {
+            SinkKind::RemoteWrite => {
+                let endpoints: Vec<_> = config
+                    .remote_write
+                    .iter()
+                    .cloned()
+                    .filter_map(remote_write::Endpoint::new)
+                    .collect();
+                if endpoints.is_empty() {
+                    println!("No remote_write endpoints configured; remote_write sink disabled");
+                    return None;
+                }
+                Some(Sink::RemoteWrite(endpoints))
+            }
+            SinkKind::Otlp => match &config.otlp {
+                Some(cfg) => otlp::Exporter::new(cfg.clone()).map(Sink::Otlp),
+                None => {
+                    eprintln!("OTLP sink selected but no OTLP endpoint configured");
+                    None
+                }
+            },
+            SinkKind::Influx => match &config.influx {
+                Some(cfg) => influx::Writer::new(cfg.clone()).map(Sink::Influx),
+                None => {
+                    eprintln!("InfluxDB sink selected but INFLUX_URL is not set");
+                    None
+                }
+            },
+            SinkKind::Graphite => match &config.graphite {
+                Some(cfg) => Some(Sink::Plaintext(plaintext::Writer::graphite(cfg.clone()))),
+                None => {
+                    eprintln!("Graphite sink selected but GRAPHITE_ADDR is not set");
+                    None
+                }
+            },
+            SinkKind::Statsd => match &config.statsd {
+                Some(cfg) => Some(Sink::Plaintext(plaintext::Writer::statsd(cfg.clone()))),
+                None => {
+                    eprintln!("StatsD sink selected but STATSD_ADDR is not set");
+                    None
+                }
+            },
         }
-        {
-            let q = loopback_mtu.lock().await;
-            if let Some(&(_, mtu)) = q.back() {
-                series.push(make_ts("loopback_mtu_bytes", &[], mtu as f64, ts_ms));
+    }
+
+    async fn push(&self, metrics: &[Metric], ts_ms: i64) {
+        match self {
+            Sink::RemoteWrite(endpoints) => {
+                // Encode protobuf → snappy → HTTP POST to every endpoint
+                let series = remote_write::to_series(metrics, ts_ms);
+                let Some(body) = remote_write::encode(series) else { return };
+                for endpoint in endpoints {
+                    endpoint.push(body.clone()).await;
+                }
             }
+            Sink::Otlp(exporter) => exporter.push(metrics, ts_ms).await,
+            Sink::Influx(writer) => writer.push(metrics, ts_ms).await,
+            Sink::Plaintext(writer) => writer.push(metrics, ts_ms).await,
         }
+    }
+}
+
+// ── Push loop ─────────────────────────────────────────────────────────────────
+
+pub async fn start_push_loop(runtime: Arc<Runtime>) {
+    let config = &runtime.config;
+    let sinks: Vec<Sink> = config
+        .metrics_sinks
+        .iter()
+        .filter_map(|&kind| Sink::new(kind, config))
+        .collect();
+    if sinks.is_empty() {
+        println!("No metrics sinks configured; metrics push disabled");
+        return;
+    }
+    let external_labels = &config.external_labels;
+
+    let mut interval = tokio::time::interval(Duration::from_secs(30));
+    interval.tick().await; // discard immediate first tick; wait a full interval
 
-        // Per-target ping metrics
-        for src in &ping_sources {
-            let target = src.target.as_str();
-            let extra = &[("target", target)];
+    loop {
+        // A push forced through the control API does not shift the schedule.
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = runtime.push_now.notified() => {}
+        }
+
+        let ts_ms = now_ms();
+        let now_us = ts_ms as u128 * 1000;
+        let mut metrics: Vec<Metric> = Vec::new();
+        let log = runtime.outages.lock().await;
+
+        // Loopback metrics, then per-target ping and STAMP metrics
+        for src in runtime.sources.snapshot() {
+            let (prefix, mut extra) = src.series();
+            extra.extend(src.labels.iter().cloned());
+            extra.extend(external_labels.iter().cloned());
+            let extra = &extra;
             {
-                let q = src.history.lock().await;
-                let stats = compute_stats(&q);
-                push_stats(&mut series, "ping", extra, &stats, ts_ms);
+                let mut q = src.history.lock().await;
+                let stats = compute_stats(&mut q);
+                push_stats(&mut metrics, prefix, extra, &stats);
+                push_icmp_errors(&mut metrics, prefix, extra, &q);
+                if src.control.size_mode() != SizeMode::Fixed {
+                    push_sizes(&mut metrics, prefix, extra, &mut q, src.control.size_range(), now_us);
+                }
+                let intervals = log.intervals(&src.name, now_us);
+                push_sla(&mut metrics, prefix, extra, q.sla(), &intervals, now_us);
             }
+            push_outages(&mut metrics, prefix, extra, &log, &src.name, now_us);
             {
                 let q = src.mtu_history.lock().await;
                 if let Some(&(_, mtu)) = q.back() {
-                    series.push(make_ts("ping_mtu_bytes", extra, mtu as f64, ts_ms));
+                    metrics.push(metric(&format!("{prefix}_mtu_bytes"), extra, Value::Gauge(mtu as f64)));
                 }
             }
+            if src.kind == SourceKind::Loopback {
+                let rejected = runtime.session.rejected.load(Ordering::Relaxed) as f64;
+                metrics.push(metric(&format!("{prefix}_datagrams_rejected_total"), extra, Value::Counter(rejected)));
+            }
         }
 
-        // Encode protobuf → snappy → HTTP POST
-        let proto = WriteRequest { timeseries: series }.encode_to_vec();
-        let body = match snap::raw::Encoder::new().compress_vec(&proto) {
-            Ok(b) => b,
-            Err(e) => {
-                eprintln!("Mimir: snappy compression failed: {e}");
-                continue;
-            }
-        };
-
-        match client
-            .post(&mimir_url)
-            .header("Content-Type", "application/x-protobuf")
-            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
-            .header("X-Scope-OrgID", "anonymous")
-            .body(body)
-            .send()
-            .await
-        {
-            Ok(r) if r.status().is_success() => {}
-            Ok(r) => eprintln!("Mimir push failed: HTTP {}", r.status()),
-            Err(e) => eprintln!("Mimir push error: {e}"),
+        drop(log);
+
+        let clock_metrics = [
+            ("clock_jumps_total", Value::Counter(clock::jumps() as f64)),
+            ("clock_synchronised", Value::Gauge(clock::synchronised() as u8 as f64)),
+        ];
+        for (name, value) in clock_metrics {
+            metrics.push(metric(name, external_labels, value));
+        }
+
+        for sink in &sinks {
+            sink.push(&metrics, ts_ms).await;
         }
     }
 }
@@ -0,0 +1,324 @@
+use prost::Message as _;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{Metric, Value};
+use crate::config::OtlpConfig;
+
+// ── OTLP metrics protobuf types (opentelemetry/proto/metrics/v1) ──────────────
+//
+// Only the fields we emit. `oneof` members are modelled as optional fields,
+// which is wire-compatible as long as at most one of them is set.
+//
+// Counters and histograms count from process start, so both are cumulative:
+// counters as monotonic sums, histograms with explicit bucket bounds.
+
+const AGGREGATION_TEMPORALITY_CUMULATIVE: i32 = 2;
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ExportMetricsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    resource_metrics: Vec<ResourceMetrics>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ResourceMetrics {
+    #[prost(message, optional, tag = "1")]
+    resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    scope_metrics: Vec<ScopeMetrics>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Resource {
+    #[prost(message, repeated, tag = "1")]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ScopeMetrics {
+    #[prost(message, optional, tag = "1")]
+    scope: Option<InstrumentationScope>,
+    #[prost(message, repeated, tag = "2")]
+    metrics: Vec<OtlpMetric>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct InstrumentationScope {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    version: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct OtlpMetric {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "3")]
+    unit: String,
+    #[prost(message, optional, tag = "5")]
+    gauge: Option<Gauge>,
+    #[prost(message, optional, tag = "7")]
+    sum: Option<Sum>,
+    #[prost(message, optional, tag = "9")]
+    histogram: Option<OtlpHistogram>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Gauge {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<NumberDataPoint>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Sum {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<NumberDataPoint>,
+    #[prost(int32, tag = "2")]
+    aggregation_temporality: i32,
+    #[prost(bool, tag = "3")]
+    is_monotonic: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct NumberDataPoint {
+    #[prost(fixed64, tag = "2")]
+    start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "3")]
+    time_unix_nano: u64,
+    #[prost(double, optional, tag = "4")]
+    as_double: Option<f64>,
+    #[prost(message, repeated, tag = "7")]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct OtlpHistogram {
+    #[prost(message, repeated, tag = "1")]
+    data_points: Vec<HistogramDataPoint>,
+    #[prost(int32, tag = "2")]
+    aggregation_temporality: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct HistogramDataPoint {
+    #[prost(fixed64, tag = "2")]
+    start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "3")]
+    time_unix_nano: u64,
+    #[prost(fixed64, tag = "4")]
+    count: u64,
+    #[prost(double, optional, tag = "5")]
+    sum: Option<f64>,
+    /// Per bucket, not cumulative; one more than `explicit_bounds`.
+    #[prost(fixed64, repeated, tag = "6")]
+    bucket_counts: Vec<u64>,
+    #[prost(double, repeated, tag = "7")]
+    explicit_bounds: Vec<f64>,
+    #[prost(message, repeated, tag = "9")]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct KeyValue {
+    #[prost(string, tag = "1")]
+    key: String,
+    #[prost(message, optional, tag = "2")]
+    value: Option<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AnyValue {
+    #[prost(string, optional, tag = "1")]
+    string_value: Option<String>,
+}
+
+fn attributes(pairs: &[(String, String)]) -> Vec<KeyValue> {
+    pairs
+        .iter()
+        .map(|(k, v)| KeyValue {
+            key: k.clone(),
+            value: Some(AnyValue { string_value: Some(v.clone()) }),
+        })
+        .collect()
+}
+
+/// Units follow the metric name suffix convention used by the Prometheus names.
+fn unit(name: &str) -> &'static str {
+    if name.ends_with("_microseconds") {
+        "us"
+    } else if name.ends_with("_bytes") {
+        "By"
+    } else {
+        "1"
+    }
+}
+
+// ── Exporter ──────────────────────────────────────────────────────────────────
+
+pub struct Exporter {
+    config: OtlpConfig,
+    client: reqwest::Client,
+    /// Start of the cumulative aggregation period: process start.
+    start_time_unix_nano: u64,
+}
+
+impl Exporter {
+    pub fn new(config: OtlpConfig) -> Option<Self> {
+        let client = match reqwest::Client::builder().timeout(Duration::from_secs(20)).build() {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("OTLP: cannot build HTTP client: {}", e);
+                return None;
+            }
+        };
+        let start_time_unix_nano = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        println!("Exporting OTLP metrics to {}", config.url);
+        Some(Exporter { config, client, start_time_unix_nano })
+    }
+
+    fn encode(&self, metrics: &[Metric], ts_ms: i64) -> Vec<u8> {
+        let time_unix_nano = ts_ms as u64 * 1_000_000;
+        let start_time_unix_nano = self.start_time_unix_nano;
+
+        let number_point = |labels: &[(String, String)], v: f64| NumberDataPoint {
+            start_time_unix_nano,
+            time_unix_nano,
+            as_double: Some(v),
+            attributes: attributes(labels),
+        };
+
+        let otlp_metrics = metrics
+            .iter()
+            .map(|m| {
+                let mut out = OtlpMetric { name: m.name.clone(), unit: unit(&m.name).to_string(), ..Default::default() };
+                match &m.value {
+                    Value::Counter(v) => {
+                        out.sum = Some(Sum {
+                            data_points: vec![number_point(&m.labels, *v)],
+                            aggregation_temporality: AGGREGATION_TEMPORALITY_CUMULATIVE,
+                            is_monotonic: true,
+                        })
+                    }
+                    Value::Gauge(v) => out.gauge = Some(Gauge { data_points: vec![number_point(&m.labels, *v)] }),
+                    Value::Histogram(h) => {
+                        out.histogram = Some(OtlpHistogram {
+                            data_points: vec![HistogramDataPoint {
+                                start_time_unix_nano,
+                                time_unix_nano,
+                                count: h.count,
+                                sum: Some(h.sum),
+                                bucket_counts: h.counts.clone(),
+                                explicit_bounds: h.bounds.clone(),
+                                attributes: attributes(&m.labels),
+                            }],
+                            aggregation_temporality: AGGREGATION_TEMPORALITY_CUMULATIVE,
+                        })
+                    }
+                }
+                out
+            })
+            .collect();
+
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource {
+                    attributes: attributes(&self.config.resource_attributes),
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope {
+                        name: env!("CARGO_PKG_NAME").to_string(),
+                        version: env!("CARGO_PKG_VERSION").to_string(),
+                    }),
+                    metrics: otlp_metrics,
+                }],
+            }],
+        }
+        .encode_to_vec()
+    }
+
+    pub async fn push(&self, metrics: &[Metric], ts_ms: i64) {
+        let body = self.encode(metrics, ts_ms);
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header("Content-Type", "application/x-protobuf");
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+        match request.body(body).send().await {
+            Ok(r) if r.status().is_success() => {}
+            Ok(r) => eprintln!("OTLP push failed: HTTP {}", r.status()),
+            Err(e) => eprintln!("OTLP push error: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{metric, Histogram};
+    use crate::test_http;
+
+    #[tokio::test]
+    async fn posts_a_decodable_export_request() {
+        let (listener, url) = test_http::bind().await;
+        let collector = tokio::spawn(test_http::receive_one(listener));
+
+        let exporter = Exporter::new(OtlpConfig {
+            url: format!("{}/v1/metrics", url),
+            headers: Vec::new(),
+            resource_attributes: vec![("service.name".to_string(), "loopback".to_string())],
+        })
+        .unwrap();
+        let target = [("target".to_string(), "1.1.1.1".to_string())];
+        let metrics = [
+            metric("ping_packets_sent_total", &target, Value::Counter(10.0)),
+            metric("ping_rtt_median_microseconds", &target, Value::Gauge(1500.0)),
+            metric(
+                "ping_rtt_microseconds",
+                &target,
+                Value::Histogram(Histogram { bounds: vec![1000.0, 2500.0], counts: vec![1, 2, 0], sum: 4000.0, count: 3 }),
+            ),
+        ];
+        exporter.push(&metrics, 1_700_000_000_000).await;
+
+        let posted = collector.await.unwrap();
+        assert!(posted.head.starts_with("post /v1/metrics "));
+        assert!(posted.head.contains("content-type: application/x-protobuf"));
+        let request = ExportMetricsServiceRequest::decode(posted.body.as_slice()).unwrap();
+        let resource = &request.resource_metrics[0];
+        let service = &resource.resource.as_ref().unwrap().attributes[0];
+        assert_eq!(service.key, "service.name");
+        let scope = &resource.scope_metrics[0];
+        assert_eq!(scope.scope.as_ref().unwrap().name, "loopback");
+
+        let by_name = |name: &str| scope.metrics.iter().find(|m| m.name == name).unwrap();
+        let sent = by_name("ping_packets_sent_total").sum.as_ref().unwrap();
+        assert!(sent.is_monotonic);
+        assert_eq!(sent.aggregation_temporality, AGGREGATION_TEMPORALITY_CUMULATIVE);
+        let point = &sent.data_points[0];
+        assert_eq!(point.as_double, Some(10.0));
+        assert_eq!(point.time_unix_nano, 1_700_000_000_000_000_000);
+        assert_eq!(point.attributes[0].key, "target");
+
+        let median = by_name("ping_rtt_median_microseconds");
+        assert_eq!(median.unit, "us");
+        assert_eq!(median.gauge.as_ref().unwrap().data_points[0].as_double, Some(1500.0));
+
+        let rtt = by_name("ping_rtt_microseconds");
+        assert_eq!(rtt.unit, "us");
+        let histogram = rtt.histogram.as_ref().unwrap();
+        assert_eq!(histogram.aggregation_temporality, AGGREGATION_TEMPORALITY_CUMULATIVE);
+        let point = &histogram.data_points[0];
+        assert_eq!(point.explicit_bounds, [1000.0, 2500.0]);
+        assert_eq!(point.bucket_counts, [1, 2, 0]);
+        assert_eq!((point.count, point.sum), (3, Some(4000.0)));
+        assert_eq!(point.start_time_unix_nano, exporter.start_time_unix_nano);
+        assert_eq!(point.attributes[0].key, "target");
+        assert_eq!(scope.metrics.len(), 3);
+    }
+}
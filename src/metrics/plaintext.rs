@@ -0,0 +1,189 @@
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+
+use super::Metric;
+use crate::config::{PlaintextConfig, Protocol};
+
+// ── Graphite plaintext and StatsD ─────────────────────────────────────────────
+//
+// Graphite (tagged, 1.1+):  <name>;<label>=<value>;... <value> <unix seconds>
+// StatsD (DogStatsD tags):  <name>:<value>|g|#<label>:<value>,...
+//
+// Our counters are absolute totals since startup, so StatsD gets them as
+// gauges; StatsD would otherwise sum the increments itself. Neither format
+// takes NaN or infinity, so such samples are left out.
+
+/// Keep UDP datagrams under a typical path MTU.
+const MAX_DATAGRAM: usize = 1400;
+
+#[derive(Clone, Copy)]
+enum Format {
+    Graphite,
+    Statsd,
+}
+
+/// Replace characters that are separators in the target format.
+fn sanitize(s: &str, reserved: &[char]) -> String {
+    s.chars()
+        .map(|c| if c.is_whitespace() || reserved.contains(&c) { '_' } else { c })
+        .collect()
+}
+
+fn encode(format: Format, prefix: &str, metrics: &[Metric], ts_ms: i64) -> Vec<String> {
+    let mut lines = Vec::new();
+    for m in metrics {
+        m.for_each_sample(|name, le, value| {
+            if !value.is_finite() {
+                return;
+            }
+            let tags = m
+                .labels
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .chain(le.map(|le| ("le", le)))
+                .filter(|(_, v)| !v.is_empty());
+            let line = match format {
+                Format::Graphite => {
+                    let reserved = [';', '~', '!', '^', '='];
+                    let mut line = sanitize(&format!("{}{}", prefix, name), &reserved);
+                    for (k, v) in tags {
+                        line.push_str(&format!(";{}={}", sanitize(k, &reserved), sanitize(v, &reserved)));
+                    }
+                    format!("{} {} {}", line, value, ts_ms / 1000)
+                }
+                Format::Statsd => {
+                    let reserved = [':', '|', '@', ',', '#'];
+                    let tags: Vec<String> = tags
+                        .map(|(k, v)| format!("{}:{}", sanitize(k, &reserved), sanitize(v, &reserved)))
+                        .collect();
+                    let mut line =
+                        format!("{}:{}|g", sanitize(&format!("{}{}", prefix, name), &reserved), value);
+                    if !tags.is_empty() {
+                        line.push_str("|#");
+                        line.push_str(&tags.join(","));
+                    }
+                    line
+                }
+            };
+            lines.push(line);
+        });
+    }
+    lines
+}
+
+pub struct Writer {
+    config: PlaintextConfig,
+    format: Format,
+}
+
+impl Writer {
+    pub fn graphite(config: PlaintextConfig) -> Self {
+        println!("Writing Graphite plaintext to {} ({:?})", config.addr, config.protocol);
+        Writer { config, format: Format::Graphite }
+    }
+
+    pub fn statsd(config: PlaintextConfig) -> Self {
+        println!("Writing StatsD to {} ({:?})", config.addr, config.protocol);
+        Writer { config, format: Format::Statsd }
+    }
+
+    fn name(&self) -> &'static str {
+        match self.format {
+            Format::Graphite => "Graphite",
+            Format::Statsd => "StatsD",
+        }
+    }
+
+    pub async fn push(&self, metrics: &[Metric], ts_ms: i64) {
+        let lines = encode(self.format, &self.config.prefix, metrics, ts_ms);
+        let result = match self.config.protocol {
+            Protocol::Tcp => send_tcp(&self.config.addr, &lines).await,
+            Protocol::Udp => send_udp(&self.config.addr, &lines).await,
+        };
+        if let Err(e) = result {
+            eprintln!("{} push to {} failed: {}", self.name(), self.config.addr, e);
+        }
+    }
+}
+
+async fn send_tcp(addr: &str, lines: &[String]) -> std::io::Result<()> {
+    let connect = TcpStream::connect(addr);
+    let mut stream = tokio::time::timeout(Duration::from_secs(10), connect)
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "connect timed out"))??;
+    let mut body = lines.join("\n");
+    body.push('\n');
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Pack as many newline-separated lines per datagram as fit.
+async fn send_udp(addr: &str, lines: &[String]) -> std::io::Result<()> {
+    let remote = tokio::net::lookup_host(addr)
+        .await?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no address"))?;
+    let bind = if remote.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind).await?;
+    socket.connect(remote).await?;
+
+    let mut datagram = String::new();
+    for line in lines {
+        if !datagram.is_empty() && datagram.len() + 1 + line.len() > MAX_DATAGRAM {
+            socket.send(datagram.as_bytes()).await?;
+            datagram.clear();
+        }
+        if !datagram.is_empty() {
+            datagram.push('\n');
+        }
+        datagram.push_str(line);
+    }
+    if !datagram.is_empty() {
+        socket.send(datagram.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{metric, Histogram, Value};
+
+    fn metrics() -> Vec<Metric> {
+        let labels = [("target".to_string(), "10.0.0.1:9;x|y".to_string()), ("empty".to_string(), String::new())];
+        vec![
+            metric("packets_sent_total", &labels, Value::Counter(10.0)),
+            metric("jitter", &labels, Value::Gauge(f64::NAN)),
+            metric("rtt", &[], Value::Histogram(Histogram { bounds: vec![1000.0], counts: vec![1, 2], sum: 4000.0, count: 3 })),
+        ]
+    }
+
+    #[test]
+    fn graphite_lines_carry_sanitised_tags() {
+        assert_eq!(
+            encode(Format::Graphite, "lb.", &metrics(), 1_700_000_000_999),
+            [
+                "lb.packets_sent_total;target=10.0.0.1:9_x|y 10 1700000000",
+                "lb.rtt_bucket;le=1000 1 1700000000",
+                "lb.rtt_bucket;le=+Inf 3 1700000000",
+                "lb.rtt_sum 4000 1700000000",
+                "lb.rtt_count 3 1700000000",
+            ]
+        );
+    }
+
+    #[test]
+    fn statsd_lines_are_gauges_with_sanitised_tags() {
+        assert_eq!(
+            encode(Format::Statsd, "lb.", &metrics(), 0),
+            [
+                "lb.packets_sent_total:10|g|#target:10.0.0.1_9;x_y",
+                "lb.rtt_bucket:1|g|#le:1000",
+                "lb.rtt_bucket:3|g|#le:+Inf",
+                "lb.rtt_sum:4000|g",
+                "lb.rtt_count:3|g",
+            ]
+        );
+    }
+}
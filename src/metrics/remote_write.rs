@@ -0,0 +1,182 @@
+use prost::Message as _;
+use std::time::Duration;
+
+use super::Metric;
+use crate::config::{Auth, RemoteWriteConfig};
+
+// ── Prometheus remote_write protobuf types ────────────────────────────────────
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TimeSeries {
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Sample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+fn make_ts(name: &str, labels: &[(String, String)], extra: Option<(&str, String)>, value: f64, ts_ms: i64) -> TimeSeries {
+    let mut labels: Vec<Label> = labels
+        .iter()
+        .map(|(k, v)| Label { name: k.clone(), value: v.clone() })
+        .collect();
+    labels.push(Label { name: "__name__".into(), value: name.into() });
+    if let Some((k, v)) = extra {
+        labels.push(Label { name: k.into(), value: v });
+    }
+    // Prometheus requires labels sorted by name.
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+    TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp: ts_ms }],
+    }
+}
+
+/// Flatten metrics into Prometheus series. Histograms become the classic
+/// cumulative `_bucket{le=...}`, `_sum` and `_count` series.
+pub fn to_series(metrics: &[Metric], ts_ms: i64) -> Vec<TimeSeries> {
+    let mut series = Vec::new();
+    for m in metrics {
+        m.for_each_sample(|name, le, value| {
+            let le = le.map(|le| ("le", le.to_string()));
+            series.push(make_ts(name, &m.labels, le, value, ts_ms));
+        });
+    }
+    series
+}
+
+/// Encode protobuf → snappy.
+pub fn encode(series: Vec<TimeSeries>) -> Option<Vec<u8>> {
+    let proto = WriteRequest { timeseries: series }.encode_to_vec();
+    match snap::raw::Encoder::new().compress_vec(&proto) {
+        Ok(b) => Some(b),
+        Err(e) => {
+            eprintln!("Remote write: snappy compression failed: {e}");
+            None
+        }
+    }
+}
+
+// ── Endpoints ─────────────────────────────────────────────────────────────────
+
+pub struct Endpoint {
+    config: RemoteWriteConfig,
+    client: reqwest::Client,
+}
+
+impl Endpoint {
+    /// Build the HTTP client for one endpoint. Returns None (and logs why) if
+    /// the TLS material cannot be loaded.
+    pub fn new(config: RemoteWriteConfig) -> Option<Self> {
+        let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(20));
+
+        if let Some(path) = &config.ca_file {
+            let cert = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|pem| reqwest::Certificate::from_pem(&pem).map_err(|e| e.to_string()));
+            match cert {
+                Ok(cert) => builder = builder.add_root_certificate(cert),
+                Err(e) => {
+                    eprintln!("Remote write {}: cannot load CA {}: {}", config.name, path, e);
+                    return None;
+                }
+            }
+        }
+
+        match (&config.client_cert_file, &config.client_key_file) {
+            (Some(cert_path), key_path) => {
+                // rustls wants certificate chain and private key in one PEM buffer.
+                let pem = std::fs::read(cert_path).and_then(|mut pem| {
+                    if let Some(key_path) = key_path {
+                        pem.push(b'\n');
+                        pem.extend(std::fs::read(key_path)?);
+                    }
+                    Ok(pem)
+                });
+                let identity = pem
+                    .map_err(|e| e.to_string())
+                    .and_then(|pem| reqwest::Identity::from_pem(&pem).map_err(|e| e.to_string()));
+                match identity {
+                    Ok(identity) => builder = builder.identity(identity),
+                    Err(e) => {
+                        eprintln!(
+                            "Remote write {}: cannot load client certificate {}: {}",
+                            config.name, cert_path, e
+                        );
+                        return None;
+                    }
+                }
+            }
+            (None, Some(_)) => {
+                eprintln!(
+                    "Remote write {}: client key given without a client certificate, ignoring",
+                    config.name
+                );
+            }
+            (None, None) => {}
+        }
+
+        match builder.build() {
+            Ok(client) => Some(Endpoint { config, client }),
+            Err(e) => {
+                eprintln!("Remote write {}: cannot build HTTP client: {}", config.name, e);
+                None
+            }
+        }
+    }
+
+    pub async fn push(&self, body: Vec<u8>) {
+        let cfg = &self.config;
+        let mut request = self
+            .client
+            .post(&cfg.url)
+            .header("Content-Type", "application/x-protobuf")
+            .header("Content-Encoding", "snappy")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0");
+
+        if let Some(tenant) = &cfg.tenant_id {
+            request = request.header("X-Scope-OrgID", tenant);
+        }
+        match &cfg.auth {
+            Auth::None => {}
+            Auth::Basic { username, password } => {
+                let Some(password) = password.resolve() else { return };
+                request = request.basic_auth(username, Some(password));
+            }
+            Auth::Bearer(token) => {
+                let Some(token) = token.resolve() else { return };
+                request = request.bearer_auth(token);
+            }
+        }
+        for (name, value) in &cfg.headers {
+            request = request.header(name, value);
+        }
+
+        match request.body(body).send().await {
+            Ok(r) if r.status().is_success() => {}
+            Ok(r) => eprintln!("Remote write {} push failed: HTTP {}", cfg.name, r.status()),
+            Err(e) => eprintln!("Remote write {} push error: {e}", cfg.name),
+        }
+    }
+}
@@ -1,10 +1,75 @@
-pub const MAX_LATENCY_MICROS: u64 = 1_000_000;
+use std::net::IpAddr;
+
+/// Time a reply may take before its packet counts as lost, unless configured
+/// per source.
+pub const DEFAULT_LOSS_TIMEOUT_MICROS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketState {
+    /// Sent, and still within the loss timeout.
+    Pending,
+    Received,
+    /// No reply within the loss timeout.
+    Lost,
+    /// Counted as lost, but a reply arrived after the loss timeout.
+    Late,
+    /// Never left the host: the send failed locally, e.g. with EMSGSIZE.
+    /// Not network loss, so left out of loss, availability and outages.
+    Failed,
+}
+
+impl PacketState {
+    pub fn name(self) -> &'static str {
+        match self {
+            PacketState::Pending => "pending",
+            PacketState::Received => "received",
+            PacketState::Lost => "lost",
+            PacketState::Late => "late",
+            PacketState::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcmpKind {
+    /// Too big to forward unfragmented; the error carries the next-hop MTU.
+    FragNeeded,
+    PortUnreachable,
+    HostUnreachable,
+    NetUnreachable,
+    TtlExceeded,
+    Other,
+}
+
+impl IcmpKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IcmpKind::FragNeeded => "frag_needed",
+            IcmpKind::PortUnreachable => "port_unreachable",
+            IcmpKind::HostUnreachable => "host_unreachable",
+            IcmpKind::NetUnreachable => "net_unreachable",
+            IcmpKind::TtlExceeded => "ttl_exceeded",
+            IcmpKind::Other => "other",
+        }
+    }
+}
+
+/// An ICMP error a router returned about one packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IcmpError {
+    pub kind: IcmpKind,
+    /// MTU reported with a frag-needed (packet too big) error.
+    pub mtu: Option<u32>,
+    /// Address of the router that sent the error.
+    pub router: Option<IpAddr>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Packet {
     pub timestamp: u128, // microseconds since epoch (when sent)
-    pub latency: u64,    // round-trip in microseconds; MAX_LATENCY_MICROS = lost/pending
+    pub latency: u64,    // round-trip in microseconds, once a reply arrived
     pub size: u32,       // payload size in bytes
+    pub state: PacketState,
     pub reordered: bool, // arrived after a packet with a higher sequence number
     pub duplicate: bool, // a copy of this packet was already received
 }
@@ -13,14 +78,35 @@ impl Packet {
     pub fn pending(timestamp: u128, size: u32) -> Self {
         Self {
             timestamp,
-            latency: MAX_LATENCY_MICROS,
+            latency: 0,
             size,
+            state: PacketState::Pending,
             reordered: false,
             duplicate: false,
         }
     }
 
+    /// Record a reply `latency` after sending: received, or late once past
+    /// `loss_timeout`.
+    pub fn reply(&mut self, latency: u64, loss_timeout: u64) {
+        self.latency = latency;
+        self.state = if latency <= loss_timeout { PacketState::Received } else { PacketState::Late };
+    }
+
+    pub fn is_received(&self) -> bool {
+        self.state == PacketState::Received
+    }
+
+    /// Lost, including late packets: their reply came after the loss timeout.
     pub fn is_lost(&self) -> bool {
-        self.latency >= MAX_LATENCY_MICROS
+        matches!(self.state, PacketState::Lost | PacketState::Late)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state == PacketState::Pending
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.state == PacketState::Failed
     }
 }
@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::os::unix::io::RawFd;
+
+use crate::model::{IcmpError, IcmpKind};
+use crate::network::inflight::IN_FLIGHT_WINDOW;
+use crate::network::timestamping::{self, Stamp};
+
+// ── Socket error queue (IP_RECVERR, MSG_ERRQUEUE) ─────────────────────────────
+//
+// With IP_RECVERR set, the kernel queues the ICMP errors routers send back
+// about a socket's packets, with the start of the packet they quote, instead
+// of dropping them. The sender's payload starts with its counter, so each
+// error can be put down to the probe that caused it. Transmit timestamps come
+// back through the same queue, so one reader drains both.
+
+/// ICMP errors kept until the sender collects them.
+const MAX_PENDING_ERRORS: usize = 1024;
+
+/// Classify an extended error from the error queue; None unless it came from
+/// ICMP or ICMPv6.
+pub(crate) fn classify(err: &libc::sock_extended_err, router: Option<IpAddr>) -> Option<IcmpError> {
+    let kind = match (err.ee_origin, err.ee_type, err.ee_code) {
+        (libc::SO_EE_ORIGIN_ICMP, 3, 4) | (libc::SO_EE_ORIGIN_ICMP6, 2, _) => IcmpKind::FragNeeded,
+        (libc::SO_EE_ORIGIN_ICMP, 3, 3) | (libc::SO_EE_ORIGIN_ICMP6, 1, 4) => IcmpKind::PortUnreachable,
+        (libc::SO_EE_ORIGIN_ICMP, 3, 1) | (libc::SO_EE_ORIGIN_ICMP6, 1, 3) => IcmpKind::HostUnreachable,
+        (libc::SO_EE_ORIGIN_ICMP, 3, 0) | (libc::SO_EE_ORIGIN_ICMP6, 1, 0) => IcmpKind::NetUnreachable,
+        (libc::SO_EE_ORIGIN_ICMP, 11, _) | (libc::SO_EE_ORIGIN_ICMP6, 3, _) => IcmpKind::TtlExceeded,
+        (libc::SO_EE_ORIGIN_ICMP | libc::SO_EE_ORIGIN_ICMP6, _, _) => IcmpKind::Other,
+        _ => return None,
+    };
+    let mtu = (kind == IcmpKind::FragNeeded && err.ee_info > 0).then_some(err.ee_info);
+    Some(IcmpError { kind, mtu, router })
+}
+
+/// Ask for ICMP errors on a sending socket's error queue.
+pub fn enable_icmp_errors(fd: RawFd) -> bool {
+    let value: libc::c_int = 1;
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::IPPROTO_IP,
+            libc::IP_RECVERR,
+            &value as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&value) as libc::socklen_t,
+        )
+    };
+    result == 0
+}
+
+/// Kernel transmit times and ICMP errors of one socket's packets, by the
+/// sender's own packet counter. The kernel numbers timestamped packets from
+/// zero in send order, so the sender records each packet that took a number.
+///
+/// That is every packet sent, and of the failed sends only those the device
+/// queue dropped (ENOBUFS, reported because of IP_RECVERR). `__ip_append_data`
+/// and `__ip6_append_data` take the next number from `sk_tskey` only after
+/// their EMSGSIZE checks, and give it back on their own error path, so an
+/// oversized or otherwise refused packet leaves the numbering alone.
+#[derive(Default)]
+pub struct ErrorQueue {
+    fd: Option<RawFd>,
+    /// Whether the socket has transmit timestamps enabled.
+    timestamps: bool,
+    /// ID of the first entry in `sent`.
+    first_id: u32,
+    /// (counter, transmit time) of each packet sent, in ID order.
+    sent: VecDeque<(u64, Stamp)>,
+    /// (counter, error) of ICMP errors not yet collected.
+    icmp: VecDeque<(u64, IcmpError)>,
+}
+
+impl ErrorQueue {
+    /// Start reading the error queue of `fd`, which must stay open from now
+    /// on, with or without transmit timestamps enabled.
+    pub fn attach(&mut self, fd: RawFd, timestamps: bool) {
+        self.fd = Some(fd);
+        self.timestamps = timestamps;
+    }
+
+    pub fn sent(&mut self, counter: u64) {
+        self.number(counter);
+    }
+
+    /// Note a send that failed with `error`.
+    pub fn send_failed(&mut self, counter: u64, error: &std::io::Error) {
+        if error.raw_os_error() == Some(libc::ENOBUFS) {
+            self.number(counter);
+        }
+    }
+
+    fn number(&mut self, counter: u64) {
+        if !self.timestamps {
+            return;
+        }
+        self.sent.push_back((counter, Stamp::default()));
+        if self.sent.len() > IN_FLIGHT_WINDOW {
+            self.sent.pop_front();
+            self.first_id = self.first_id.wrapping_add(1);
+        }
+    }
+
+    /// Read everything queued on the socket so far.
+    fn drain(&mut self) {
+        let Some(fd) = self.fd else { return };
+        let mut buf = [0u8; 64];
+        while let Ok((n, ancillary)) =
+            timestamping::recvmsg(fd, &mut buf, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT)
+        {
+            if let Some(id) = ancillary.tx_id {
+                if let Some((_, sent)) = self.sent.get_mut(id.wrapping_sub(self.first_id) as usize) {
+                    sent.merge(ancillary.stamp);
+                }
+            }
+            // The quoted packet is our payload, which starts with the counter.
+            if let (Some(error), true) = (ancillary.icmp, n >= 8) {
+                let counter = u64::from_be_bytes(buf[..8].try_into().unwrap());
+                self.icmp.push_back((counter, error));
+                if self.icmp.len() > MAX_PENDING_ERRORS {
+                    self.icmp.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Transmit time of the packet numbered `counter`, once the kernel has
+    /// reported it.
+    pub fn get(&mut self, counter: u64) -> Option<Stamp> {
+        self.drain();
+        let i = self.sent.binary_search_by_key(&counter, |&(c, _)| c).ok()?;
+        let stamp = self.sent[i].1;
+        (stamp.software.is_some() || stamp.hardware.is_some()).then_some(stamp)
+    }
+
+    /// ICMP errors received since the last call, by packet counter.
+    pub fn take_icmp_errors(&mut self) -> Vec<(u64, IcmpError)> {
+        self.drain();
+        self.icmp.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(origin: u8, ee_type: u8, ee_code: u8, ee_info: u32) -> libc::sock_extended_err {
+        libc::sock_extended_err {
+            ee_errno: 0,
+            ee_origin: origin,
+            ee_type,
+            ee_code,
+            ee_pad: 0,
+            ee_info,
+            ee_data: 0,
+        }
+    }
+
+    fn kind(origin: u8, ee_type: u8, ee_code: u8) -> Option<IcmpKind> {
+        classify(&error(origin, ee_type, ee_code, 0), None).map(|e| e.kind)
+    }
+
+    #[test]
+    fn maps_icmp_types_and_codes() {
+        use libc::SO_EE_ORIGIN_ICMP as ICMP;
+        assert_eq!(kind(ICMP, 3, 0), Some(IcmpKind::NetUnreachable));
+        assert_eq!(kind(ICMP, 3, 1), Some(IcmpKind::HostUnreachable));
+        assert_eq!(kind(ICMP, 3, 3), Some(IcmpKind::PortUnreachable));
+        assert_eq!(kind(ICMP, 3, 4), Some(IcmpKind::FragNeeded));
+        assert_eq!(kind(ICMP, 3, 13), Some(IcmpKind::Other));
+        assert_eq!(kind(ICMP, 11, 0), Some(IcmpKind::TtlExceeded));
+        assert_eq!(kind(ICMP, 11, 1), Some(IcmpKind::TtlExceeded));
+        assert_eq!(kind(ICMP, 12, 0), Some(IcmpKind::Other));
+    }
+
+    #[test]
+    fn maps_icmpv6_types_and_codes() {
+        use libc::SO_EE_ORIGIN_ICMP6 as ICMP6;
+        assert_eq!(kind(ICMP6, 1, 0), Some(IcmpKind::NetUnreachable));
+        assert_eq!(kind(ICMP6, 1, 3), Some(IcmpKind::HostUnreachable));
+        assert_eq!(kind(ICMP6, 1, 4), Some(IcmpKind::PortUnreachable));
+        assert_eq!(kind(ICMP6, 1, 1), Some(IcmpKind::Other));
+        assert_eq!(kind(ICMP6, 2, 0), Some(IcmpKind::FragNeeded));
+        assert_eq!(kind(ICMP6, 3, 0), Some(IcmpKind::TtlExceeded));
+        assert_eq!(kind(ICMP6, 4, 0), Some(IcmpKind::Other));
+    }
+
+    #[test]
+    fn keeps_the_mtu_of_frag_needed_errors_only() {
+        let router = Some("192.0.2.1".parse().unwrap());
+        let e = classify(&error(libc::SO_EE_ORIGIN_ICMP, 3, 4, 1400), router).unwrap();
+        assert_eq!(e, IcmpError { kind: IcmpKind::FragNeeded, mtu: Some(1400), router });
+        let e = classify(&error(libc::SO_EE_ORIGIN_ICMP6, 2, 0, 1280), None).unwrap();
+        assert_eq!(e.mtu, Some(1280));
+        // No next-hop MTU reported.
+        assert_eq!(classify(&error(libc::SO_EE_ORIGIN_ICMP, 3, 4, 0), None).unwrap().mtu, None);
+        assert_eq!(classify(&error(libc::SO_EE_ORIGIN_ICMP, 3, 3, 1400), None).unwrap().mtu, None);
+    }
+
+    #[test]
+    fn refused_sends_do_not_shift_transmit_stamps() {
+        use std::os::unix::io::AsRawFd;
+
+        let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        if !timestamping::enable_transmit(socket.as_raw_fd()) {
+            return;
+        }
+        let mut queue = ErrorQueue::default();
+        queue.attach(socket.as_raw_fd(), true);
+        let to = receiver.local_addr().unwrap();
+
+        for counter in 0..4u64 {
+            // Packet 1 is too big for any path: the send fails with EMSGSIZE.
+            let size = if counter == 1 { 70_000 } else { 100 };
+            let mut payload = vec![0; size];
+            payload[..8].copy_from_slice(&counter.to_be_bytes());
+            match socket.send_to(&payload, to) {
+                Ok(_) => queue.sent(counter),
+                Err(e) => {
+                    assert_eq!(e.raw_os_error(), Some(libc::EMSGSIZE));
+                    queue.send_failed(counter, &e);
+                }
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let stamps: Vec<_> = [0, 2, 3].iter().map(|&c| queue.get(c).and_then(|s| s.software)).collect();
+        assert!(stamps.iter().all(Option::is_some), "{stamps:?}");
+        assert!(stamps.windows(2).all(|w| w[0] <= w[1]));
+        assert!(queue.get(4).is_none());
+    }
+
+    #[test]
+    fn ignores_errors_not_from_icmp() {
+        assert_eq!(classify(&error(libc::SO_EE_ORIGIN_LOCAL, 0, 0, 1400), None), None);
+        assert_eq!(classify(&error(libc::SO_EE_ORIGIN_TIMESTAMPING, 0, 0, 0), None), None);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+/// How many sent packets keep their entry: 100 s at a 10 ms interval.
+pub const IN_FLIGHT_WINDOW: usize = 10_000;
+
+/// Sent packets by key (session and counter), pointing at their history
+/// slot. Replies are matched here rather than by their distance from the
+/// newest packet, which breaks once the history holds packets from before a
+/// reload or has evicted some.
+///
+/// Entries outlive the reply so that late replies and duplicates still find
+/// their packet; the oldest go once `capacity` is reached. A slot the history
+/// has evicted in the meantime is simply not found there.
+pub struct InFlight<K, V = u64> {
+    slots: HashMap<K, V>,
+    order: VecDeque<K>,
+    capacity: usize,
+}
+
+impl<K: Copy + Eq + Hash, V: Copy> InFlight<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        InFlight {
+            slots: HashMap::with_capacity(capacity + 1),
+            order: VecDeque::with_capacity(capacity + 1),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, slot: V) {
+        if self.slots.insert(key, slot).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.slots.remove(&old);
+            }
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.slots.get(key).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_the_oldest_entries_at_capacity() {
+        let mut in_flight: InFlight<(u32, u64)> = InFlight::new(3);
+        for counter in 0..5 {
+            in_flight.insert((1, counter), 100 + counter);
+        }
+        assert_eq!(in_flight.get(&(1, 0)), None);
+        assert_eq!(in_flight.get(&(1, 1)), None);
+        assert_eq!(in_flight.get(&(1, 2)), Some(102));
+        assert_eq!(in_flight.get(&(1, 4)), Some(104));
+        // Another session's counter is another packet.
+        assert_eq!(in_flight.get(&(2, 4)), None);
+    }
+
+    #[test]
+    fn reinserting_a_key_keeps_one_entry() {
+        let mut in_flight: InFlight<u64> = InFlight::new(2);
+        in_flight.insert(0, 10);
+        in_flight.insert(0, 11);
+        in_flight.insert(1, 12);
+        assert_eq!(in_flight.get(&0), Some(11));
+        assert_eq!(in_flight.get(&1), Some(12));
+        in_flight.insert(2, 13);
+        assert_eq!(in_flight.get(&0), None);
+        assert_eq!(in_flight.get(&1), Some(12));
+    }
+}
@@ -1,22 +1,59 @@
 use byteorder::{BigEndian, ReadBytesExt};
 use std::collections::{HashSet, VecDeque};
 use std::io::Cursor;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::Interest;
 use tokio::net::UdpSocket;
 use tokio::sync::Mutex;
 
-use crate::model::Packet;
+use crate::clock;
+use crate::events::{Event, Publisher};
+use crate::history::PacketHistory;
+use crate::network::mtu;
+use crate::network::sender::{Session, HEADER_SIZE, SIGNED_SIZE};
+use crate::network::timestamping;
 
 // Keep a sliding window of recently seen sequence numbers for duplicate detection.
 const SEEN_WINDOW: usize = 10_000;
 
+/// Duplicate and reorder detection over the sequence numbers of replies.
+#[derive(Default)]
+pub struct SeenWindow {
+    seqs: VecDeque<u64>,
+    set: HashSet<u64>,
+    max_seen: Option<u64>,
+}
+
+impl SeenWindow {
+    /// Record a sequence number; returns (duplicate, reordered). A packet is
+    /// reordered if it arrives after a higher-numbered one.
+    pub fn observe(&mut self, seq: u64) -> (bool, bool) {
+        if self.set.contains(&seq) {
+            return (true, false);
+        }
+        let reordered = self.max_seen.is_some_and(|max| seq < max);
+        if !reordered {
+            self.max_seen = Some(seq);
+        }
+        // Maintain bounded sliding window
+        self.seqs.push_back(seq);
+        self.set.insert(seq);
+        if self.seqs.len() > SEEN_WINDOW {
+            if let Some(old) = self.seqs.pop_front() {
+                self.set.remove(&old);
+            }
+        }
+        (false, reordered)
+    }
+}
+
 pub async fn start_listener(
     port: u16,
-    session_id: u32,
-    sent_counter: Arc<AtomicU64>,
-    history: Arc<Mutex<VecDeque<Packet>>>,
+    session: Arc<Session>,
+    history: Arc<Mutex<PacketHistory>>,
+    events: Publisher,
 ) {
     let addr = format!("0.0.0.0:{}", port);
     let socket = match UdpSocket::bind(&addr).await {
@@ -32,16 +69,19 @@ pub async fn start_listener(
     };
 
     println!("Listening for loopback packets on {}", addr);
+    let fd = socket.as_raw_fd();
+    if !timestamping::enable_receive(fd) {
+        eprintln!(
+            "Kernel receive timestamps unavailable ({}); RTTs use user-space receive times",
+            std::io::Error::last_os_error()
+        );
+    }
 
     let mut buf = [0u8; 2048];
-    // Sliding window for duplicate / reorder detection
-    let mut seen_seqs: VecDeque<u64> = VecDeque::with_capacity(SEEN_WINDOW + 1);
-    let mut seen_set: HashSet<u64> = HashSet::with_capacity(SEEN_WINDOW + 1);
-    let mut max_seen_seq: u64 = 0;
-    let mut first_packet = true;
+    let mut seen = SeenWindow::default();
 
     loop {
-        let (size, _) = match socket.recv_from(&mut buf).await {
+        let (size, received) = match socket.async_io(Interest::READABLE, || timestamping::recv(fd, &mut buf)).await {
             Ok(r) => r,
             Err(e) => {
                 eprintln!("Loopback recv error: {}", e);
@@ -50,67 +90,125 @@ pub async fn start_listener(
         };
 
         let payload = &buf[..size];
+        if payload.starts_with(mtu::PROBE_MARKER) {
+            continue;
+        }
+
+        // With a shared secret, only signed datagrams count; anyone can
+        // reach the forwarded port and guess a session ID.
+        if let Some(key) = &session.key {
+            let signed = size >= HEADER_SIZE as usize
+                && key.verify(&payload[..SIGNED_SIZE], &payload[SIGNED_SIZE..HEADER_SIZE as usize]);
+            if !signed {
+                session.rejected.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+        }
         let mut cursor = Cursor::new(payload);
 
         let counter = cursor.read_u64::<BigEndian>().unwrap_or_default();
         let timestamp = cursor.read_u128::<BigEndian>().unwrap_or_default();
         let recv_size = cursor.read_u32::<BigEndian>().unwrap_or(0);
         let recv_session = cursor.read_u32::<BigEndian>().unwrap_or(0);
+        let monotonic = cursor.read_u64::<BigEndian>().ok();
 
         // Discard packets from a previous process run. They carry a different
         // session_id and would corrupt reorder detection by inflating max_seen_seq.
-        if recv_session != session_id {
+        if recv_session != session.id {
             continue;
         }
 
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_micros();
-        let latency = (now as i128 - timestamp as i128).max(0) as u64;
-
-        // Duplicate detection
-        let is_duplicate = seen_set.contains(&counter);
-
-        // Reorder detection: a packet is reordered if it arrives after a higher-numbered
-        // packet has already been received (and it's not a duplicate)
-        let is_reordered = !is_duplicate && !first_packet && counter < max_seen_seq;
-
-        if !is_duplicate {
-            if first_packet || counter > max_seen_seq {
-                max_seen_seq = counter;
-                first_packet = false;
-            }
-            // Maintain bounded sliding window
-            seen_seqs.push_back(counter);
-            seen_set.insert(counter);
-            if seen_seqs.len() > SEEN_WINDOW {
-                if let Some(old) = seen_seqs.pop_front() {
-                    seen_set.remove(&old);
-                }
-            }
-        }
+        // The session matches, so the monotonic send time is from this run.
+        let user_latency = match monotonic {
+            Some(sent) => clock::monotonic_micros().saturating_sub(sent),
+            None => (clock::wall_micros() as i128 - timestamp as i128).max(0) as u64,
+        };
+        // Kernel timestamps leave out scheduling delay on both ends; what
+        // user space adds on top is reported as host-induced delay.
+        let kernel_latency = session.error_queue.lock().unwrap().get(counter).and_then(|sent| sent.elapsed(&received));
+        let latency = kernel_latency.unwrap_or(user_latency);
 
-        // Locate this packet in the history queue by age.
-        // Lock the queue FIRST, then read sent_counter. Because the sender now
-        // increments the counter and pushes to the queue under the same lock,
-        // we see a consistent snapshot: queue.len() corresponds to sent_counter.
-        let mut queue = history.lock().await;
-        let sent = sent_counter.load(Ordering::Relaxed);
-        let age = sent.saturating_sub(counter) as usize;
+        let (is_duplicate, is_reordered) = seen.observe(counter);
 
-        if let Some(index) = queue.len().checked_sub(age) {
-            if let Some(packet) = queue.get_mut(index) {
+        let slot = session.in_flight.lock().unwrap().get(&(recv_session, counter));
+        let mut late = false;
+        if let Some(slot) = slot {
+            let mut queue = history.lock().await;
+            let loss_timeout = queue.loss_timeout();
+            late = latency > loss_timeout;
+            queue.update(slot, |packet| {
                 if is_duplicate {
                     packet.duplicate = true;
                 } else {
-                    packet.latency = latency;
+                    packet.reply(latency, loss_timeout);
                     packet.reordered = is_reordered;
                     if recv_size > 0 {
                         packet.size = recv_size;
                     }
                 }
+            });
+            if let (Some(kernel), false) = (kernel_latency, is_duplicate || late) {
+                queue.record_host_delay(timestamp, user_latency.saturating_sub(kernel));
             }
         }
+
+        let event = if is_duplicate {
+            Event::Duplicated
+        } else if late {
+            Event::Late { latency, size: recv_size }
+        } else {
+            Event::Received { latency, size: recv_size, reordered: is_reordered }
+        };
+        events.publish(timestamp, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use crate::analysis::sla::SlaThresholds;
+    use crate::events::EventBus;
+    use crate::model::{Packet, PacketState};
+    use crate::network::auth::PayloadKey;
+    use crate::network::sender::build_payload;
+    use crate::outage::OutageThresholds;
+
+    #[tokio::test]
+    async fn drops_unsigned_datagrams_when_keyed() {
+        let port = std::net::UdpSocket::bind("0.0.0.0:0").unwrap().local_addr().unwrap().port();
+        let thresholds = OutageThresholds { min_losses: 5, min_silence_micros: 5_000_000, recovery_packets: 5 };
+        let history = PacketHistory::new(VecDeque::new(), 1_000_000, thresholds, SlaThresholds::default());
+        let history = Arc::new(Mutex::new(history));
+        let session = Arc::new(Session::new(0x5EC2E7, Some(PayloadKey::new("secret"))));
+        for counter in 0..3 {
+            let slot = history.lock().await.push(Packet::pending(clock::wall_micros(), 100), 100);
+            session.in_flight.lock().unwrap().insert((session.id, counter), slot);
+        }
+        let listener = tokio::spawn(start_listener(
+            port,
+            Arc::clone(&session),
+            Arc::clone(&history),
+            EventBus::new().publisher("loopback"),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let now = clock::monotonic_micros();
+        let unsigned = build_payload(0, clock::wall_micros(), 100, &Session::new(session.id, None), now);
+        let mut forged = build_payload(1, clock::wall_micros(), 100, &session, now);
+        forged[HEADER_SIZE as usize - 1] ^= 1;
+        let signed = build_payload(2, clock::wall_micros(), 100, &session, now);
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for payload in [unsigned, forged, signed] {
+            socket.send_to(&payload, ("127.0.0.1", port)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        listener.abort();
+
+        assert_eq!(session.rejected.load(Ordering::Relaxed), 2);
+        let states: Vec<PacketState> = history.lock().await.iter().map(|p| p.state).collect();
+        assert_eq!(states, [PacketState::Pending, PacketState::Pending, PacketState::Received]);
     }
 }
@@ -1,5 +1,13 @@
+pub mod auth;
+pub mod errqueue;
+pub mod inflight;
 pub mod ip;
 pub mod listener;
 pub mod mtu;
 pub mod pinger;
+pub mod random;
+pub mod schedule;
 pub mod sender;
+pub mod sizes;
+pub mod stamp;
+pub mod timestamping;
@@ -5,6 +5,9 @@ use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
 use tokio::time;
 
+use crate::events::{Event, Publisher};
+use crate::source::SourceControl;
+
 fn now_micros() -> u128 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -12,11 +15,28 @@ fn now_micros() -> u128 {
         .as_micros()
 }
 
-fn push_mtu(history: &mut VecDeque<(u128, u32)>, mtu: u32, max_queue_size: usize) {
+/// Wait for the next scheduled probe, or one requested through the control
+/// API. Scheduled probes are skipped while the source is paused.
+async fn next_probe(interval: &mut time::Interval, control: &SourceControl) {
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if !control.paused() {
+                    return;
+                }
+            }
+            _ = control.mtu_probe_requested() => return,
+        }
+    }
+}
+
+fn push_mtu(history: &mut VecDeque<(u128, u32)>, mtu: u32, max_queue_size: usize, events: &Publisher) {
     if history.len() >= max_queue_size {
         history.pop_front();
     }
-    history.push_back((now_micros(), mtu));
+    let now = now_micros();
+    history.push_back((now, mtu));
+    events.publish(now, Event::Mtu { mtu });
 }
 
 // ── UDP loopback MTU (via EMSGSIZE binary search) ─────────────────────────────
@@ -25,28 +45,34 @@ fn push_mtu(history: &mut VecDeque<(u128, u32)>, mtu: u32, max_queue_size: usize
 // kernel's PMTU cache knows the path MTU. We send a packet twice — the first
 // send triggers an ICMP "fragmentation needed" from the router if oversized,
 // and the kernel updates its cache; the second send then returns EMSGSIZE.
+//
+// Probes that fit travel the loopback path to our own listener, which drops
+// them by their marker instead of counting them as unsigned datagrams.
+
+/// First bytes of every UDP MTU probe.
+pub const PROBE_MARKER: &[u8; 8] = b"LBMTUPRB";
 
 pub async fn start_probing_udp(
-    bind_addr: String,
     address: String,
     min_mtu: u32,
-    max_mtu: u32,
     max_queue_size: usize,
     history: Arc<Mutex<VecDeque<(u128, u32)>>>,
+    control: Arc<SourceControl>,
+    events: Publisher,
 ) {
     let mut interval = time::interval(Duration::from_secs(60));
     loop {
-        interval.tick().await;
-        let bind = bind_addr.clone();
+        next_probe(&mut interval, &control).await;
         let addr = address.clone();
         let mtu =
-            tokio::task::spawn_blocking(move || probe_udp_blocking(&bind, &addr, min_mtu, max_mtu))
+            tokio::task::spawn_blocking(move || probe_udp_blocking("0.0.0.0:0", &addr, min_mtu))
                 .await
                 .unwrap_or(None);
         if let Some(mtu) = mtu {
             println!("UDP MTU probe: {} bytes", mtu);
+            control.set_path_mtu(mtu);
             let mut q = history.lock().await;
-            push_mtu(&mut q, mtu, max_queue_size);
+            push_mtu(&mut q, mtu, max_queue_size, &events);
         }
     }
 }
@@ -70,7 +96,7 @@ fn binary_search_mtu(min: u32, max: u32, mut probe: impl FnMut(u32) -> Option<bo
 
 /// Use 1472 and 1512 as checkpoints to narrow the binary search range.
 /// Only 9000 short-circuits; every other path ends in binary search.
-fn probe_mtu(min: u32, max: u32, mut probe: impl FnMut(u32) -> Option<bool>) -> Option<u32> {
+fn probe_mtu(min: u32, mut probe: impl FnMut(u32) -> Option<bool>) -> Option<u32> {
     if matches!(probe(9000), Some(true)) {
         return Some(9000);
     }
@@ -87,7 +113,7 @@ fn probe_mtu(min: u32, max: u32, mut probe: impl FnMut(u32) -> Option<bool>) ->
     binary_search_mtu(1516, 8996, &mut probe).or(Some(1512))
 }
 
-fn probe_udp_blocking(bind_addr: &str, address: &str, min: u32, max: u32) -> Option<u32> {
+fn probe_udp_blocking(bind_addr: &str, address: &str, min: u32) -> Option<u32> {
     use libc::{IP_MTU_DISCOVER, IP_PMTUDISC_DO};
     use std::os::unix::io::AsRawFd;
 
@@ -104,8 +130,10 @@ fn probe_udp_blocking(bind_addr: &str, address: &str, min: u32, max: u32) -> Opt
         );
     }
 
-    probe_mtu(min, max, |size| {
-        let payload = vec![0u8; size as usize];
+    probe_mtu(min, |size| {
+        let mut payload = vec![0u8; size as usize];
+        let marked = PROBE_MARKER.len().min(payload.len());
+        payload[..marked].copy_from_slice(&PROBE_MARKER[..marked]);
         let _ = socket.send_to(&payload, address);
         std::thread::sleep(Duration::from_millis(80));
         match socket.send_to(&payload, address) {
@@ -129,9 +157,10 @@ fn probe_udp_blocking(bind_addr: &str, address: &str, min: u32, max: u32) -> Opt
 pub async fn start_probing_icmp(
     target: String,
     min_mtu: u32,
-    max_mtu: u32,
     max_queue_size: usize,
     history: Arc<Mutex<VecDeque<(u128, u32)>>>,
+    control: Arc<SourceControl>,
+    events: Publisher,
 ) {
     let ip: Ipv4Addr = match target.parse::<IpAddr>() {
         Ok(IpAddr::V4(v4)) => v4,
@@ -149,15 +178,14 @@ pub async fn start_probing_icmp(
     let mut seq: u16 = 0;
 
     loop {
-        interval.tick().await;
+        next_probe(&mut interval, &control).await;
 
         let ip_copy = ip;
         let min = min_mtu;
-        let max = max_mtu;
         let seq_base = seq;
 
         let result = tokio::task::spawn_blocking(move || {
-            probe_icmp_blocking(ip_copy, min, max, seq_base)
+            probe_icmp_blocking(ip_copy, min, seq_base)
         })
         .await
         .unwrap_or(None);
@@ -168,14 +196,14 @@ pub async fn start_probing_icmp(
         if let Some(mtu) = result {
             println!("ICMP MTU probe {}: {} bytes", target, mtu);
             let mut q = history.lock().await;
-            push_mtu(&mut q, mtu, max_queue_size);
+            push_mtu(&mut q, mtu, max_queue_size, &events);
         }
     }
 }
 
 /// Probe sizes represent total IP packet size (IP header + ICMP header + data).
 /// ICMP data length = probe_size - 20 (IP hdr) - 8 (ICMP hdr), minimum 0.
-fn probe_icmp_blocking(ip: Ipv4Addr, min: u32, max: u32, seq_base: u16) -> Option<u32> {
+fn probe_icmp_blocking(ip: Ipv4Addr, min: u32, seq_base: u16) -> Option<u32> {
     use socket2::{Domain, Protocol, Socket, Type};
     use std::mem::MaybeUninit;
     use std::os::unix::io::AsRawFd;
@@ -203,7 +231,7 @@ fn probe_icmp_blocking(ip: Ipv4Addr, min: u32, max: u32, seq_base: u16) -> Optio
     let mut seq = seq_base;
     let mut buf = [MaybeUninit::uninit(); 2048];
 
-    probe_mtu(min, max, |size| {
+    probe_mtu(min, |size| {
         seq = seq.wrapping_add(1);
         let packet = build_icmp_echo(IDENT, seq, size);
         let _ = socket.send_to(&packet, &dest);
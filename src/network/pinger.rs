@@ -1,19 +1,23 @@
-use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError};
 use tokio::sync::Mutex;
-use tokio::time::{self, Duration};
+use tokio::time::Duration;
 
-use crate::model::{Packet, MAX_LATENCY_MICROS};
+use crate::events::{Event, Publisher};
+use crate::history::PacketHistory;
+use crate::model::Packet;
+use crate::network::schedule::Ticker;
+use crate::network::sizes::SizePicker;
+use crate::source::SourceControl;
 
 pub async fn start_pinging(
     target: String,
-    interval_millis: u64,
-    max_packet_size: u32,
     max_queue_size: usize,
-    history: Arc<Mutex<VecDeque<Packet>>>,
+    history: Arc<Mutex<PacketHistory>>,
+    control: Arc<SourceControl>,
+    events: Publisher,
 ) {
     let ip: IpAddr = match target.parse() {
         Ok(ip) => ip,
@@ -35,47 +39,56 @@ pub async fn start_pinging(
         }
     };
 
-    let mut pinger = client
-        .pinger(ip, PingIdentifier(std::process::id() as u16))
-        .await;
-    pinger.timeout(Duration::from_secs(1));
+    let ident = PingIdentifier(std::process::id() as u16);
+    // Replies after the loss timeout are dropped by the pinger, so ICMP
+    // packets are never late, only lost.
+    let loss_timeout = history.lock().await.loss_timeout();
 
-    println!("Pinging {} every {}ms", target, interval_millis);
+    println!(
+        "Pinging {} every {}ms ({} schedule)",
+        target,
+        control.interval_millis(),
+        control.schedule().name()
+    );
 
-    let payload = vec![0u8; max_packet_size as usize];
-    let mut interval = time::interval(Duration::from_millis(interval_millis));
+    let mut ticker = Ticker::new(&control);
+    let mut sizes = SizePicker::new(false);
     let mut seq: u16 = 0;
 
     loop {
-        interval.tick().await;
+        // Interval and payload size can change at runtime.
+        ticker.tick(&control).await;
+        if control.paused() {
+            continue;
+        }
+        let size = sizes.next(&control);
 
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_micros();
+        events.publish(timestamp, Event::Sent { size });
+        // Settling marks the packet lost unless its reply arrives in time.
+        let slot = history.lock().await.push(Packet::pending(timestamp, size), max_queue_size);
 
-        let latency = match pinger.ping(PingSequence(seq), &payload).await {
-            Ok((_packet, duration)) => duration.as_micros() as u64,
-            Err(SurgeError::Timeout { .. }) => MAX_LATENCY_MICROS,
-            Err(e) => {
-                eprintln!("Ping error to {}: {}", target, e);
-                MAX_LATENCY_MICROS
-            }
-        };
-
-        {
-            let mut queue = history.lock().await;
-            if queue.len() >= max_queue_size {
-                queue.pop_front();
-            }
-            queue.push_back(Packet {
-                timestamp,
-                latency,
-                size: max_packet_size,
-                reordered: false, // ICMP is sequential — reorder can't occur
-                duplicate: false,
-            });
-        }
+        // Each ping waits for its reply in its own task, so a slow or lost
+        // reply never delays the next send.
+        let mut pinger = client.pinger(ip, ident).await;
+        pinger.timeout(Duration::from_micros(loss_timeout));
+        let (history, events, target) = (history.clone(), events.clone(), target.clone());
+        tokio::spawn(async move {
+            let payload = vec![0; size as usize];
+            let latency = match pinger.ping(PingSequence(seq), &payload).await {
+                Ok((_packet, duration)) => duration.as_micros() as u64,
+                Err(SurgeError::Timeout { .. }) => return,
+                Err(e) => {
+                    eprintln!("Ping error to {}: {}", target, e);
+                    return;
+                }
+            };
+            events.publish(timestamp, Event::Received { latency, size, reordered: false });
+            history.lock().await.update(slot, |packet| packet.reply(latency, loss_timeout));
+        });
 
         seq = seq.wrapping_add(1);
     }
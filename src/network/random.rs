@@ -0,0 +1,36 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// xorshift64*, seeded from the OS through std's hasher keys. Plenty for
+/// picking send times and sizes; not for anything secret.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new() -> Self {
+        Rng(RandomState::new().build_hasher().finish() | 1)
+    }
+
+    /// A fixed sequence, for tests.
+    #[cfg(test)]
+    pub fn seeded(seed: u64) -> Self {
+        Rng(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1).
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,166 @@
+use tokio::time::{self, Duration, Instant};
+
+use crate::network::random::Rng;
+use crate::source::SourceControl;
+
+// ── Probe scheduling ──────────────────────────────────────────────────────────
+//
+// Probes at a fixed interval can phase-lock with periodic events on the path
+// (RFC 2330 §11.1.1). Poisson sampling, with exponentially distributed gaps,
+// cannot, and sees every state of the network in proportion to its time
+// share. Periodic sampling from a random start (RFC 3432) keeps the fixed
+// spacing but does not line up with other probers started alongside.
+//
+// The interval is the mean gap under every schedule, so the packet rate, and
+// any rate computed from the packet counters, is the same.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    Fixed,
+    Poisson,
+    RandomStart,
+}
+
+impl Schedule {
+    pub fn parse(s: &str) -> Option<Schedule> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fixed" => Some(Schedule::Fixed),
+            "poisson" => Some(Schedule::Poisson),
+            "random_start" | "periodic" => Some(Schedule::RandomStart),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Schedule::Fixed => "fixed",
+            Schedule::Poisson => "poisson",
+            Schedule::RandomStart => "random_start",
+        }
+    }
+}
+
+/// Send times of one prober, following its source's schedule and interval.
+pub struct Ticker {
+    schedule: Schedule,
+    interval_millis: u64,
+    next: Instant,
+    rng: Rng,
+}
+
+impl Ticker {
+    pub fn new(control: &SourceControl) -> Self {
+        Self::with_rng(control.schedule(), control.interval_millis(), Rng::new())
+    }
+
+    fn with_rng(schedule: Schedule, interval_millis: u64, rng: Rng) -> Self {
+        let mut ticker = Ticker { schedule, interval_millis, next: Instant::now(), rng };
+        let gap = ticker.first_gap(false);
+        ticker.next += gap;
+        ticker
+    }
+
+    fn period(&self) -> Duration {
+        Duration::from_millis(self.interval_millis)
+    }
+
+    fn exponential(&mut self) -> Duration {
+        self.period().mul_f64(-(1.0 - self.rng.uniform()).ln())
+    }
+
+    /// Time to the first send after starting or an interval change. A fixed
+    /// schedule starts at once, or one period after a change.
+    fn first_gap(&mut self, changed: bool) -> Duration {
+        match self.schedule {
+            Schedule::Fixed if changed => self.period(),
+            Schedule::Fixed => Duration::ZERO,
+            Schedule::Poisson => self.exponential(),
+            Schedule::RandomStart => self.period().mul_f64(self.rng.uniform()),
+        }
+    }
+
+    /// Wait for the next send time. Interval changes through the control API
+    /// take effect from the next send on.
+    pub async fn tick(&mut self, control: &SourceControl) {
+        if control.interval_millis() != self.interval_millis {
+            self.interval_millis = control.interval_millis();
+            self.next = Instant::now() + self.first_gap(true);
+        }
+        time::sleep_until(self.next).await;
+        self.advance(Instant::now());
+    }
+
+    fn gap(&mut self) -> Duration {
+        match self.schedule {
+            Schedule::Poisson => self.exponential(),
+            _ => self.period(),
+        }
+    }
+
+    /// Move to the first send time after `now`. Gaps run from the scheduled
+    /// time, not the wake-up, so that timer lateness neither stretches the
+    /// mean nor shifts the phase; sends a stalled prober missed are skipped
+    /// instead of sent in a burst.
+    fn advance(&mut self, now: Instant) {
+        loop {
+            let gap = self.gap();
+            self.next += gap;
+            if self.next > now {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: u64 = 100;
+
+    fn ticker(schedule: Schedule, seed: u64) -> Ticker {
+        Ticker::with_rng(schedule, INTERVAL, Rng::seeded(seed))
+    }
+
+    #[test]
+    fn poisson_gaps_average_the_interval() {
+        let mut ticker = ticker(Schedule::Poisson, 42);
+        let n = 20_000;
+        let total: Duration = (0..n).map(|_| ticker.gap()).sum();
+        let mean = total.as_secs_f64() * 1000.0 / n as f64;
+        assert!((mean - INTERVAL as f64).abs() < 3.0, "mean gap {mean} ms");
+    }
+
+    #[test]
+    fn random_start_offsets_the_first_send_within_one_period() {
+        let period = Duration::from_millis(INTERVAL);
+        let firsts: Vec<Duration> =
+            (1..=200).map(|seed| ticker(Schedule::RandomStart, seed).first_gap(false)).collect();
+        assert!(firsts.iter().all(|&gap| gap < period));
+        let mean = firsts.iter().sum::<Duration>().as_secs_f64() * 1000.0 / firsts.len() as f64;
+        assert!((mean - INTERVAL as f64 / 2.0).abs() < 10.0, "mean offset {mean} ms");
+
+        // After the offset, sends are a period apart.
+        let mut ticker = ticker(Schedule::RandomStart, 7);
+        let start = ticker.next;
+        ticker.advance(start);
+        assert_eq!(ticker.next - start, period);
+    }
+
+    #[test]
+    fn fixed_schedule_keeps_its_phase_and_skips_missed_sends() {
+        let period = Duration::from_millis(INTERVAL);
+        let mut ticker = ticker(Schedule::Fixed, 1);
+        assert_eq!(ticker.first_gap(false), Duration::ZERO);
+        assert_eq!(ticker.first_gap(true), period);
+        let start = ticker.next;
+
+        // Woken a little late: the next send stays on the grid.
+        ticker.advance(start + period / 4);
+        assert_eq!(ticker.next, start + period);
+
+        // Stalled for two and a half periods: skip to the next slot on the grid.
+        ticker.advance(start + period * 7 / 2);
+        assert_eq!(ticker.next, start + period * 4);
+    }
+}
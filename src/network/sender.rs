@@ -1,22 +1,61 @@
 use byteorder::{BigEndian, WriteBytesExt};
 use pnet::datalink;
-use std::collections::VecDeque;
 use std::io::Cursor;
 use std::net::IpAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
-use tokio::time::{self, Duration};
 
-use crate::model::Packet;
+use crate::clock;
+use crate::events::{Event, Publisher};
+use crate::history::PacketHistory;
+use crate::model::{IcmpKind, Packet, PacketState};
+use crate::network::auth::{PayloadKey, TAG_SIZE};
+use crate::network::inflight::{InFlight, IN_FLIGHT_WINDOW};
+use crate::network::schedule::Ticker;
+use crate::network::sizes::SizePicker;
+use crate::network::errqueue::{self, ErrorQueue};
+use crate::network::timestamping;
+use crate::source::SourceControl;
+
+/// State the loopback sender shares with the listener.
+pub struct Session {
+    /// Random-ish per run, so replies to a previous run's packets are ignored.
+    pub id: u32,
+    /// Packets sent so far; the next packet's counter.
+    pub sent: AtomicU64,
+    /// History slots of sent packets by (session ID, counter).
+    pub in_flight: StdMutex<InFlight<(u32, u64)>>,
+    /// Kernel transmit times and ICMP errors by counter, from the sender
+    /// socket's error queue.
+    pub error_queue: StdMutex<ErrorQueue>,
+    /// Signs payload headers when `PROBE_SECRET` is set.
+    pub key: Option<PayloadKey>,
+    /// Datagrams the listener dropped for a missing or wrong signature.
+    pub rejected: AtomicU64,
+}
+
+impl Session {
+    pub fn new(id: u32, key: Option<PayloadKey>) -> Self {
+        Session {
+            id,
+            key,
+            rejected: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            in_flight: StdMutex::new(InFlight::new(IN_FLIGHT_WINDOW)),
+            error_queue: Default::default(),
+        }
+    }
+}
 
 pub async fn start_sending(
     config: &crate::config::Config,
     public_ip: String,
-    session_id: u32,
-    sent_counter: Arc<AtomicU64>,
-    history: Arc<Mutex<VecDeque<Packet>>>,
+    session: Arc<Session>,
+    history: Arc<Mutex<PacketHistory>>,
+    control: Arc<SourceControl>,
+    events: Publisher,
 ) {
     // When ALTERNATIVE_INTERFACE is set, verify the VPN is up (port forwarding requires it),
     // but always egress via the default route (eth0 → internet → VPN server NAT-PMP →
@@ -83,51 +122,116 @@ pub async fn start_sending(
             );
         }
     }
+    let timestamps = timestamping::enable_transmit(fd);
+    if !timestamps {
+        eprintln!(
+            "Kernel transmit timestamps unavailable ({}); RTTs use user-space send times",
+            std::io::Error::last_os_error()
+        );
+    }
+    let icmp_errors = errqueue::enable_icmp_errors(fd);
+    if !icmp_errors {
+        eprintln!("Failed to set IP_RECVERR: {}", std::io::Error::last_os_error());
+    }
+    if timestamps || icmp_errors {
+        session.error_queue.lock().unwrap().attach(fd, timestamps);
+    }
 
     let address = format!("{}:{}", public_ip, config.target_port);
-    let size = config.max_packet_size as u32;
-    let mut interval = time::interval(Duration::from_millis(config.interval_millis));
+    let mut ticker = Ticker::new(&control);
+    let mut sizes = SizePicker::new(config.clamp_to_mtu);
 
     loop {
-        interval.tick().await;
+        // Interval and packet size can change at runtime.
+        ticker.tick(&control).await;
+        // Also clears the socket error an ICMP error leaves, which would
+        // otherwise fail the next send.
+        record_icmp_errors(&session, &history, &control, &events).await;
+        if control.paused() {
+            continue;
+        }
+        let size = sizes.next(&control).max(HEADER_SIZE);
 
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_micros();
 
-        // Increment counter and push to history atomically under the same lock,
-        // so the listener can rely on: queue.len() == sent_counter at all times.
-        let counter;
-        {
-            let mut queue = history.lock().await;
-            if queue.len() >= config.max_queue_size {
-                queue.pop_front();
+        // Registered before sending, so the reply always finds its slot.
+        let counter = session.sent.fetch_add(1, Ordering::Relaxed);
+        let slot = history.lock().await.push(Packet::pending(timestamp, size), config.max_queue_size);
+        session.in_flight.lock().unwrap().insert((session.id, counter), slot);
+
+        let payload = build_payload(counter, timestamp, size, &session, clock::monotonic_micros());
+        match socket.send_to(&payload, &address) {
+            Ok(_) => {
+                session.error_queue.lock().unwrap().sent(counter);
+                events.publish(timestamp, Event::Sent { size });
+            }
+            Err(e) => {
+                // The packet never left: a local failure, not network loss.
+                eprintln!("Failed to send packet: {}", e);
+                session.error_queue.lock().unwrap().send_failed(counter, &e);
+                history.lock().await.update(slot, |p| p.state = PacketState::Failed);
+                events.publish(timestamp, Event::Failed { size, error: &e.to_string() });
+                if e.raw_os_error() == Some(libc::EMSGSIZE) {
+                    // The path MTU shrank; re-probe rather than wait for the next interval.
+                    control.request_mtu_probe();
+                }
             }
-            counter = sent_counter.fetch_add(1, Ordering::Relaxed);
-            queue.push_back(Packet::pending(timestamp, size));
         }
+    }
+}
 
-        let payload = build_payload(counter, timestamp, size, session_id);
-        socket.send_to(&payload, &address).unwrap_or_else(|e| {
-            eprintln!("Failed to send packet: {}", e);
-            0
-        });
+/// Attach the ICMP errors routers returned since the last call to the
+/// packets that caused them.
+async fn record_icmp_errors(
+    session: &Session,
+    history: &Mutex<PacketHistory>,
+    control: &SourceControl,
+    events: &Publisher,
+) {
+    let errors = session.error_queue.lock().unwrap().take_icmp_errors();
+    if errors.is_empty() {
+        return;
+    }
+    let mut queue = history.lock().await;
+    for (counter, error) in errors {
+        let Some(slot) = session.in_flight.lock().unwrap().get(&(session.id, counter)) else { continue };
+        if let Some(timestamp) = queue.record_icmp_error(slot, error) {
+            events.publish(timestamp, Event::Icmp(&error));
+        }
+        if error.kind == IcmpKind::FragNeeded {
+            // The path MTU shrank; re-probe rather than wait for the next interval.
+            control.request_mtu_probe();
+        }
     }
 }
 
-/// Payload layout (32 bytes header + padding):
+/// Bytes signed by the HMAC tag.
+pub const SIGNED_SIZE: usize = 40;
+/// Bytes before the padding; smaller packets are sent at this size.
+pub const HEADER_SIZE: u32 = (SIGNED_SIZE + TAG_SIZE) as u32;
+
+/// Payload layout (56 bytes header + padding):
 ///   [0..8]   counter    u64 big-endian
-///   [8..24]  timestamp  u128 big-endian
+///   [8..24]  timestamp  u128 big-endian (wall clock, dates the packet)
 ///   [24..28] size       u32 big-endian
 ///   [28..32] session_id u32 big-endian  ← prevents stale packets from a prior run
-///   [32..]   zero padding
-fn build_payload(counter: u64, timestamp: u128, size: u32, session_id: u32) -> Vec<u8> {
-    let mut payload = vec![0u8; size as usize];
+///   [32..40] monotonic  u64 big-endian (send time for latency, immune to clock steps)
+///   [40..56] HMAC-SHA256 of [0..40], truncated; zero without a secret
+///   [56..]   zero padding
+pub(crate) fn build_payload(counter: u64, timestamp: u128, size: u32, session: &Session, monotonic: u64) -> Vec<u8> {
+    let mut payload = vec![0u8; size.max(HEADER_SIZE) as usize];
     let mut cursor = Cursor::new(&mut payload);
     cursor.write_u64::<BigEndian>(counter).unwrap();
     cursor.write_u128::<BigEndian>(timestamp).unwrap();
     cursor.write_u32::<BigEndian>(size).unwrap();
-    cursor.write_u32::<BigEndian>(session_id).unwrap();
+    cursor.write_u32::<BigEndian>(session.id).unwrap();
+    cursor.write_u64::<BigEndian>(monotonic).unwrap();
+    if let Some(key) = &session.key {
+        let tag = key.sign(&payload[..SIGNED_SIZE]);
+        payload[SIGNED_SIZE..HEADER_SIZE as usize].copy_from_slice(&tag);
+    }
     payload
 }
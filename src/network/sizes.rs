@@ -0,0 +1,149 @@
+use crate::network::random::Rng;
+use crate::source::SourceControl;
+
+// ── Probe sizes ───────────────────────────────────────────────────────────────
+//
+// Probes of one size hide size-dependent loss: fragmentation trouble or a
+// tunnel whose overhead was not subtracted from the MTU only hit large
+// packets. Sweeping or drawing sizes between MIN_PACKET_SIZE and the packet
+// size, with loss and RTT broken down by size bucket, shows where it starts.
+
+/// Distinct sizes one sweep visits, evenly spaced from min to max.
+const SWEEP_STEPS: u32 = 32;
+/// Equal-width size buckets for the loss and RTT breakdown.
+pub const SIZE_BUCKETS: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeMode {
+    /// Every probe at the packet size.
+    Fixed,
+    /// Cycle from the minimum up to the packet size.
+    Sweep,
+    /// Uniformly random between the minimum and the packet size.
+    Random,
+}
+
+impl SizeMode {
+    pub fn parse(s: &str) -> Option<SizeMode> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "fixed" => Some(SizeMode::Fixed),
+            "sweep" => Some(SizeMode::Sweep),
+            "random" => Some(SizeMode::Random),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SizeMode::Fixed => "fixed",
+            SizeMode::Sweep => "sweep",
+            SizeMode::Random => "random",
+        }
+    }
+}
+
+/// Sizes of one prober's packets, following its source's size mode.
+pub struct SizePicker {
+    step: u32,
+    rng: Rng,
+    /// No larger than the latest MTU probe result, once there is one.
+    clamp_to_mtu: bool,
+}
+
+impl SizePicker {
+    pub fn new(clamp_to_mtu: bool) -> Self {
+        SizePicker { step: 0, rng: Rng::new(), clamp_to_mtu }
+    }
+
+    pub fn next(&mut self, control: &SourceControl) -> u32 {
+        let size = self.pick(control);
+        match control.path_mtu() {
+            Some(mtu) if self.clamp_to_mtu => size.min(mtu),
+            _ => size,
+        }
+    }
+
+    fn pick(&mut self, control: &SourceControl) -> u32 {
+        let (min, max) = control.size_range();
+        match control.size_mode() {
+            SizeMode::Fixed => max,
+            SizeMode::Sweep => {
+                let size = min + ((max - min) as u64 * self.step as u64 / (SWEEP_STEPS - 1) as u64) as u32;
+                self.step = (self.step + 1) % SWEEP_STEPS;
+                size
+            }
+            SizeMode::Random => min + (self.rng.next_u64() % (max - min + 1) as u64) as u32,
+        }
+    }
+}
+
+/// Inclusive upper bounds of the size buckets over [min, max]; the last is max.
+pub fn bucket_bounds(min: u32, max: u32) -> Vec<u32> {
+    let width = (max - min + 1).div_ceil(SIZE_BUCKETS).max(1);
+    let mut bounds: Vec<u32> = (1..=SIZE_BUCKETS).map(|i| (min + i * width - 1).min(max)).collect();
+    bounds.dedup();
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::schedule::Schedule;
+
+    fn control(mode: SizeMode) -> SourceControl {
+        SourceControl::new(1000, 1400, Schedule::Fixed, 100, mode)
+    }
+
+    fn picker(clamp_to_mtu: bool) -> SizePicker {
+        SizePicker { step: 0, rng: Rng::seeded(42), clamp_to_mtu }
+    }
+
+    #[test]
+    fn sizes_stay_within_the_range() {
+        let random = control(SizeMode::Random);
+        let mut sizes = picker(false);
+        let drawn: Vec<u32> = (0..1000).map(|_| sizes.next(&random)).collect();
+        assert!(drawn.iter().all(|size| (100..=1400).contains(size)));
+        assert!(drawn.iter().any(|&size| size < 110) && drawn.iter().any(|&size| size > 1390));
+
+        let sweep = control(SizeMode::Sweep);
+        let mut sizes = picker(false);
+        let swept: Vec<u32> = (0..SWEEP_STEPS).map(|_| sizes.next(&sweep)).collect();
+        assert_eq!((swept[0], swept[SWEEP_STEPS as usize - 1]), (100, 1400));
+        assert!(swept.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sizes.next(&sweep), 100);
+
+        // A packet size below the minimum narrows the range to that size.
+        sweep.set_packet_size(64);
+        assert!((0..SWEEP_STEPS).all(|_| sizes.next(&sweep) == 64));
+    }
+
+    #[test]
+    fn sizes_are_clamped_to_the_path_mtu_only_when_asked() {
+        let fixed = control(SizeMode::Fixed);
+        fixed.set_path_mtu(1200);
+        assert_eq!(picker(false).next(&fixed), 1400);
+        assert_eq!(picker(true).next(&fixed), 1200);
+
+        // Without a probe result yet, nothing is clamped.
+        let sweep = control(SizeMode::Sweep);
+        let mut sizes = picker(true);
+        assert_eq!(sizes.next(&sweep), 100);
+        assert_eq!(sizes.next(&sweep), 141);
+        sweep.set_path_mtu(500);
+        assert!((2..SWEEP_STEPS).all(|_| sizes.next(&sweep) <= 500));
+    }
+
+    #[test]
+    fn buckets_cover_the_range() {
+        for (min, max) in [(100, 1400), (100, 107), (64, 64), (28, 9000)] {
+            let bounds = bucket_bounds(min, max);
+            assert!(!bounds.is_empty() && bounds.len() <= SIZE_BUCKETS as usize);
+            assert_eq!(*bounds.last().unwrap(), max);
+            assert!(bounds[0] >= min);
+            assert!(bounds.windows(2).all(|w| w[0] < w[1]));
+        }
+        assert_eq!(bucket_bounds(100, 107), [100, 101, 102, 103, 104, 105, 106, 107]);
+        assert_eq!(bucket_bounds(64, 64), [64]);
+    }
+}
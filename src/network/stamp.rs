@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+
+use crate::clock;
+use crate::events::{Event, Publisher};
+use crate::history::PacketHistory;
+use crate::model::{Packet, PacketState};
+use crate::network::inflight::{InFlight, IN_FLIGHT_WINDOW};
+use crate::network::listener::SeenWindow;
+use crate::network::schedule::Ticker;
+use crate::source::SourceControl;
+
+// ── STAMP (RFC 8762), unauthenticated mode ────────────────────────────────────
+//
+// The session-sender puts its transmit time t1 in each test packet. The
+// session-reflector answers with its receive time t2, its transmit time t3 and
+// the sender's fields echoed back, and the reply arrives at t4. Forward delay
+// is t2 - t1, reverse delay t4 - t3 and the round trip (t4 - t1) - (t3 - t2),
+// which leaves out the time spent in the reflector. One-way delays are only
+// meaningful when both clocks are synchronised.
+//
+// Packet layouts (big-endian, 44 bytes before zero padding):
+//   sender:    [0..4] seq  [4..12] timestamp  [12..14] error estimate
+//              [14..16] SSID (RFC 8972)  [16..44] MBZ
+//   reflector: [0..4] seq  [4..12] timestamp (t3)  [12..14] error estimate
+//              [14..16] SSID  [16..24] receive timestamp (t2)
+//              [24..28] sender seq  [28..36] sender timestamp (t1)
+//              [36..38] sender error estimate  [38..40] MBZ
+//              [40] sender TTL  [41..44] MBZ
+
+pub const DEFAULT_PORT: u16 = 862;
+const BASE_PACKET_SIZE: usize = 44;
+/// NTP timestamp format, clock not known to be synchronised, error about
+/// 1 ms (multiplier 1, scale 22: 2^22 * 2^-32 s).
+const ERROR_ESTIMATE: u16 = 0x1601;
+const NTP_UNIX_OFFSET_SECS: u64 = 2_208_988_800;
+/// Per-sender reflector state is dropped beyond this many sessions.
+const MAX_REFLECTOR_SESSIONS: usize = 1024;
+
+fn now_micros() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros()
+}
+
+fn to_ntp(micros: u128) -> u64 {
+    let secs = (micros / 1_000_000) as u64 + NTP_UNIX_OFFSET_SECS;
+    let fraction = (((micros % 1_000_000) as u64) << 32) / 1_000_000;
+    secs << 32 | fraction
+}
+
+fn from_ntp(ntp: u64) -> u128 {
+    let secs = (ntp >> 32).saturating_sub(NTP_UNIX_OFFSET_SECS) as u128;
+    let micros = ((ntp & 0xFFFF_FFFF) * 1_000_000) >> 32;
+    secs * 1_000_000 + micros as u128
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+fn build_test_packet(seq: u32, timestamp: u128, ssid: u16, size: usize) -> Vec<u8> {
+    let mut packet = vec![0u8; size.max(BASE_PACKET_SIZE)];
+    packet[0..4].copy_from_slice(&seq.to_be_bytes());
+    packet[4..12].copy_from_slice(&to_ntp(timestamp).to_be_bytes());
+    packet[12..14].copy_from_slice(&ERROR_ESTIMATE.to_be_bytes());
+    packet[14..16].copy_from_slice(&ssid.to_be_bytes());
+    packet
+}
+
+/// The reflected packet is as long as the test packet, as RFC 8762 asks.
+fn build_reflection(request: &[u8], seq: u32, received: u128, sent: u128) -> Vec<u8> {
+    let mut packet = vec![0u8; request.len()];
+    packet[0..4].copy_from_slice(&seq.to_be_bytes());
+    packet[4..12].copy_from_slice(&to_ntp(sent).to_be_bytes());
+    packet[12..14].copy_from_slice(&ERROR_ESTIMATE.to_be_bytes());
+    packet[14..16].copy_from_slice(&request[14..16]);
+    packet[16..24].copy_from_slice(&to_ntp(received).to_be_bytes());
+    packet[24..38].copy_from_slice(&request[0..14]);
+    // The sender TTL is not read from the IP header and stays zero.
+    packet
+}
+
+// ── Session-reflector ─────────────────────────────────────────────────────────
+
+/// Reflect test packets from any sender. The reflector is stateful: it numbers
+/// its replies per sender and SSID, so senders can tell forward loss from
+/// reverse loss.
+pub async fn start_reflector(addr: String) {
+    let socket = match UdpSocket::bind(&addr).await {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Cannot bind STAMP reflector on {}: {}", addr, e);
+            return;
+        }
+    };
+    println!("STAMP reflector listening on {}", addr);
+    reflect(socket).await;
+}
+
+async fn reflect(socket: UdpSocket) {
+    let mut buf = [0u8; 9216];
+    let mut sessions: HashMap<(SocketAddr, u16), u32> = HashMap::new();
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("STAMP reflector recv error: {}", e);
+                continue;
+            }
+        };
+        let received = now_micros();
+        if len < BASE_PACKET_SIZE {
+            continue;
+        }
+        let request = &buf[..len];
+        let key = (peer, u16::from_be_bytes([request[14], request[15]]));
+        if sessions.len() >= MAX_REFLECTOR_SESSIONS && !sessions.contains_key(&key) {
+            sessions.clear();
+        }
+        let seq = sessions.entry(key).or_insert(0);
+        let reply = build_reflection(request, *seq, received, now_micros());
+        *seq = seq.wrapping_add(1);
+        if let Err(e) = socket.send_to(&reply, peer).await {
+            eprintln!("STAMP reflector: failed to reply to {}: {}", peer, e);
+        }
+    }
+}
+
+// ── Session-sender ────────────────────────────────────────────────────────────
+
+/// The histories one session feeds.
+pub struct SessionHistories {
+    /// Every test packet, like a ping target.
+    pub round_trip: Arc<Mutex<PacketHistory>>,
+    /// Test packets as the reflector saw them, with the forward delay.
+    pub forward: Arc<Mutex<PacketHistory>>,
+    /// Replies the reflector sent, with the reverse delay.
+    pub reverse: Arc<Mutex<PacketHistory>>,
+}
+
+/// Splits unanswered test packets into forward and reverse losses.
+///
+/// `sender seq - reflector seq` counts the test packets that never reached a
+/// stateful reflector, so its growth between two replies is the number of
+/// forward losses among the packets in between; the rest were replies lost on
+/// the way back. With a stateless reflector, which echoes the sender's
+/// sequence number, every loss counts as reverse loss.
+///
+/// Directional histories are filled as replies arrive, so losses show up
+/// there only once a later reply proves them. Test packets whose reply was
+/// lost are left out of the forward history: the reflector received them,
+/// but their forward delay is unknown.
+struct Directions {
+    /// Sender seq, reflector seq and send time of the last in-order reply.
+    last: Option<(u32, u32, u128)>,
+}
+
+/// One reply, times in microseconds since the epoch on their own clocks.
+struct Reply {
+    sender_seq: u32,
+    reflector_seq: u32,
+    sent: u128,
+    reflected: u128,
+    transmitted: u128,
+    arrived: u128,
+    size: u32,
+}
+
+impl Reply {
+    fn parse(buf: &[u8], ssid: u16, arrived: u128) -> Option<Reply> {
+        if buf.len() < BASE_PACKET_SIZE || buf[14..16] != ssid.to_be_bytes() {
+            return None;
+        }
+        Some(Reply {
+            sender_seq: u32_at(buf, 24),
+            reflector_seq: u32_at(buf, 0),
+            sent: from_ntp(u64_at(buf, 28)),
+            reflected: from_ntp(u64_at(buf, 16)),
+            transmitted: from_ntp(u64_at(buf, 4)),
+            arrived,
+            size: buf.len() as u32,
+        })
+    }
+
+    /// `elapsed` is t4 - t1 on the monotonic clock, when the packet is
+    /// still in flight, so that wall-clock steps do not distort it.
+    fn round_trip(&self, elapsed: Option<u64>) -> u64 {
+        let total = elapsed.unwrap_or(self.arrived.saturating_sub(self.sent) as u64);
+        total.saturating_sub(self.transmitted.saturating_sub(self.reflected) as u64)
+    }
+
+    /// One-way delays are off by the clock offset, so however large they
+    /// are, the packet counts as received.
+    fn one_way(&self, from: u128, to: u128) -> Packet {
+        let latency = to.saturating_sub(from) as u64;
+        Packet { latency, state: PacketState::Received, ..Packet::pending(self.sent, self.size) }
+    }
+}
+
+impl Directions {
+    async fn record(&mut self, reply: &Reply, histories: &SessionHistories, max_queue_size: usize) {
+        let (last_seq, last_reflector_seq, last_sent) =
+            self.last.unwrap_or((u32::MAX, u32::MAX, reply.sent));
+        let step = reply.sender_seq.wrapping_sub(last_seq);
+        if step == 0 || step > u32::MAX / 2 {
+            return; // out of order: already counted as lost
+        }
+        self.last = Some((reply.sender_seq, reply.reflector_seq, reply.sent));
+
+        let missing = (step - 1) as usize;
+        let lag = reply.sender_seq.wrapping_sub(reply.reflector_seq);
+        let last_lag = last_seq.wrapping_sub(last_reflector_seq);
+        let forward_lost = (lag.wrapping_sub(last_lag) as i32).clamp(0, missing as i32) as usize;
+        // Send times of the missing packets are interpolated between the two replies.
+        let sent_at = |i: usize| {
+            last_sent + reply.sent.saturating_sub(last_sent) * (i as u128 + 1) / step as u128
+        };
+        let skip = missing.saturating_sub(max_queue_size);
+        let lost = |timestamp| Packet { state: PacketState::Lost, ..Packet::pending(timestamp, reply.size) };
+
+        {
+            let mut forward = histories.forward.lock().await;
+            for i in skip..forward_lost {
+                forward.push(lost(sent_at(i)), max_queue_size);
+            }
+            forward.push(reply.one_way(reply.sent, reply.reflected), max_queue_size);
+        }
+        {
+            let mut reverse = histories.reverse.lock().await;
+            for i in skip.max(forward_lost)..missing {
+                reverse.push(lost(sent_at(i)), max_queue_size);
+            }
+            reverse.push(reply.one_way(reply.transmitted, reply.arrived), max_queue_size);
+        }
+    }
+}
+
+/// Send test packets to `reflector` and record the replies.
+pub async fn start_session(
+    reflector: SocketAddr,
+    ssid: u16,
+    max_queue_size: usize,
+    histories: SessionHistories,
+    control: Arc<SourceControl>,
+    events: Publisher,
+) {
+    let bind_addr = if reflector.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = match UdpSocket::bind(bind_addr).await {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Cannot bind STAMP sender socket for {}: {}", reflector, e);
+            return;
+        }
+    };
+    if let Err(e) = socket.connect(reflector).await {
+        eprintln!("STAMP session with {} disabled: {}", reflector, e);
+        return;
+    }
+    println!("STAMP session with {} (SSID 0x{:04X})", reflector, ssid);
+
+    let mut ticker = Ticker::new(&control);
+    let mut sent: u32 = 0;
+    let mut buf = [0u8; 9216];
+    let mut seen = SeenWindow::default();
+    let mut in_flight = InFlight::new(IN_FLIGHT_WINDOW);
+    let mut directions = Directions { last: None };
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(&control) => {
+                // Interval and packet size can change at runtime.
+                if control.paused() {
+                    continue;
+                }
+                let size = (control.packet_size() as usize).max(BASE_PACKET_SIZE);
+                let timestamp = now_micros();
+                let seq = sent;
+                sent = sent.wrapping_add(1);
+                let packet = Packet::pending(timestamp, size as u32);
+                let slot = histories.round_trip.lock().await.push(packet, max_queue_size);
+                in_flight.insert(seq, (slot, clock::monotonic_micros()));
+                events.publish(timestamp, Event::Sent { size: size as u32 });
+                if let Err(e) = socket.send(&build_test_packet(seq, timestamp, ssid, size)).await {
+                    eprintln!("STAMP: failed to send to {}: {}", reflector, e);
+                }
+            }
+            result = socket.recv(&mut buf) => {
+                let len = match result {
+                    Ok(len) => len,
+                    // ICMP port unreachable surfaces here when nothing listens.
+                    Err(_) => continue,
+                };
+                let Some(reply) = Reply::parse(&buf[..len], ssid, now_micros()) else { continue };
+                let (is_duplicate, is_reordered) = seen.observe(reply.sender_seq as u64);
+                let entry = in_flight.get(&reply.sender_seq);
+                let latency = reply.round_trip(entry.map(|(_, at)| clock::monotonic_micros().saturating_sub(at)));
+                let mut late = false;
+                if let Some((slot, _)) = entry {
+                    let mut queue = histories.round_trip.lock().await;
+                    let loss_timeout = queue.loss_timeout();
+                    late = latency > loss_timeout;
+                    queue.update(slot, |packet| {
+                        if is_duplicate {
+                            packet.duplicate = true;
+                        } else {
+                            packet.reply(latency, loss_timeout);
+                            packet.reordered = is_reordered;
+                        }
+                    });
+                }
+                let size = reply.size;
+                if is_duplicate {
+                    events.publish(reply.sent, Event::Duplicated);
+                    continue;
+                } else if late {
+                    events.publish(reply.sent, Event::Late { latency, size });
+                } else {
+                    events.publish(reply.sent, Event::Received { latency, size, reordered: is_reordered });
+                }
+                directions.record(&reply, &histories, max_queue_size).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::time::Duration;
+
+    use crate::analysis::sla::SlaThresholds;
+    use crate::events::EventBus;
+    use crate::network::schedule::Schedule;
+    use crate::network::sizes::SizeMode;
+    use crate::outage::OutageThresholds;
+
+    async fn reflector() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(reflect(socket));
+        addr
+    }
+
+    fn history() -> Arc<Mutex<PacketHistory>> {
+        let thresholds = OutageThresholds { min_losses: 5, min_silence_micros: 5_000_000, recovery_packets: 5 };
+        let history = PacketHistory::new(VecDeque::new(), 1_000_000, thresholds, SlaThresholds::default());
+        Arc::new(Mutex::new(history))
+    }
+
+    #[tokio::test]
+    async fn reflector_numbers_replies_and_echoes_the_sender() {
+        let addr = reflector().await;
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(addr).await.unwrap();
+
+        let mut buf = [0u8; 1500];
+        for (i, sender_seq) in [7u32, 8, 10].into_iter().enumerate() {
+            let t1 = now_micros();
+            socket.send(&build_test_packet(sender_seq, t1, 0xBEEF, 100)).await.unwrap();
+            let len = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf)).await.unwrap().unwrap();
+            let t4 = now_micros();
+            let packet = &buf[..len];
+            assert_eq!(len, 100);
+
+            let reply = Reply::parse(packet, 0xBEEF, t4).unwrap();
+            assert_eq!(reply.reflector_seq, i as u32);
+            assert_eq!(reply.sender_seq, sender_seq);
+            // NTP timestamps keep about a microsecond of precision.
+            assert!(reply.sent.abs_diff(t1) <= 1);
+            assert!(reply.sent <= reply.reflected + 1);
+            assert!(reply.reflected <= reply.transmitted);
+            assert!(reply.transmitted <= t4);
+            assert_eq!(packet[12..14], ERROR_ESTIMATE.to_be_bytes());
+            assert_eq!(packet[36..38], ERROR_ESTIMATE.to_be_bytes());
+        }
+        // Another SSID is another session, numbered from zero.
+        socket.send(&build_test_packet(0, now_micros(), 0x0001, 44)).await.unwrap();
+        let len = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(Reply::parse(&buf[..len], 0x0001, now_micros()).unwrap().reflector_seq, 0);
+    }
+
+    #[tokio::test]
+    async fn session_records_replies_in_every_direction() {
+        let addr = reflector().await;
+        let histories = SessionHistories { round_trip: history(), forward: history(), reverse: history() };
+        let (round_trip, forward, reverse) =
+            (Arc::clone(&histories.round_trip), Arc::clone(&histories.forward), Arc::clone(&histories.reverse));
+        let control = Arc::new(SourceControl::new(10, 64, Schedule::Fixed, 64, SizeMode::Fixed));
+        let session = tokio::spawn(start_session(
+            addr,
+            0x1234,
+            1000,
+            histories,
+            control,
+            EventBus::new().publisher("stamp"),
+        ));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        session.abort();
+
+        let round_trip = round_trip.lock().await;
+        let received = round_trip.iter().filter(|p| p.is_received()).count();
+        assert!(received >= 10, "{} of {} packets answered", received, round_trip.len());
+        assert!(round_trip.iter().all(|p| p.is_received() || p.is_pending()));
+        assert!(round_trip.iter().all(|p| p.size == 64 && !p.duplicate && !p.reordered));
+        for history in [forward.lock().await, reverse.lock().await] {
+            assert_eq!(history.len(), received);
+            assert!(history.iter().all(|p| p.is_received()));
+        }
+    }
+}
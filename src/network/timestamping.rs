@@ -0,0 +1,157 @@
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::RawFd;
+
+use crate::model::IcmpError;
+use crate::network::errqueue;
+
+// ── Kernel packet timestamps (SO_TIMESTAMPING) ────────────────────────────────
+//
+// User-space timestamps include however long tokio took to poll the socket.
+// The kernel can stamp packets as they leave for the driver and as they come
+// in; NICs that timestamp in hardware (with hardware timestamping enabled on
+// the interface, e.g. by hwstamp_ctl or ptp4l) stamp them on the wire. Transmit
+// times come back through the socket's error queue, numbered in send order
+// (SOF_TIMESTAMPING_OPT_ID). Receive times ride along with each datagram.
+//
+// Without SO_TIMESTAMPING, SO_TIMESTAMPNS still gives software receive times.
+// The error queue is drained in `errqueue`, along with ICMP errors.
+
+const TX_FLAGS: libc::c_uint = libc::SOF_TIMESTAMPING_TX_SOFTWARE
+    | libc::SOF_TIMESTAMPING_TX_HARDWARE
+    | libc::SOF_TIMESTAMPING_OPT_ID
+    | libc::SOF_TIMESTAMPING_OPT_TSONLY;
+const RX_FLAGS: libc::c_uint = libc::SOF_TIMESTAMPING_RX_SOFTWARE | libc::SOF_TIMESTAMPING_RX_HARDWARE;
+const REPORT_FLAGS: libc::c_uint = libc::SOF_TIMESTAMPING_SOFTWARE | libc::SOF_TIMESTAMPING_RAW_HARDWARE;
+
+/// Kernel timestamps of one packet, microseconds. Software ones are on the
+/// system clock, hardware ones on the NIC's clock, so only differences on
+/// the same clock mean anything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stamp {
+    pub software: Option<u128>,
+    pub hardware: Option<u128>,
+}
+
+impl Stamp {
+    /// Time from `self` to `later`, on hardware clocks when both have one.
+    pub fn elapsed(&self, later: &Stamp) -> Option<u64> {
+        let diff = |from: Option<u128>, to: Option<u128>| Some(to?.checked_sub(from?)? as u64);
+        diff(self.hardware, later.hardware).or_else(|| diff(self.software, later.software))
+    }
+
+    pub(crate) fn merge(&mut self, other: Stamp) {
+        self.software = other.software.or(self.software);
+        self.hardware = other.hardware.or(self.hardware);
+    }
+}
+
+fn set_option(fd: RawFd, name: libc::c_int, value: libc::c_uint) -> bool {
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            name,
+            &value as *const _ as *const libc::c_void,
+            mem::size_of_val(&value) as libc::socklen_t,
+        )
+    };
+    result == 0
+}
+
+/// Ask for transmit timestamps on a sending socket.
+pub fn enable_transmit(fd: RawFd) -> bool {
+    set_option(fd, libc::SO_TIMESTAMPING, TX_FLAGS | REPORT_FLAGS)
+}
+
+/// Ask for receive timestamps, falling back to SO_TIMESTAMPNS.
+pub fn enable_receive(fd: RawFd) -> bool {
+    set_option(fd, libc::SO_TIMESTAMPING, RX_FLAGS | REPORT_FLAGS) || set_option(fd, libc::SO_TIMESTAMPNS, 1)
+}
+
+fn micros(ts: &libc::timespec) -> Option<u128> {
+    (ts.tv_sec != 0 || ts.tv_nsec != 0).then(|| ts.tv_sec as u128 * 1_000_000 + ts.tv_nsec as u128 / 1000)
+}
+
+/// What the control messages of one `recvmsg` carried.
+#[derive(Default)]
+pub(crate) struct Ancillary {
+    pub stamp: Stamp,
+    /// Transmit timestamp ID, for messages from the error queue.
+    pub tx_id: Option<u32>,
+    /// ICMP error about the packet returned with it, from the error queue.
+    pub icmp: Option<IcmpError>,
+}
+
+/// Address in the `sockaddr` of `len` bytes at `addr`, if IPv4 or IPv6.
+unsafe fn offender(addr: *const u8, len: usize) -> Option<IpAddr> {
+    if len < mem::size_of::<libc::sa_family_t>() {
+        return None;
+    }
+    match (addr as *const libc::sa_family_t).read_unaligned() as libc::c_int {
+        libc::AF_INET if len >= mem::size_of::<libc::sockaddr_in>() => {
+            let sin = (addr as *const libc::sockaddr_in).read_unaligned();
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr))))
+        }
+        libc::AF_INET6 if len >= mem::size_of::<libc::sockaddr_in6>() => {
+            let sin6 = (addr as *const libc::sockaddr_in6).read_unaligned();
+            Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn recvmsg(fd: RawFd, buf: &mut [u8], flags: libc::c_int) -> io::Result<(usize, Ancillary)> {
+    let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() };
+    let mut control = [0u64; 64];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+
+    let n = unsafe { libc::recvmsg(fd, &mut msg, flags) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut ancillary = Ancillary::default();
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while let Some(header) = unsafe { cmsg.as_ref() } {
+        let data = unsafe { libc::CMSG_DATA(cmsg) };
+        match (header.cmsg_level, header.cmsg_type) {
+            (libc::SOL_SOCKET, libc::SCM_TIMESTAMPING) => {
+                // [software, deprecated, raw hardware]
+                let ts = unsafe { (data as *const [libc::timespec; 3]).read_unaligned() };
+                ancillary.stamp.merge(Stamp { software: micros(&ts[0]), hardware: micros(&ts[2]) });
+            }
+            (libc::SOL_SOCKET, libc::SCM_TIMESTAMPNS) => {
+                let ts = unsafe { (data as *const libc::timespec).read_unaligned() };
+                ancillary.stamp.software = micros(&ts);
+            }
+            (libc::SOL_IP, libc::IP_RECVERR) | (libc::SOL_IPV6, libc::IPV6_RECVERR) => {
+                let err = unsafe { (data as *const libc::sock_extended_err).read_unaligned() };
+                if err.ee_errno == libc::ENOMSG as u32 && err.ee_origin == libc::SO_EE_ORIGIN_TIMESTAMPING {
+                    ancillary.tx_id = Some(err.ee_data);
+                } else {
+                    // The offending router's address follows the error (SO_EE_OFFENDER).
+                    let len = header.cmsg_len - (data as usize - cmsg as usize);
+                    let offender = len
+                        .checked_sub(mem::size_of::<libc::sock_extended_err>())
+                        .and_then(|len| unsafe { offender(data.add(mem::size_of::<libc::sock_extended_err>()), len) });
+                    ancillary.icmp = errqueue::classify(&err, offender);
+                }
+            }
+            _ => {}
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+    Ok((n as usize, ancillary))
+}
+
+/// Receive one datagram with its kernel receive timestamp, if any. The
+/// socket must be non-blocking.
+pub fn recv(fd: RawFd, buf: &mut [u8]) -> io::Result<(usize, Stamp)> {
+    recvmsg(fd, buf, 0).map(|(n, ancillary)| (n, ancillary.stamp))
+}
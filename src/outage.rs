@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time;
+
+use crate::events::{Event, EventBus};
+use crate::persistence;
+use crate::source::Sources;
+
+/// Loss intervals used to compute an outage's peak loss ratio.
+const PEAK_BUCKET_MICROS: u128 = 10 * 1_000_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct OutageThresholds {
+    /// Open an outage after this many consecutive losses...
+    pub min_losses: u64,
+    /// ...or once consecutive losses span this long.
+    pub min_silence_micros: u128,
+    /// Close it after this many consecutive received packets.
+    pub recovery_packets: u64,
+}
+
+/// One outage of a path ("loopback") or ping target.
+#[derive(Debug, Clone)]
+pub struct Outage {
+    pub source: String,
+    /// Send timestamp of the first lost packet, microseconds since epoch.
+    pub start: u128,
+    /// Send timestamp of the first packet of the recovery; None while ongoing.
+    pub end: Option<u128>,
+    pub packets_sent: u64,
+    pub packets_lost: u64,
+    /// Highest loss ratio over any 10-second interval of the outage.
+    pub peak_loss: f64,
+}
+
+impl Outage {
+    pub fn duration_micros(&self, now: u128) -> u128 {
+        self.end.unwrap_or(now).saturating_sub(self.start)
+    }
+}
+
+#[derive(Debug)]
+struct Open {
+    start: u128,
+    sent: u64,
+    lost: u64,
+    peak_loss: f64,
+    bucket_start: u128,
+    bucket_sent: u64,
+    bucket_lost: u64,
+    recovery_start: u128,
+    recovered: u64,
+}
+
+impl Open {
+    fn record(&mut self, ts: u128, lost: bool) {
+        if ts.saturating_sub(self.bucket_start) >= PEAK_BUCKET_MICROS {
+            self.close_bucket();
+            self.bucket_start = ts;
+        }
+        self.sent += 1;
+        self.bucket_sent += 1;
+        if lost {
+            self.lost += 1;
+            self.bucket_lost += 1;
+        }
+    }
+
+    fn close_bucket(&mut self) {
+        if self.bucket_sent > 0 {
+            let ratio = self.bucket_lost as f64 / self.bucket_sent as f64;
+            self.peak_loss = self.peak_loss.max(ratio);
+        }
+        self.bucket_sent = 0;
+        self.bucket_lost = 0;
+    }
+}
+
+/// Streaming outage detector, fed with the final outcome of each packet in
+/// send order.
+#[derive(Debug)]
+pub struct OutageDetector {
+    thresholds: OutageThresholds,
+    streak: u64,
+    streak_start: u128,
+    open: Option<Open>,
+    /// Outages closed since the last `take_closed`; `source` is filled in there.
+    closed: Vec<Outage>,
+}
+
+impl OutageDetector {
+    pub fn new(thresholds: OutageThresholds) -> Self {
+        Self { thresholds, streak: 0, streak_start: 0, open: None, closed: Vec::new() }
+    }
+
+    pub fn record(&mut self, ts: u128, lost: bool) {
+        let t = self.thresholds;
+        if lost {
+            if self.streak == 0 {
+                self.streak_start = ts;
+            }
+            self.streak += 1;
+        } else {
+            self.streak = 0;
+        }
+
+        match &mut self.open {
+            Some(open) => {
+                open.record(ts, lost);
+                if lost {
+                    open.recovered = 0;
+                } else {
+                    if open.recovered == 0 {
+                        open.recovery_start = ts;
+                    }
+                    open.recovered += 1;
+                    if open.recovered >= t.recovery_packets {
+                        open.close_bucket();
+                        let open = self.open.take().unwrap();
+                        self.closed.push(Outage {
+                            source: String::new(),
+                            start: open.start,
+                            end: Some(open.recovery_start),
+                            packets_sent: open.sent,
+                            packets_lost: open.lost,
+                            peak_loss: open.peak_loss,
+                        });
+                    }
+                }
+            }
+            None => {
+                let span = ts.saturating_sub(self.streak_start);
+                if lost && (self.streak >= t.min_losses || span >= t.min_silence_micros) {
+                    self.open = Some(Open {
+                        start: self.streak_start,
+                        sent: self.streak,
+                        lost: self.streak,
+                        peak_loss: 0.0,
+                        bucket_start: self.streak_start,
+                        bucket_sent: self.streak,
+                        bucket_lost: self.streak,
+                        recovery_start: 0,
+                        recovered: 0,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The ongoing outage, if any, as an open-ended `Outage`.
+    pub fn current(&self, source: &str) -> Option<Outage> {
+        self.open.as_ref().map(|o| {
+            let bucket = if o.bucket_sent > 0 { o.bucket_lost as f64 / o.bucket_sent as f64 } else { 0.0 };
+            Outage {
+                source: source.to_string(),
+                start: o.start,
+                end: None,
+                packets_sent: o.sent,
+                packets_lost: o.lost,
+                peak_loss: o.peak_loss.max(bucket),
+            }
+        })
+    }
+
+    pub fn take_closed(&mut self, source: &str) -> Vec<Outage> {
+        self.closed
+            .drain(..)
+            .map(|outage| Outage { source: source.to_string(), ..outage })
+            .collect()
+    }
+}
+
+// ── Event log ─────────────────────────────────────────────────────────────────
+
+/// Closed outages of every source (persisted) plus the ongoing ones.
+#[derive(Debug, Default)]
+pub struct OutageLog {
+    pub closed: VecDeque<Outage>,
+    pub open: Vec<Outage>,
+}
+
+impl OutageLog {
+    pub fn iter(&self) -> impl Iterator<Item = &Outage> {
+        self.closed.iter().chain(self.open.iter())
+    }
+
+    /// [start, end) in Unix seconds of every outage of `source`, ongoing ones
+    /// ending now.
+    pub fn intervals(&self, source: &str, now: u128) -> Vec<(u64, u64)> {
+        self.iter()
+            .filter(|o| o.source == source)
+            .map(|o| ((o.start / 1_000_000) as u64, (o.end.unwrap_or(now) / 1_000_000) as u64))
+            .collect()
+    }
+}
+
+/// Collect outage events from every history once a second and persist the
+/// log every minute.
+pub async fn start_outage_log(
+    path: String,
+    sources: Sources,
+    log: Arc<Mutex<OutageLog>>,
+    events: EventBus,
+) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    let mut ticks: u64 = 0;
+    loop {
+        interval.tick().await;
+        let mut closed = Vec::new();
+        let mut open = Vec::new();
+        for src in sources.snapshot() {
+            let source = &src.name;
+            let mut h = src.history.lock().await;
+            let Some(detector) = h.outages() else { continue };
+            for outage in detector.take_closed(source) {
+                println!(
+                    "Outage on {} ended after {:.1}s ({} of {} packets lost)",
+                    source,
+                    outage.duration_micros(outage.start) as f64 / 1e6,
+                    outage.packets_lost,
+                    outage.packets_sent
+                );
+                let end = outage.end.unwrap_or(outage.start);
+                events.publisher(source).publish(end, Event::OutageEnded(&outage));
+                closed.push(outage);
+            }
+            open.extend(detector.current(source));
+        }
+
+        let mut log = log.lock().await;
+        for outage in &open {
+            if !log.open.iter().any(|o| o.source == outage.source) {
+                println!("Outage on {} started", outage.source);
+                events.publisher(&outage.source).publish(outage.start, Event::OutageStarted);
+            }
+        }
+        log.closed.extend(closed);
+        log.open = open;
+
+        ticks += 1;
+        if ticks.is_multiple_of(60) {
+            let cutoff = persistence::cutoff_micros().unwrap_or(0);
+            while log.closed.front().is_some_and(|o| o.start < cutoff) {
+                log.closed.pop_front();
+            }
+            persistence::save_outages(&path, &log.closed);
+        }
+    }
+}
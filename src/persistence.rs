@@ -1,27 +1,34 @@
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::collections::VecDeque;
 use std::fs::{self, File};
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::path::Path;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::time;
 
-use crate::model::Packet;
+use crate::clock;
+use crate::history::PacketHistory;
+use crate::model::{IcmpError, IcmpKind, Packet, PacketState};
+use crate::outage::Outage;
 
 // Magic headers — first byte 0xFF is safe: valid u128 timestamps always start with 0x00
-const PACKET_MAGIC: [u8; 4] = [0xFF, b'L', b'B', 1];
+const PACKET_MAGIC: [u8; 4] = [0xFF, b'L', b'B', 2];
+/// Before packet states: a latency of 1 s or more meant lost.
+const PACKET_MAGIC_V1: [u8; 4] = [0xFF, b'L', b'B', 1];
+const V1_LOST_MICROS: u64 = 1_000_000;
 const MTU_MAGIC: [u8; 4] = [0xFF, b'M', b'T', 1];
+const OUTAGE_MAGIC: [u8; 4] = [0xFF, b'O', b'E', 1];
+const ICMP_MAGIC: [u8; 4] = [0xFF, b'I', b'E', 1];
 
 const THIRTY_DAYS_MICROS: u128 = 30 * 24 * 60 * 60 * 1_000_000;
 
-fn cutoff_micros() -> u128 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_micros()
-        .saturating_sub(THIRTY_DAYS_MICROS)
+/// Start of the retention window, or None while the wall clock cannot be
+/// trusted, in which case nothing is dropped.
+pub fn cutoff_micros() -> Option<u128> {
+    clock::synchronised().then(|| clock::wall_micros().saturating_sub(THIRTY_DAYS_MICROS))
 }
 
 // ── Packet history ────────────────────────────────────────────────────────────
@@ -43,8 +50,9 @@ pub fn load(path: &str) -> VecDeque<Packet> {
     let mut reader = BufReader::new(file);
     let mut magic = [0u8; 4];
 
-    let records = match reader.read_exact(&mut magic) {
+    let mut records = match reader.read_exact(&mut magic) {
         Ok(()) if magic == PACKET_MAGIC => load_packets_new(&mut reader),
+        Ok(()) if magic == PACKET_MAGIC_V1 => load_packets_v1(&mut reader),
         Ok(()) if magic[0] == 0x00 => {
             // Old format: seek back and read (u128, u64) records
             if reader.seek(SeekFrom::Start(0)).is_err() {
@@ -55,18 +63,80 @@ pub fn load(path: &str) -> VecDeque<Packet> {
         _ => VecDeque::new(),
     };
 
+    if let Some(newest) = records.back() {
+        clock::observe_persisted(newest.timestamp);
+    }
+    if let Some(cutoff) = cutoff_micros() {
+        records.retain(|p| p.timestamp >= cutoff);
+    }
     println!("Loaded {} records from {}", records.len(), path.display());
     records
 }
 
+fn state_code(state: PacketState) -> u8 {
+    match state {
+        PacketState::Pending => 0,
+        PacketState::Received => 1,
+        PacketState::Lost => 2,
+        PacketState::Late => 3,
+        PacketState::Failed => 4,
+    }
+}
+
+fn state_from_code(code: u8) -> Option<PacketState> {
+    match code {
+        0 => Some(PacketState::Pending),
+        1 => Some(PacketState::Received),
+        2 => Some(PacketState::Lost),
+        3 => Some(PacketState::Late),
+        4 => Some(PacketState::Failed),
+        _ => None,
+    }
+}
+
+/// State of a packet saved before packet states existed.
+fn legacy_state(latency: u64) -> PacketState {
+    if latency >= V1_LOST_MICROS {
+        PacketState::Lost
+    } else {
+        PacketState::Received
+    }
+}
+
 fn load_packets_new(reader: &mut BufReader<File>) -> VecDeque<Packet> {
-    let cutoff = cutoff_micros();
     let mut records = VecDeque::new();
-    loop {
-        let timestamp = match reader.read_u128::<BigEndian>() {
+    while let Ok(timestamp) = reader.read_u128::<BigEndian>() {
+        let latency = match reader.read_u64::<BigEndian>() {
             Ok(v) => v,
             Err(_) => break,
         };
+        let size = match reader.read_u32::<BigEndian>() {
+            Ok(v) => v,
+            Err(_) => break,
+        };
+        let flags = match reader.read_u8() {
+            Ok(v) => v,
+            Err(_) => break,
+        };
+        let state = match reader.read_u8().ok().and_then(state_from_code) {
+            Some(v) => v,
+            None => break,
+        };
+        records.push_back(Packet {
+            timestamp,
+            latency,
+            size,
+            state,
+            reordered: flags & 0b01 != 0,
+            duplicate: flags & 0b10 != 0,
+        });
+    }
+    records
+}
+
+fn load_packets_v1(reader: &mut BufReader<File>) -> VecDeque<Packet> {
+    let mut records = VecDeque::new();
+    while let Ok(timestamp) = reader.read_u128::<BigEndian>() {
         let latency = match reader.read_u64::<BigEndian>() {
             Ok(v) => v,
             Err(_) => break,
@@ -79,78 +149,179 @@ fn load_packets_new(reader: &mut BufReader<File>) -> VecDeque<Packet> {
             Ok(v) => v,
             Err(_) => break,
         };
-        if timestamp >= cutoff {
-            records.push_back(Packet {
-                timestamp,
-                latency,
-                size,
-                reordered: flags & 0b01 != 0,
-                duplicate: flags & 0b10 != 0,
-            });
-        }
+        let state = legacy_state(latency);
+        records.push_back(Packet {
+            timestamp,
+            latency: if state == PacketState::Lost { 0 } else { latency },
+            size,
+            state,
+            reordered: flags & 0b01 != 0,
+            duplicate: flags & 0b10 != 0,
+        });
     }
     records
 }
 
 fn load_packets_old(reader: &mut BufReader<File>) -> VecDeque<Packet> {
-    let cutoff = cutoff_micros();
     let mut records = VecDeque::new();
-    loop {
-        let timestamp = match reader.read_u128::<BigEndian>() {
-            Ok(v) => v,
-            Err(_) => break,
-        };
+    while let Ok(timestamp) = reader.read_u128::<BigEndian>() {
         let latency = match reader.read_u64::<BigEndian>() {
             Ok(v) => v,
             Err(_) => break,
         };
-        if timestamp >= cutoff {
-            records.push_back(Packet {
-                timestamp,
-                latency,
-                size: 0, // unknown in old format
-                reordered: false,
-                duplicate: false,
-            });
-        }
+        let state = legacy_state(latency);
+        records.push_back(Packet {
+            timestamp,
+            latency: if state == PacketState::Lost { 0 } else { latency },
+            size: 0, // unknown in old format
+            state,
+            reordered: false,
+            duplicate: false,
+        });
     }
     records
 }
 
-pub fn save(path: &str, history: &VecDeque<Packet>) {
+pub fn save(path: &str, history: &PacketHistory) {
     let tmp = format!("{}.tmp", path);
     let result = (|| -> std::io::Result<()> {
         let file = File::create(&tmp)?;
         let mut writer = BufWriter::new(file);
         writer.write_all(&PACKET_MAGIC)?;
-        for p in history {
+        for p in history.iter() {
             writer.write_u128::<BigEndian>(p.timestamp)?;
             writer.write_u64::<BigEndian>(p.latency)?;
             writer.write_u32::<BigEndian>(p.size)?;
             let flags = (p.reordered as u8) | ((p.duplicate as u8) << 1);
             writer.write_u8(flags)?;
+            writer.write_u8(state_code(p.state))?;
         }
         Ok(())
     })();
     commit(result, &tmp, path);
+    save_icmp_errors(path, history);
 }
 
-pub async fn start_periodic_save(path: String, history: Arc<Mutex<VecDeque<Packet>>>) {
+pub async fn start_periodic_save(path: String, history: Arc<Mutex<PacketHistory>>) {
     let mut interval = time::interval(Duration::from_secs(60));
     loop {
         interval.tick().await;
         let mut queue = history.lock().await;
-        let cutoff = cutoff_micros();
-        let before = queue.len();
-        queue.retain(|p| p.timestamp >= cutoff);
-        let removed = before - queue.len();
-        if removed > 0 {
-            println!("Removed {} records older than 30 days from {}", removed, path);
+        if let Some(cutoff) = cutoff_micros() {
+            let removed = queue.prune_older_than(cutoff);
+            if removed > 0 {
+                println!("Removed {} records older than 30 days from {}", removed, path);
+            }
         }
         save(&path, &queue);
     }
 }
 
+// ── ICMP errors ───────────────────────────────────────────────────────────────
+//
+// Sidecar of a packet history file, `<file>.icmp`, only there while the
+// history holds errors. Record: packet timestamp u128, type u8, MTU u32 (0 for
+// none), router address family u8 (0 for none, 4 or 6) + 4 or 16 bytes.
+
+fn icmp_file(path: &str) -> String {
+    format!("{}.icmp", path)
+}
+
+fn icmp_code(kind: IcmpKind) -> u8 {
+    match kind {
+        IcmpKind::FragNeeded => 0,
+        IcmpKind::PortUnreachable => 1,
+        IcmpKind::HostUnreachable => 2,
+        IcmpKind::NetUnreachable => 3,
+        IcmpKind::TtlExceeded => 4,
+        IcmpKind::Other => 5,
+    }
+}
+
+fn icmp_from_code(code: u8) -> Option<IcmpKind> {
+    match code {
+        0 => Some(IcmpKind::FragNeeded),
+        1 => Some(IcmpKind::PortUnreachable),
+        2 => Some(IcmpKind::HostUnreachable),
+        3 => Some(IcmpKind::NetUnreachable),
+        4 => Some(IcmpKind::TtlExceeded),
+        5 => Some(IcmpKind::Other),
+        _ => None,
+    }
+}
+
+/// ICMP errors saved alongside the packet history at `path`.
+pub fn load_icmp_errors(path: &str) -> VecDeque<(u128, IcmpError)> {
+    let path = icmp_file(path);
+    let file = match File::open(&path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return VecDeque::new(),
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", path, e);
+            return VecDeque::new();
+        }
+    };
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() || magic != ICMP_MAGIC {
+        return VecDeque::new();
+    }
+    let mut records = VecDeque::new();
+    while let Ok(ts) = reader.read_u128::<BigEndian>() {
+        let Some(kind) = reader.read_u8().ok().and_then(icmp_from_code) else { break };
+        let Ok(mtu) = reader.read_u32::<BigEndian>() else { break };
+        let router = match reader.read_u8() {
+            Ok(0) => None,
+            Ok(4) => match reader.read_u32::<BigEndian>() {
+                Ok(v) => Some(IpAddr::V4(Ipv4Addr::from(v))),
+                Err(_) => break,
+            },
+            Ok(6) => match reader.read_u128::<BigEndian>() {
+                Ok(v) => Some(IpAddr::V6(Ipv6Addr::from(v))),
+                Err(_) => break,
+            },
+            _ => break,
+        };
+        records.push_back((ts, IcmpError { kind, mtu: (mtu > 0).then_some(mtu), router }));
+    }
+    records
+}
+
+fn save_icmp_errors(path: &str, history: &PacketHistory) {
+    let path = icmp_file(path);
+    if history.icmp_errors().next().is_none() {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => eprintln!("Failed to remove {}: {}", path, e),
+            _ => {}
+        }
+        return;
+    }
+    let tmp = format!("{}.tmp", path);
+    let result = (|| -> std::io::Result<()> {
+        let file = File::create(&tmp)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&ICMP_MAGIC)?;
+        for (ts, e) in history.icmp_errors() {
+            writer.write_u128::<BigEndian>(*ts)?;
+            writer.write_u8(icmp_code(e.kind))?;
+            writer.write_u32::<BigEndian>(e.mtu.unwrap_or(0))?;
+            match e.router {
+                None => writer.write_u8(0)?,
+                Some(IpAddr::V4(ip)) => {
+                    writer.write_u8(4)?;
+                    writer.write_u32::<BigEndian>(ip.into())?;
+                }
+                Some(IpAddr::V6(ip)) => {
+                    writer.write_u8(6)?;
+                    writer.write_u128::<BigEndian>(ip.into())?;
+                }
+            }
+        }
+        Ok(())
+    })();
+    commit(result, &tmp, &path);
+}
+
 // ── MTU history ───────────────────────────────────────────────────────────────
 
 pub fn load_mtu(path: &str) -> VecDeque<(u128, u32)> {
@@ -170,13 +341,9 @@ pub fn load_mtu(path: &str) -> VecDeque<(u128, u32)> {
     if reader.read_exact(&mut magic).is_err() || magic != MTU_MAGIC {
         return VecDeque::new();
     }
-    let cutoff = cutoff_micros();
+    let cutoff = cutoff_micros().unwrap_or(0);
     let mut records = VecDeque::new();
-    loop {
-        let ts = match reader.read_u128::<BigEndian>() {
-            Ok(v) => v,
-            Err(_) => break,
-        };
+    while let Ok(ts) = reader.read_u128::<BigEndian>() {
         let mtu = match reader.read_u32::<BigEndian>() {
             Ok(v) => v,
             Err(_) => break,
@@ -209,12 +376,87 @@ pub async fn start_periodic_save_mtu(path: String, history: Arc<Mutex<VecDeque<(
     loop {
         interval.tick().await;
         let mut queue = history.lock().await;
-        let cutoff = cutoff_micros();
-        queue.retain(|&(ts, _)| ts >= cutoff);
+        if let Some(cutoff) = cutoff_micros() {
+            queue.retain(|&(ts, _)| ts >= cutoff);
+        }
         save_mtu(&path, &queue);
     }
 }
 
+// ── Outage event log ──────────────────────────────────────────────────────────
+//
+// Record: start u128, end u128, packets_sent u64, packets_lost u64,
+// peak_loss f64, source length u16 + UTF-8 bytes. Only closed outages are stored.
+
+pub fn load_outages(path: &str) -> VecDeque<Outage> {
+    let path = Path::new(path);
+    if !path.exists() {
+        return VecDeque::new();
+    }
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", path.display(), e);
+            return VecDeque::new();
+        }
+    };
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() || magic != OUTAGE_MAGIC {
+        return VecDeque::new();
+    }
+    let cutoff = cutoff_micros().unwrap_or(0);
+    let mut records = VecDeque::new();
+    while let Ok(start) = reader.read_u128::<BigEndian>() {
+        let record = (|| -> std::io::Result<Outage> {
+            let end = reader.read_u128::<BigEndian>()?;
+            let packets_sent = reader.read_u64::<BigEndian>()?;
+            let packets_lost = reader.read_u64::<BigEndian>()?;
+            let peak_loss = reader.read_f64::<BigEndian>()?;
+            let len = reader.read_u16::<BigEndian>()? as usize;
+            let mut source = vec![0u8; len];
+            reader.read_exact(&mut source)?;
+            Ok(Outage {
+                source: String::from_utf8_lossy(&source).into_owned(),
+                start,
+                end: Some(end),
+                packets_sent,
+                packets_lost,
+                peak_loss,
+            })
+        })();
+        match record {
+            Ok(outage) if outage.start >= cutoff => records.push_back(outage),
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    println!("Loaded {} outage records from {}", records.len(), path.display());
+    records
+}
+
+pub fn save_outages(path: &str, outages: &VecDeque<Outage>) {
+    let tmp = format!("{}.tmp", path);
+    let result = (|| -> std::io::Result<()> {
+        let file = File::create(&tmp)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&OUTAGE_MAGIC)?;
+        for o in outages {
+            let Some(end) = o.end else { continue };
+            writer.write_u128::<BigEndian>(o.start)?;
+            writer.write_u128::<BigEndian>(end)?;
+            writer.write_u64::<BigEndian>(o.packets_sent)?;
+            writer.write_u64::<BigEndian>(o.packets_lost)?;
+            writer.write_f64::<BigEndian>(o.peak_loss)?;
+            let source = o.source.as_bytes();
+            writer.write_u16::<BigEndian>(source.len() as u16)?;
+            writer.write_all(source)?;
+        }
+        Ok(())
+    })();
+    commit(result, &tmp, path);
+}
+
 // ── Shared helper ─────────────────────────────────────────────────────────────
 
 fn commit(result: std::io::Result<()>, tmp: &str, dest: &str) {
@@ -230,3 +472,84 @@ fn commit(result: std::io::Result<()>, tmp: &str, dest: &str) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::sla::SlaThresholds;
+    use crate::outage::OutageThresholds;
+
+    #[test]
+    fn icmp_errors_round_trip_beside_the_history() {
+        let dir = std::env::temp_dir().join(format!("loopback-icmp-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.bin").to_string_lossy().into_owned();
+
+        let t0 = clock::wall_micros();
+        let thresholds = OutageThresholds { min_losses: 5, min_silence_micros: 5_000_000, recovery_packets: 5 };
+        let mut history = PacketHistory::new(VecDeque::new(), 1_000_000, thresholds, SlaThresholds::default());
+        let slots: Vec<u64> = (0..3).map(|i| history.push(Packet::pending(t0 + i * 10_000, 100), 100)).collect();
+        let errors = [
+            IcmpError { kind: IcmpKind::FragNeeded, mtu: Some(1400), router: Some("192.0.2.1".parse().unwrap()) },
+            IcmpError { kind: IcmpKind::PortUnreachable, mtu: None, router: Some("2001:db8::1".parse().unwrap()) },
+            IcmpError { kind: IcmpKind::Other, mtu: None, router: None },
+        ];
+        for (&slot, error) in slots.iter().zip(errors) {
+            history.record_icmp_error(slot, error);
+        }
+        save(&path, &history);
+
+        let mut loaded = PacketHistory::new(load(&path), 1_000_000, thresholds, SlaThresholds::default());
+        loaded.restore_icmp_errors(load_icmp_errors(&path));
+        let saved: Vec<_> = history.icmp_errors().copied().collect();
+        assert_eq!(loaded.icmp_errors().copied().collect::<Vec<_>>(), saved);
+        assert_eq!(loaded.icmp_error(t0 + 10_000), Some(&errors[1]));
+
+        // Without errors left, the sidecar goes.
+        save(&path, &PacketHistory::default());
+        assert!(!Path::new(&icmp_file(&path)).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn v1_history_loads_with_states_and_is_saved_as_v2() {
+        let dir = std::env::temp_dir().join(format!("loopback-v1-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.bin").to_string_lossy().into_owned();
+
+        // (timestamp, latency, size, flags) as v1 wrote them.
+        let t0 = clock::wall_micros();
+        let v1 = [
+            (t0, 5_000u64, 100u32, 0u8),
+            (t0 + 1, V1_LOST_MICROS, 200, 0),
+            (t0 + 2, 7_000, 300, 0b01),
+            (t0 + 3, 8_000, 400, 0b10),
+        ];
+        let mut file = PACKET_MAGIC_V1.to_vec();
+        for (timestamp, latency, size, flags) in v1 {
+            file.extend_from_slice(&timestamp.to_be_bytes());
+            file.extend_from_slice(&latency.to_be_bytes());
+            file.extend_from_slice(&size.to_be_bytes());
+            file.push(flags);
+        }
+        fs::write(&path, &file).unwrap();
+
+        let loaded = load(&path);
+        let summary = |packets: &VecDeque<Packet>| -> Vec<_> {
+            packets.iter().map(|p| (p.timestamp, p.state, p.latency, p.size, p.reordered, p.duplicate)).collect()
+        };
+        let expected = [
+            (t0, PacketState::Received, 5_000, 100, false, false),
+            (t0 + 1, PacketState::Lost, 0, 200, false, false),
+            (t0 + 2, PacketState::Received, 7_000, 300, true, false),
+            (t0 + 3, PacketState::Received, 8_000, 400, false, true),
+        ];
+        assert_eq!(summary(&loaded), expected);
+
+        let thresholds = OutageThresholds { min_losses: 5, min_silence_micros: 5_000_000, recovery_packets: 5 };
+        save(&path, &PacketHistory::new(loaded, 1_000_000, thresholds, SlaThresholds::default()));
+        assert_eq!(fs::read(&path).unwrap()[..4], PACKET_MAGIC);
+        assert_eq!(summary(&load(&path)), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
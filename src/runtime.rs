@@ -0,0 +1,213 @@
+//! State shared by the running service and its control API: the monitored
+//! sources, and what it takes to start or stop probing a ping target or a
+//! STAMP reflector.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, Notify};
+use tokio::task::AbortHandle;
+
+use crate::config::{self, Config};
+use crate::events::EventBus;
+use crate::history::PacketHistory;
+use crate::network;
+use crate::network::sender::Session;
+use crate::network::stamp::SessionHistories;
+use crate::outage::OutageLog;
+use crate::persistence;
+use crate::source::{Source, SourceControl, SourceKind, Sources};
+
+pub struct Runtime {
+    pub config: Config,
+    pub sources: Sources,
+    pub events: EventBus,
+    pub outages: Arc<Mutex<OutageLog>>,
+    /// The loopback sender's session, shared with the listener.
+    pub session: Arc<Session>,
+    /// Wakes the metrics push loop for an immediate push.
+    pub push_now: Notify,
+    /// Prober and save tasks of each ping target, aborted on removal.
+    tasks: std::sync::Mutex<HashMap<String, Vec<AbortHandle>>>,
+}
+
+impl Runtime {
+    pub fn new(
+        config: Config,
+        sources: Sources,
+        events: EventBus,
+        outages: Arc<Mutex<OutageLog>>,
+        session: Arc<Session>,
+    ) -> Self {
+        Runtime {
+            config,
+            sources,
+            events,
+            outages,
+            session,
+            push_now: Notify::new(),
+            tasks: Default::default(),
+        }
+    }
+
+    /// Packet and MTU history files of a source.
+    fn files(&self, name: &str) -> (String, String) {
+        if name == "loopback" {
+            (self.config.data_file.clone(), self.config.loopback_mtu_file())
+        } else {
+            (self.config.ping_data_file_for(name), self.config.ping_mtu_file_for(name))
+        }
+    }
+
+    /// A source with its persisted histories loaded, the files read on a
+    /// blocking thread.
+    async fn load_source(&self, name: &str, kind: SourceKind, labels: Vec<(String, String)>) -> Source {
+        let config = &self.config;
+        let (data_file, mtu_file) = self.files(name);
+        let has_mtu = !matches!(kind, SourceKind::Stamp { .. });
+        let (packets, icmp_errors, mtu_history) = tokio::task::spawn_blocking(move || {
+            let mtu_history = if has_mtu { persistence::load_mtu(&mtu_file) } else { Default::default() };
+            (persistence::load(&data_file), persistence::load_icmp_errors(&data_file), mtu_history)
+        })
+        .await
+        .unwrap_or_default();
+        let mut history = PacketHistory::new(
+            packets,
+            config.loss_timeouts.micros_for(name),
+            config.outage_thresholds,
+            config.sla_thresholds,
+        );
+        history.restore_icmp_errors(icmp_errors);
+        history.set_events(self.events.publisher(name));
+        Source {
+            name: name.to_string(),
+            labels,
+            history: Arc::new(Mutex::new(history)),
+            mtu_history: Arc::new(Mutex::new(mtu_history)),
+            control: Arc::new(SourceControl::new(
+                config.interval_millis,
+                config.max_packet_size as u32,
+                config.schedules.for_source(name),
+                config.min_packet_size as u32,
+                config.size_mode,
+            )),
+            kind,
+        }
+    }
+
+    /// Load a ping target's persisted history and start pinging it, probing
+    /// its MTU and saving its history.
+    pub async fn add_target(&self, target: &str) -> Result<(), String> {
+        if target == "loopback" || target.parse::<IpAddr>().is_err() {
+            return Err(format!("invalid ping target '{}'", target));
+        }
+        if self.sources.get(target).is_some() {
+            return Err(format!("'{}' is already monitored", target));
+        }
+        let config = &self.config;
+        let source = self.load_source(target, SourceKind::Ping, config.labels_for(target)).await;
+        if !self.sources.add(source.clone()) {
+            return Err(format!("'{}' is already monitored", target));
+        }
+
+        let (data_file, mtu_file) = self.files(target);
+        let publisher = self.events.publisher(target);
+        let handles = [
+            tokio::spawn(network::pinger::start_pinging(
+                target.to_string(),
+                config.max_queue_size,
+                Arc::clone(&source.history),
+                Arc::clone(&source.control),
+                publisher.clone(),
+            )),
+            tokio::spawn(network::mtu::start_probing_icmp(
+                target.to_string(),
+                config.min_mtu,
+                config.max_queue_size,
+                Arc::clone(&source.mtu_history),
+                Arc::clone(&source.control),
+                publisher,
+            )),
+            tokio::spawn(persistence::start_periodic_save(data_file, Arc::clone(&source.history))),
+            tokio::spawn(persistence::start_periodic_save_mtu(mtu_file, Arc::clone(&source.mtu_history))),
+        ];
+        let handles = handles.iter().map(|h| h.abort_handle()).collect();
+        self.tasks.lock().unwrap().insert(target.to_string(), handles);
+        Ok(())
+    }
+
+    /// Start a STAMP session with `reflector` (`ip:port`). The round trip
+    /// source owns the prober settings; the directional ones only hold data.
+    pub async fn add_stamp_session(&self, reflector: &str) -> Result<(), String> {
+        let addr: SocketAddr =
+            reflector.parse().map_err(|_| format!("invalid STAMP reflector '{}'", reflector))?;
+        let labels = self.config.labels_for(reflector);
+        let [round_trip, forward, reverse] = config::stamp_sources(reflector).map(|(name, direction)| {
+            let kind = SourceKind::Stamp { reflector: reflector.to_string(), direction };
+            let labels = labels.clone();
+            async move { self.load_source(&name, kind, labels).await }
+        });
+        let sources = [round_trip.await, forward.await, reverse.await];
+        if sources.iter().any(|s| self.sources.get(&s.name).is_some()) {
+            return Err(format!("STAMP session with {} already runs", reflector));
+        }
+        for source in &sources {
+            self.sources.add(source.clone());
+        }
+
+        let [round_trip, forward, reverse] = &sources;
+        let histories = SessionHistories {
+            round_trip: Arc::clone(&round_trip.history),
+            forward: Arc::clone(&forward.history),
+            reverse: Arc::clone(&reverse.history),
+        };
+        // Low bits of the start time tell this session's replies apart from
+        // those of an earlier run.
+        let ssid = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u16;
+        let mut handles = vec![tokio::spawn(network::stamp::start_session(
+            addr,
+            ssid,
+            self.config.max_queue_size,
+            histories,
+            Arc::clone(&round_trip.control),
+            self.events.publisher(&round_trip.name),
+        ))];
+        for source in &sources {
+            let (data_file, _) = self.files(&source.name);
+            handles.push(tokio::spawn(persistence::start_periodic_save(data_file, Arc::clone(&source.history))));
+        }
+        let handles = handles.iter().map(|h| h.abort_handle()).collect();
+        self.tasks.lock().unwrap().insert(round_trip.name.clone(), handles);
+        Ok(())
+    }
+
+    /// Stop monitoring a ping target, saving its history one last time.
+    pub async fn remove_target(&self, target: &str) -> Result<(), String> {
+        if self.sources.get(target).is_some_and(|s| s.kind != SourceKind::Ping) {
+            return Err(format!("'{}' is not a ping target", target));
+        }
+        let source = self.sources.remove(target).ok_or(format!("unknown source '{}'", target))?;
+        for handle in self.tasks.lock().unwrap().remove(target).unwrap_or_default() {
+            handle.abort();
+        }
+        self.save_source(&source).await;
+        Ok(())
+    }
+
+    async fn save_source(&self, source: &Source) {
+        let (data_file, mtu_file) = self.files(&source.name);
+        persistence::save(&data_file, &*source.history.lock().await);
+        if !matches!(source.kind, SourceKind::Stamp { .. }) {
+            persistence::save_mtu(&mtu_file, &*source.mtu_history.lock().await);
+        }
+    }
+
+    /// Save every history and the outage log now.
+    pub async fn save(&self) {
+        for source in self.sources.snapshot() {
+            self.save_source(&source).await;
+        }
+        persistence::save_outages(&self.config.outage_file, &self.outages.lock().await.closed);
+    }
+}
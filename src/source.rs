@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{Mutex, Notify};
+
+use crate::history::PacketHistory;
+use crate::network::schedule::Schedule;
+use crate::network::sizes::SizeMode;
+
+/// A monitored path: the UDP loopback, one ICMP ping target or one direction
+/// of a STAMP session.
+#[derive(Clone)]
+pub struct Source {
+    /// `loopback`, the ping target, or `stamp:<reflector>[:forward|:reverse]`.
+    pub name: String,
+    /// Custom labels attached to every exported series of the source.
+    pub labels: Vec<(String, String)>,
+    pub history: Arc<Mutex<PacketHistory>>,
+    /// (timestamp, path MTU) probe results.
+    pub mtu_history: Arc<Mutex<VecDeque<(u128, u32)>>>,
+    pub control: Arc<SourceControl>,
+    pub kind: SourceKind,
+}
+
+/// What a source measures, which decides how its series are named.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceKind {
+    Loopback,
+    Ping,
+    /// One view of a STAMP session: `round_trip`, `forward` or `reverse`.
+    Stamp { reflector: String, direction: &'static str },
+}
+
+impl SourceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceKind::Loopback => "loopback",
+            SourceKind::Ping => "ping",
+            SourceKind::Stamp { .. } => "stamp",
+        }
+    }
+}
+
+impl Source {
+    /// Metric name prefix and the labels that tell this source's series
+    /// apart, sorted by name.
+    pub fn series(&self) -> (&'static str, Vec<(String, String)>) {
+        let labels = match &self.kind {
+            SourceKind::Loopback => Vec::new(),
+            SourceKind::Ping => vec![("target".to_string(), self.name.clone())],
+            SourceKind::Stamp { reflector, direction } => vec![
+                ("direction".to_string(), direction.to_string()),
+                ("reflector".to_string(), reflector.clone()),
+            ],
+        };
+        (self.kind.name(), labels)
+    }
+}
+
+/// Settings of a source's probers that can change at runtime. Probers read
+/// them on every packet.
+#[derive(Debug)]
+pub struct SourceControl {
+    paused: AtomicBool,
+    interval_millis: AtomicU64,
+    packet_size: AtomicU32,
+    /// Latest MTU probe result, 0 until the first one.
+    path_mtu: AtomicU32,
+    mtu_probe: Notify,
+    /// Fixed for the prober's lifetime.
+    schedule: Schedule,
+    min_packet_size: u32,
+    size_mode: SizeMode,
+}
+
+impl SourceControl {
+    pub fn new(
+        interval_millis: u64,
+        packet_size: u32,
+        schedule: Schedule,
+        min_packet_size: u32,
+        size_mode: SizeMode,
+    ) -> Self {
+        SourceControl {
+            paused: AtomicBool::new(false),
+            interval_millis: AtomicU64::new(interval_millis),
+            packet_size: AtomicU32::new(packet_size),
+            path_mtu: AtomicU32::new(0),
+            mtu_probe: Notify::new(),
+            schedule,
+            min_packet_size,
+            size_mode,
+        }
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+
+    pub fn size_mode(&self) -> SizeMode {
+        self.size_mode
+    }
+
+    /// Smallest and largest probe size; the packet size is the largest, and
+    /// lowering it below the minimum narrows the range to that one size.
+    pub fn size_range(&self) -> (u32, u32) {
+        let max = self.packet_size();
+        (self.min_packet_size.min(max), max)
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn interval_millis(&self) -> u64 {
+        self.interval_millis.load(Ordering::Relaxed)
+    }
+
+    pub fn set_interval_millis(&self, millis: u64) {
+        self.interval_millis.store(millis, Ordering::Relaxed);
+    }
+
+    pub fn packet_size(&self) -> u32 {
+        self.packet_size.load(Ordering::Relaxed)
+    }
+
+    pub fn set_packet_size(&self, size: u32) {
+        self.packet_size.store(size, Ordering::Relaxed);
+    }
+
+    pub fn path_mtu(&self) -> Option<u32> {
+        Some(self.path_mtu.load(Ordering::Relaxed)).filter(|&mtu| mtu > 0)
+    }
+
+    pub fn set_path_mtu(&self, mtu: u32) {
+        self.path_mtu.store(mtu, Ordering::Relaxed);
+    }
+
+    /// Ask the MTU prober to run now rather than at its next interval.
+    pub fn request_mtu_probe(&self) {
+        self.mtu_probe.notify_one();
+    }
+
+    pub async fn mtu_probe_requested(&self) {
+        self.mtu_probe.notified().await;
+    }
+}
+
+/// Every monitored source, shared by the tasks that treat them alike. Ping
+/// targets can be added and removed at runtime, so consumers look sources up
+/// on every use instead of keeping their own list.
+#[derive(Clone, Default)]
+pub struct Sources(Arc<RwLock<Vec<Source>>>);
+
+impl Sources {
+    pub fn snapshot(&self) -> Vec<Source> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Source> {
+        self.0.read().unwrap().iter().find(|s| s.name == name).cloned()
+    }
+
+    /// Register a source; false if one with the same name exists.
+    pub fn add(&self, source: Source) -> bool {
+        let mut sources = self.0.write().unwrap();
+        if sources.iter().any(|s| s.name == source.name) {
+            return false;
+        }
+        sources.push(source);
+        true
+    }
+
+    pub fn remove(&self, name: &str) -> Option<Source> {
+        let mut sources = self.0.write().unwrap();
+        let index = sources.iter().position(|s| s.name == name)?;
+        Some(sources.remove(index))
+    }
+}
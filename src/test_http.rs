@@ -0,0 +1,48 @@
+//! A stand-in HTTP server for tests of the clients that post to one.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+pub struct Request {
+    /// Request line and headers, lowercased.
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+/// Listen on a free localhost port; returns the listener and its base URL.
+pub async fn bind() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+/// Accept one request, answer 200 and return it.
+pub async fn receive_one(listener: TcpListener) -> Request {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let (head_len, body_len) = loop {
+        let n = stream.read(&mut chunk).await.unwrap();
+        assert!(n > 0, "connection closed before the headers ended");
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&buf[..i]).to_ascii_lowercase();
+            let len = head
+                .lines()
+                .find_map(|l| l.strip_prefix("content-length:"))
+                .map(|v| v.trim().parse::<usize>().unwrap())
+                .unwrap();
+            break (i + 4, len);
+        }
+    };
+    while buf.len() < head_len + body_len {
+        let n = stream.read(&mut chunk).await.unwrap();
+        assert!(n > 0, "connection closed before the body ended");
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n").await.unwrap();
+    Request {
+        head: String::from_utf8_lossy(&buf[..head_len]).to_ascii_lowercase(),
+        body: buf[head_len..head_len + body_len].to_vec(),
+    }
+}